@@ -0,0 +1,90 @@
+// Generated by sanity-codegen, do not edit.
+use sanity_rs::document::{Projection, SanityDocument};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PostStatus {
+    #[serde(rename = "draft")]
+    Draft,
+    #[serde(rename = "in-review")]
+    InReview,
+}
+
+impl Projection for PostStatus {}
+
+#[derive(Debug, Clone, Serialize, Deserialize, SanityDocument)]
+#[sanity(type = "post")]
+pub struct Post {
+    #[serde(rename = "_createdAt")]
+    pub created_at: sanity_rs::types::DateTime,
+    #[serde(rename = "_id")]
+    pub id: String,
+    #[serde(rename = "_type")]
+    pub type_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<sanity_rs::types::Reference>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<BlockContent>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extras: Option<Vec<serde_json::Value>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slug: Option<sanity_rs::types::Slug>,
+    pub status: PostStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Projection)]
+pub struct BlockContentItemImage {
+    #[serde(rename = "_key")]
+    pub key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alt: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asset: Option<sanity_rs::types::Reference>,
+}
+
+#[derive(Debug, Clone)]
+pub enum BlockContentItem {
+    Block(sanity_rs::types::Block),
+    Image(BlockContentItemImage),
+    Code(Code),
+}
+
+impl Serialize for BlockContentItem {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Block(value) => sanity_rs::document::serialize_tagged("block", value, serializer),
+            Self::Image(value) => sanity_rs::document::serialize_tagged("image", value, serializer),
+            Self::Code(value) => sanity_rs::document::serialize_tagged("code", value, serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for BlockContentItem {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (tag, value) = sanity_rs::document::deserialize_tagged(deserializer)?;
+        let item = match tag.as_str() {
+            "block" => Deserialize::deserialize(value).map(Self::Block),
+            "image" => Deserialize::deserialize(value).map(Self::Image),
+            "code" => Deserialize::deserialize(value).map(Self::Code),
+            _ => return Err(serde::de::Error::unknown_variant(&tag, &["block", "image", "code"])),
+        };
+        item.map_err(serde::de::Error::custom)
+    }
+}
+
+impl Projection for BlockContentItem {}
+
+pub type BlockContent = Vec<BlockContentItem>;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Projection)]
+pub struct Code {
+    #[serde(rename = "_type")]
+    pub type_name: String,
+    pub code: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+}
+
+pub type Tags = Vec<String>;
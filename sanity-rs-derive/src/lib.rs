@@ -0,0 +1,274 @@
+//! Derive macros for `sanity-rs`
+//!
+//! Use them through the `derive` feature of `sanity-rs`, which re-exports them next to the
+//! traits they implement in `sanity_rs::document`.
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, LitStr, Meta, Token};
+
+/// Derive `Projection` and `SanityDocument` for a document struct
+///
+/// The document `_type` defaults to the struct name in lower camel case and can be set
+/// with `#[sanity(type = "post")]`. Fields accept `#[sanity(deref)]` to follow a reference,
+/// `#[sanity(rename = "name")]` to read another attribute and `#[sanity(skip)]`. Serde
+/// `rename`, `rename_all`, `flatten` and `skip` are honoured so the projection matches what
+/// serde expects.
+#[proc_macro_derive(SanityDocument, attributes(sanity))]
+pub fn derive_sanity_document(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input, true)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derive `Projection` for a nested object struct
+#[proc_macro_derive(Projection, attributes(sanity))]
+pub fn derive_projection(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input, false)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[derive(Default)]
+struct ContainerAttributes {
+    type_name: Option<String>,
+    rename_all: Option<String>,
+}
+
+#[derive(Default)]
+struct FieldAttributes {
+    /// The attribute read from the document
+    rename: Option<String>,
+    /// The key serde expects
+    serde_rename: Option<String>,
+    deref: bool,
+    flatten: bool,
+    skip: bool,
+}
+
+fn expand(input: &DeriveInput, document: bool) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let container = container_attributes(input)?;
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    name,
+                    "only structs with named fields can be projected",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "only structs with named fields can be projected",
+            ))
+        }
+    };
+
+    let mut pushes = Vec::new();
+    for field in fields {
+        let attributes = field_attributes(field)?;
+        if attributes.skip {
+            continue;
+        }
+        let ty = &field.ty;
+        if attributes.flatten {
+            pushes.push(quote! {
+                match <#ty as ::sanity_rs::document::Projection>::fields() {
+                    Some(inner) => fields.extend(inner),
+                    None => fields.push("...".to_string()),
+                }
+            });
+            continue;
+        }
+        let ident = field.ident.as_ref().expect("named field");
+        if attributes.deref && is_reference(ty) {
+            return Err(syn::Error::new_spanned(
+                ty,
+                "`#[sanity(deref)]` returns the referenced document, which does not \
+                 deserialize into a `Reference`, use the type of that document instead",
+            ));
+        }
+        let key = match attributes.serde_rename {
+            Some(rename) => rename,
+            None => rename_field(
+                ident.to_string().trim_start_matches("r#"),
+                container.rename_all.as_deref(),
+            ),
+        };
+        let groq_name = attributes.rename.unwrap_or_else(|| key.clone());
+        let deref = attributes.deref;
+        pushes.push(quote! {
+            fields.push(::sanity_rs::document::field::<#ty>(#key, #groq_name, #deref));
+        });
+    }
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let projection = quote! {
+        impl #impl_generics ::sanity_rs::document::Projection for #name #ty_generics #where_clause {
+            fn fields() -> Option<Vec<String>> {
+                let mut fields: Vec<String> = Vec::new();
+                #(#pushes)*
+                Some(fields)
+            }
+        }
+    };
+    if !document {
+        return Ok(projection);
+    }
+
+    let type_name = container
+        .type_name
+        .unwrap_or_else(|| lower_camel_case(&name.to_string()));
+    Ok(quote! {
+        #projection
+
+        impl #impl_generics ::sanity_rs::document::SanityDocument for #name #ty_generics #where_clause {
+            const TYPE_NAME: &'static str = #type_name;
+        }
+    })
+}
+
+fn container_attributes(input: &DeriveInput) -> syn::Result<ContainerAttributes> {
+    let mut attributes = ContainerAttributes::default();
+    for attr in &input.attrs {
+        if attr.path().is_ident("sanity") {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("type") {
+                    attributes.type_name = Some(meta.value()?.parse::<LitStr>()?.value());
+                    Ok(())
+                } else {
+                    Err(meta.error("unsupported sanity attribute"))
+                }
+            })?;
+        } else if attr.path().is_ident("serde") {
+            for meta in serde_metas(attr)? {
+                if let Meta::NameValue(pair) = meta {
+                    if pair.path.is_ident("rename_all") {
+                        let value = string_value(&pair.value)?;
+                        if !matches!(
+                            value.as_str(),
+                            "lowercase" | "camelCase" | "snake_case" | "PascalCase"
+                        ) {
+                            return Err(syn::Error::new_spanned(
+                                &pair.value,
+                                "unsupported rename_all rule for a sanity projection",
+                            ));
+                        }
+                        attributes.rename_all = Some(value);
+                    }
+                }
+            }
+        }
+    }
+    Ok(attributes)
+}
+
+fn field_attributes(field: &syn::Field) -> syn::Result<FieldAttributes> {
+    let mut attributes = FieldAttributes::default();
+    for attr in &field.attrs {
+        if attr.path().is_ident("sanity") {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("deref") {
+                    attributes.deref = true;
+                } else if meta.path.is_ident("skip") {
+                    attributes.skip = true;
+                } else if meta.path.is_ident("rename") {
+                    attributes.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else {
+                    return Err(meta.error("unsupported sanity attribute"));
+                }
+                Ok(())
+            })?;
+        } else if attr.path().is_ident("serde") {
+            for meta in serde_metas(attr)? {
+                match meta {
+                    Meta::Path(path) if path.is_ident("flatten") => attributes.flatten = true,
+                    Meta::Path(path)
+                        if path.is_ident("skip") || path.is_ident("skip_deserializing") =>
+                    {
+                        attributes.skip = true
+                    }
+                    Meta::NameValue(pair) if pair.path.is_ident("rename") => {
+                        attributes.serde_rename = Some(string_value(&pair.value)?);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    Ok(attributes)
+}
+
+/// Whether a field type is a `Reference`, possibly inside an `Option`, `Vec` or `Box`
+fn is_reference(ty: &syn::Type) -> bool {
+    let syn::Type::Path(path) = ty else {
+        return false;
+    };
+    let Some(segment) = path.path.segments.last() else {
+        return false;
+    };
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(arguments)
+            if ["Option", "Vec", "Box"]
+                .iter()
+                .any(|name| segment.ident == name) =>
+        {
+            arguments.args.iter().any(|argument| match argument {
+                syn::GenericArgument::Type(ty) => is_reference(ty),
+                _ => false,
+            })
+        }
+        syn::PathArguments::None => segment.ident == "Reference",
+        _ => false,
+    }
+}
+
+fn serde_metas(attr: &syn::Attribute) -> syn::Result<Punctuated<Meta, Token![,]>> {
+    attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+}
+
+fn string_value(expr: &syn::Expr) -> syn::Result<String> {
+    match expr {
+        syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Str(lit),
+            ..
+        }) => Ok(lit.value()),
+        _ => Err(syn::Error::new_spanned(expr, "expected a string literal")),
+    }
+}
+
+fn rename_field(name: &str, rule: Option<&str>) -> String {
+    match rule {
+        Some("lowercase") => name.to_lowercase(),
+        Some("camelCase") => lower_camel_case(&pascal_case(name)),
+        Some("PascalCase") => pascal_case(name),
+        _ => name.to_string(),
+    }
+}
+
+fn pascal_case(name: &str) -> String {
+    name.split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+fn lower_camel_case(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
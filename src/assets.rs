@@ -0,0 +1,245 @@
+//! Upload images and files as asset documents, and build image urls
+use serde_json::Value;
+use url::Url;
+
+use crate::client::SanityClient;
+use crate::error::RequestError;
+use crate::types::{ImageCrop, ImageHotspot};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssetKind {
+    Image,
+    File,
+}
+
+impl AssetKind {
+    fn path(&self) -> &'static str {
+        match self {
+            AssetKind::Image => "images",
+            AssetKind::File => "files",
+        }
+    }
+}
+
+/// Url of an image on the CDN, with optional transformations
+///
+/// ```
+/// use sanity_rs::assets::ImageUrl;
+///
+/// let url = ImageUrl::new("project", "production", "image-abc-1200x800-jpg").unwrap();
+/// assert_eq!(
+///     url.width(600).format("webp").url(),
+///     "https://cdn.sanity.io/images/project/production/abc-1200x800.jpg?w=600&fm=webp"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageUrl {
+    path: String,
+    params: Vec<(&'static str, String)>,
+}
+
+impl ImageUrl {
+    /// `None` when `asset_id` is not the id of an image asset
+    pub fn new(project_id: &str, dataset: &str, asset_id: &str) -> Option<Self> {
+        let path = asset_path(asset_id).filter(|path| path.starts_with("images/"))?;
+        Some(ImageUrl {
+            path: format!(
+                "images/{}/{}/{}",
+                project_id,
+                dataset,
+                path.trim_start_matches("images/")
+            ),
+            params: Vec::new(),
+        })
+    }
+
+    /// From an image field such as `{"_type": "image", "asset": {"_ref": "image-..."}}`, an
+    /// asset document or an asset id
+    ///
+    /// The `crop` of an image field becomes a `rect` and its `hotspot` the focal point.
+    ///
+    /// ```
+    /// use sanity_rs::assets::ImageUrl;
+    /// use serde_json::json;
+    ///
+    /// let image = json!({
+    ///     "asset": {"_ref": "image-abc-1000x500-jpg"},
+    ///     "crop": {"top": 0.1, "bottom": 0.1, "left": 0.2, "right": 0.0},
+    ///     "hotspot": {"x": 0.6, "y": 0.5, "width": 0.2, "height": 0.2}
+    /// });
+    /// let url = ImageUrl::from_source("project", "production", &image).unwrap();
+    /// assert_eq!(
+    ///     url.url(),
+    ///     "https://cdn.sanity.io/images/project/production/abc-1000x500.jpg?rect=200%2C50%2C800%2C400&crop=focalpoint&fp-x=0.5&fp-y=0.5"
+    /// );
+    /// ```
+    pub fn from_source(project_id: &str, dataset: &str, source: &Value) -> Option<Self> {
+        let id = match source {
+            Value::String(id) => Some(id.as_str()),
+            Value::Object(object) => object
+                .get("asset")
+                .and_then(|asset| asset.get("_ref").or_else(|| asset.get("_id")))
+                .or_else(|| object.get("_id"))
+                .and_then(Value::as_str),
+            _ => None,
+        }?;
+        let mut url = ImageUrl::new(project_id, dataset, id)?;
+        let Some((width, height)) = url.dimensions() else {
+            return Some(url);
+        };
+        let field = |name: &str| source.get(name).cloned().filter(|value| !value.is_null());
+        // The area left after the crop, in pixels
+        let (mut left, mut top, mut rect_width, mut rect_height) = (0.0, 0.0, width, height);
+        if let Some(crop) =
+            field("crop").and_then(|crop| serde_json::from_value::<ImageCrop>(crop).ok())
+        {
+            left = (crop.left * width).round();
+            top = (crop.top * height).round();
+            rect_width = (width - crop.right * width).round() - left;
+            rect_height = (height - crop.bottom * height).round() - top;
+            if rect_width > 0.0 && rect_height > 0.0 && (rect_width, rect_height) != (width, height)
+            {
+                url = url.rect(
+                    left as u32,
+                    top as u32,
+                    rect_width as u32,
+                    rect_height as u32,
+                );
+            } else {
+                (left, top, rect_width, rect_height) = (0.0, 0.0, width, height);
+            }
+        }
+        if let Some(hotspot) = field("hotspot")
+            .and_then(|hotspot| serde_json::from_value::<ImageHotspot>(hotspot).ok())
+        {
+            url = url.focal_point(
+                (hotspot.x * width - left) / rect_width,
+                (hotspot.y * height - top) / rect_height,
+            );
+        }
+        Some(url)
+    }
+
+    /// Width and height from the asset id, e.g. `1200x800` in `image-abc-1200x800-jpg`
+    fn dimensions(&self) -> Option<(f64, f64)> {
+        let file = self.path.rsplit('/').next()?;
+        let size = file.split('.').next()?.rsplit('-').next()?;
+        let (width, height) = size.split_once('x')?;
+        Some((width.parse().ok()?, height.parse().ok()?))
+    }
+
+    fn param(mut self, name: &'static str, value: String) -> Self {
+        self.params.retain(|(n, _)| *n != name);
+        self.params.push((name, value));
+        self
+    }
+
+    pub fn width(self, width: u32) -> Self {
+        self.param("w", width.to_string())
+    }
+
+    pub fn height(self, height: u32) -> Self {
+        self.param("h", height.to_string())
+    }
+
+    /// Output format, e.g. `webp`
+    pub fn format(self, format: &str) -> Self {
+        self.param("fm", format.to_string())
+    }
+
+    /// Cut out a rectangle of the source image, in pixels
+    pub fn rect(self, left: u32, top: u32, width: u32, height: u32) -> Self {
+        self.param("rect", format!("{},{},{},{}", left, top, width, height))
+    }
+
+    /// Center of the area kept when the image is cropped to a width and height, as fractions
+    /// of the image clamped to 0 to 1
+    pub fn focal_point(self, x: f64, y: f64) -> Self {
+        let fraction = |value: f64| ((value.clamp(0.0, 1.0) * 1000.0).round() / 1000.0).to_string();
+        self.param("crop", "focalpoint".to_string())
+            .param("fp-x", fraction(x))
+            .param("fp-y", fraction(y))
+    }
+
+    /// Quality from 0 to 100
+    pub fn quality(self, quality: u8) -> Self {
+        self.param("q", quality.to_string())
+    }
+
+    pub fn url(&self) -> String {
+        let mut url = Url::parse("https://cdn.sanity.io/").expect("valid base url");
+        url.set_path(&self.path);
+        if !self.params.is_empty() {
+            url.query_pairs_mut().extend_pairs(&self.params);
+        }
+        url.to_string()
+    }
+}
+
+/// `images/<hash>-<size>.<ext>` or `files/<hash>.<ext>` for an asset id
+pub(crate) fn asset_path(id: &str) -> Option<String> {
+    let (directory, rest) = if let Some(rest) = id.strip_prefix("image-") {
+        ("images", rest)
+    } else if let Some(rest) = id.strip_prefix("file-") {
+        ("files", rest)
+    } else {
+        return None;
+    };
+    let (name, extension) = rest.rsplit_once('-')?;
+    Some(format!("{}/{}.{}", directory, name, extension))
+}
+
+impl SanityClient {
+    /// Url of an image in the dataset of the client, see `ImageUrl::from_source`
+    pub fn image_url(&self, source: &Value) -> Option<ImageUrl> {
+        ImageUrl::from_source(&self.config().project_id, &self.config().dataset, source)
+    }
+
+    /// Upload an asset to the dataset of the client, returning its asset document
+    ///
+    /// The content type is guessed from the extension of `filename`.
+    pub async fn upload_asset(
+        &self,
+        kind: AssetKind,
+        bytes: Vec<u8>,
+        filename: &str,
+    ) -> Result<Value, RequestError> {
+        self.upload_asset_to(&self.config().dataset, kind, bytes, filename)
+            .await
+    }
+
+    pub(crate) async fn upload_asset_to(
+        &self,
+        dataset: &str,
+        kind: AssetKind,
+        bytes: Vec<u8>,
+        filename: &str,
+    ) -> Result<Value, RequestError> {
+        let mut url = self.endpoint(&format!("assets/{}/{}", kind.path(), dataset))?;
+        url.query_pairs_mut().append_pair("filename", filename);
+        let request = self
+            .request("POST", url)
+            .header("Content-Type", content_type(filename))
+            .body(bytes);
+        let mut response = self.send_json(request).await?;
+        match response.get_mut("document") {
+            Some(document) if document.get("_id").is_some() => Ok(document.take()),
+            _ => Err(RequestError::StringParsingError(format!(
+                "no asset document in {}",
+                response
+            ))),
+        }
+    }
+}
+
+fn content_type(filename: &str) -> &'static str {
+    match filename.rsplit('.').next().unwrap_or_default() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "pdf" => "application/pdf",
+        _ => "application/octet-stream",
+    }
+}
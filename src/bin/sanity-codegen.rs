@@ -0,0 +1,72 @@
+//! Generate Rust types from a Sanity schema export
+//!
+//! Usage: `sanity-codegen <schema.json> [output.rs] [--queries <queries.json>]`
+//!
+//! The schema export is produced with `sanity schema extract`. The optional queries file
+//! is a JSON object of named GROQ queries, e.g. `{"postQuery": "*[_type == \"post\"]"}`,
+//! for which result types are inferred. Without an output path the generated code is
+//! written to stdout.
+use std::process::ExitCode;
+
+use sanity_rs::codegen::{generate, schema::Schema, typegen};
+use sanity_rs::error::CodegenError;
+
+const USAGE: &str = "Usage: sanity-codegen <schema.json> [output.rs] [--queries <queries.json>]";
+
+fn run(input: &str, output: Option<&str>, queries: Option<&str>) -> Result<(), CodegenError> {
+    let schema = Schema::from_json(&std::fs::read_to_string(input)?)?;
+    let code = match queries {
+        Some(queries) => {
+            let queries: serde_json::Map<String, serde_json::Value> =
+                serde_json::from_str(&std::fs::read_to_string(queries)?)?;
+            let queries = queries
+                .into_iter()
+                .map(|(name, query)| match query {
+                    serde_json::Value::String(query) => Ok((name, query)),
+                    _ => Err(CodegenError::InvalidQuery(name)),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            typegen::generate(&schema, &queries)?
+        }
+        None => generate(&schema),
+    };
+    match output {
+        Some(output) => std::fs::write(output, code)?,
+        None => print!("{}", code),
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let mut positional = Vec::new();
+    let mut queries = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--queries" {
+            match args.next() {
+                Some(path) => queries = Some(path),
+                None => {
+                    eprintln!("{}", USAGE);
+                    return ExitCode::FAILURE;
+                }
+            }
+        } else {
+            positional.push(arg);
+        }
+    }
+    let (input, output) = match positional.as_slice() {
+        [input] => (input, None),
+        [input, output] => (input, Some(output.as_str())),
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+    match run(input, output, queries.as_deref()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
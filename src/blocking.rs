@@ -0,0 +1,189 @@
+//! A synchronous client for CLIs and build scripts, behind the `blocking` feature
+//!
+//! Requests are built and parsed by the async `SanityClient`, this one drives it on its own
+//! single threaded runtime. Like `reqwest::blocking`, it must not be used from within an
+//! async runtime.
+//!
+//! ```no_run
+//! use sanity_rs::blocking::SanityClient;
+//! use sanity_rs::client::QueryResult;
+//! use sanity_rs::config::SanityConfig;
+//! use serde_json::Value;
+//!
+//! let config = SanityConfig::new("project".to_string(), "production".to_string());
+//! let mut client = SanityClient::new(config).unwrap();
+//! let posts: QueryResult<Vec<Value>> = client.query(r#"*[_type == "post"]"#).unwrap().json().unwrap();
+//! ```
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use tokio::runtime::{Builder, Runtime};
+
+use crate::assets::AssetKind;
+use crate::client;
+use crate::config::SanityConfig;
+use crate::error::RequestError;
+use crate::groq::builder::BuiltQuery;
+use crate::mutation::{Mutation, MutationResult};
+use crate::orm::ORM;
+use crate::transport::{ReqwestTransport, Transport};
+
+pub struct SanityClient {
+    client: client::SanityClient,
+    runtime: Runtime,
+}
+
+impl SanityClient {
+    pub fn new(config: SanityConfig) -> Result<Self, RequestError> {
+        Self::with_transport(config, Arc::new(ReqwestTransport::default()))
+    }
+
+    /// Create a client which sends its requests through `transport`, e.g. a `MockTransport`
+    pub fn with_transport(
+        config: SanityConfig,
+        transport: Arc<dyn Transport>,
+    ) -> Result<Self, RequestError> {
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+        Ok(Self {
+            client: client::SanityClient::with_transport(config, transport)?,
+            runtime,
+        })
+    }
+
+    /// The async client doing the work, e.g. to read the pending payload
+    pub fn inner(&self) -> &client::SanityClient {
+        &self.client
+    }
+
+    /// Set the body of the request, see `ORM::send`
+    pub fn body(&mut self, body: &str) -> &mut Self {
+        self.client.body(body);
+        self
+    }
+
+    /// Send a query to the Sanity API
+    pub fn query(&mut self, body: &str) -> Result<&mut Self, RequestError> {
+        self.runtime.block_on(self.client.query(body))?;
+        Ok(self)
+    }
+
+    /// Send a query along with the values of its `$name` parameters
+    pub fn query_with_params(
+        &mut self,
+        body: &str,
+        params: &Map<String, Value>,
+    ) -> Result<&mut Self, RequestError> {
+        self.runtime
+            .block_on(self.client.query_with_params(body, params))?;
+        Ok(self)
+    }
+
+    /// Send a query composed with the GROQ builder
+    pub fn fetch(&mut self, query: &BuiltQuery) -> Result<&mut Self, RequestError> {
+        self.runtime.block_on(self.client.fetch(query))?;
+        Ok(self)
+    }
+
+    pub fn get_by_id(&mut self, id: &str) -> &mut Self {
+        self.client.get_by_id(id);
+        self
+    }
+
+    pub fn get_by_ids(&mut self, ids: &[&str]) -> &mut Self {
+        self.client.get_by_ids(ids);
+        self
+    }
+
+    /// Send the pending `get_by_id` or `get_by_ids` query with the body appended
+    pub fn send(&mut self) -> Result<&mut Self, RequestError> {
+        self.runtime.block_on(self.client.send())?;
+        Ok(self)
+    }
+
+    /// Parse the JSON response
+    pub fn json<T: DeserializeOwned>(&mut self) -> Result<T, RequestError> {
+        self.client.json()
+    }
+
+    /// Apply mutations to the dataset of the client in one transaction
+    pub fn mutate(&self, mutations: &[Mutation]) -> Result<MutationResult, RequestError> {
+        self.runtime.block_on(self.client.mutate(mutations))
+    }
+
+    /// Upload an asset to the dataset of the client, returning its asset document
+    pub fn upload_asset(
+        &self,
+        kind: AssetKind,
+        bytes: Vec<u8>,
+        filename: &str,
+    ) -> Result<Value, RequestError> {
+        self.runtime
+            .block_on(self.client.upload_asset(kind, bytes, filename))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::client::QueryResult;
+    use crate::groq::builder::{eq, Projection, Query};
+    use crate::transport::{test_config, MockTransport};
+    use serde_json::json;
+
+    fn client(mock: &Arc<MockTransport>) -> SanityClient {
+        SanityClient::with_transport(test_config(), mock.clone()).unwrap()
+    }
+
+    #[test]
+    fn queries() -> Result<(), RequestError> {
+        let mock = Arc::new(MockTransport::new().dataset(vec![
+            json!({"_id": "a", "_type": "post", "title": "A"}),
+            json!({"_id": "b", "_type": "author", "name": "B"}),
+        ]));
+        let mut client = client(&mock);
+
+        let posts: QueryResult<Vec<Value>> =
+            client.query(r#"*[_type == "post"]{title}"#)?.json()?;
+        assert_eq!(posts.result, [json!({"title": "A"})]);
+
+        let query = Query::all()
+            .filter(eq("_type", "author"))
+            .project(Projection::new().field("name"))
+            .build();
+        let authors: QueryResult<Vec<Value>> = client.fetch(&query)?.json()?;
+        assert_eq!(authors.result, [json!({"name": "B"})]);
+
+        let post: QueryResult<Value> = client.get_by_id("a").body("{_id}").send()?.json()?;
+        assert_eq!(post.result, json!({"_id": "a"}));
+        Ok(())
+    }
+
+    #[test]
+    fn mutations_and_assets() -> Result<(), RequestError> {
+        let mock = Arc::new(
+            MockTransport::new()
+                .route(
+                    "POST",
+                    "/v2022-03-07/data/mutate/production",
+                    r#"{"transactionId":"tx-1","results":[]}"#,
+                )
+                .route(
+                    "POST",
+                    "/v2022-03-07/assets/images/production",
+                    r#"{"document":{"_id":"image-abc-1x1-png","_type":"sanity.imageAsset"}}"#,
+                ),
+        );
+        let client = client(&mock);
+        let result = client.mutate(&[Mutation::Delete {
+            id: "a".to_string(),
+        }])?;
+        assert_eq!(result.transaction_id, "tx-1");
+        let asset = client.upload_asset(AssetKind::Image, b"png".to_vec(), "logo.png")?;
+        assert_eq!(asset["_id"], "image-abc-1x1-png");
+        let upload = &mock.requests()[1];
+        assert_eq!(upload.url.query(), Some("filename=logo.png"));
+        assert_eq!(upload.headers["Content-Type"], "image/png");
+        Ok(())
+    }
+}
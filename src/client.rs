@@ -1,19 +1,37 @@
 use crate::config::SanityConfig;
+use crate::groq::builder::BuiltQuery;
+use crate::transport::{Request, ReqwestTransport, Transport};
 use crate::{
     error::{RequestError, URLError},
     url::SanityURL,
 };
 
-use reqwest::Client as ReqwestClient;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use std::fmt::Display;
+use std::sync::Arc;
 use url::Url;
 
+/// Response body of the query endpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryResult<T> {
+    pub query: String,
+    pub result: T,
+    #[serde(rename = "syncTags", default)]
+    pub sync_tags: Vec<String>,
+    pub ms: u64,
+}
+
 #[allow(dead_code)]
 pub struct RequestPayload {
     /// Root url without the query body
     pub query: Url,
     /// Groq body
     pub body: Option<String>,
+    /// Query waiting for `body` before it is sent, see `ORM::send`
+    pub groq: Option<String>,
+    /// Values of the `$name` parameters of `groq`
+    pub params: Map<String, Value>,
     pub query_result: Option<String>,
 }
 
@@ -24,6 +42,8 @@ impl Default for RequestPayload {
                 .map_err(URLError::InvalidURL)
                 .unwrap(),
             body: None,
+            groq: None,
+            params: Map::new(),
             query_result: None,
         }
     }
@@ -39,7 +59,7 @@ impl RequestPayload {
 #[allow(dead_code)]
 pub struct SanityClient {
     config: SanityConfig,
-    pub client: ReqwestClient,
+    transport: Arc<dyn Transport>,
     pub payload: RequestPayload,
 }
 
@@ -47,6 +67,14 @@ impl SanityClient {
     /// Create a new instance for the SanityClient
     /// Initialize a client instance based on Configuration
     pub fn new(config: SanityConfig) -> Result<Self, RequestError> {
+        Self::with_transport(config, Arc::new(ReqwestTransport::default()))
+    }
+
+    /// Create a client which sends its requests through `transport`, e.g. a `MockTransport`
+    pub fn with_transport(
+        config: SanityConfig,
+        transport: Arc<dyn Transport>,
+    ) -> Result<Self, RequestError> {
         let url = SanityURL::new()
             .host(match &config.api_host {
                 Some(host) => host.to_string(),
@@ -59,7 +87,7 @@ impl SanityClient {
             .map_err(RequestError::URLParsingError)?;
         let mut client = Self {
             config,
-            client: ReqwestClient::new(),
+            transport,
             payload: RequestPayload::default(),
         };
         client.payload.query = url;
@@ -76,13 +104,76 @@ impl SanityClient {
 
     /// Send a query to the Sanity API
     pub async fn query(&mut self, body: &str) -> Result<&mut Self, RequestError> {
-        let query = &mut self.payload.query;
-        SanityURL::query(query, body);
-        let v = self.client.get(query.as_str()).send();
-        let v = v.await?.text().await?;
-        self.payload.query_result = Some(v);
+        SanityURL::query(&mut self.payload.query, body)?;
+        self.execute().await
+    }
+
+    /// Send a query along with the values of its `$name` parameters
+    pub async fn query_with_params(
+        &mut self,
+        body: &str,
+        params: &Map<String, Value>,
+    ) -> Result<&mut Self, RequestError> {
+        SanityURL::query_with_params(&mut self.payload.query, body, params);
+        self.execute().await
+    }
+
+    /// Send a query composed with the GROQ builder
+    pub async fn fetch(&mut self, query: &BuiltQuery) -> Result<&mut Self, RequestError> {
+        self.query_with_params(&query.query, &query.params).await
+    }
+
+    /// Send a GET request for the current url and keep the response body
+    pub(crate) async fn execute(&mut self) -> Result<&mut Self, RequestError> {
+        let request = self.request("GET", self.payload.query.clone());
+        let response = self.transport.send(request).await?;
+        self.payload.query_result = Some(response.body);
         Ok(self)
     }
+
+    /// A request carrying the token of the client, if any
+    pub(crate) fn request(&self, method: &str, url: Url) -> Request {
+        let mut request = Request::get(url);
+        request.method = method.to_string();
+        match &self.config.token {
+            Some(token) => request.header("Authorization", &format!("Bearer {}", token)),
+            None => request,
+        }
+    }
+
+    /// Url of an API endpoint on the live API, e.g. `data/export/production`
+    pub(crate) fn endpoint(&self, path: &str) -> Result<Url, RequestError> {
+        let mut url = SanityURL::new();
+        url.host(match &self.config.api_host {
+            Some(host) => host.to_string(),
+            None => "api.sanity.io".to_string(),
+        })
+        .project_id(&self.config.project_id);
+        if let Some(api_version) = &self.config.api_version {
+            url.api_version(api_version);
+        }
+        Ok(url.endpoint(path)?)
+    }
+
+    /// Send a request and parse its JSON response, statuses from 400 up are errors
+    pub(crate) async fn send_json(&self, request: Request) -> Result<Value, RequestError> {
+        let response = self.transport.send(request).await?;
+        if response.status >= 400 {
+            return Err(RequestError::ApiError {
+                status: response.status,
+                body: response.body,
+            });
+        }
+        Ok(serde_json::from_str(&response.body)?)
+    }
+
+    pub(crate) fn config(&self) -> &SanityConfig {
+        &self.config
+    }
+
+    pub(crate) fn transport(&self) -> &Arc<dyn Transport> {
+        &self.transport
+    }
 }
 
 impl Display for SanityClient {
@@ -0,0 +1,839 @@
+//! Generate Rust types from a Sanity schema export
+//!
+//! Every document becomes a struct deriving `SanityDocument`, object types become structs
+//! deriving `Projection`, and polymorphic arrays become enums tagged by `_type`.
+//! The generated code expects `serde` and `sanity-rs` with the `derive` feature.
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+
+pub mod schema;
+pub mod typegen;
+
+use schema::{ObjectAttribute, Schema, SchemaType, TypeNode};
+
+/// Schema types mapped onto the primitives in `sanity_rs::types`
+const BUILTINS: &[(&str, &str)] = &[
+    ("slug", "sanity_rs::types::Slug"),
+    ("geopoint", "sanity_rs::types::Geopoint"),
+    ("sanity.imageCrop", "sanity_rs::types::ImageCrop"),
+    ("sanity.imageHotspot", "sanity_rs::types::ImageHotspot"),
+];
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "extern", "false",
+    "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+    "return", "static", "struct", "trait", "true", "type", "unsafe", "use", "where", "while",
+    "abstract", "become", "box", "do", "final", "gen", "macro", "override", "priv", "try",
+    "typeof", "unsized", "virtual", "yield",
+];
+
+/// A Rust type used for a field
+#[derive(Debug, Clone, PartialEq)]
+pub enum RustType {
+    String,
+    Number,
+    Bool,
+    Value,
+    Option(Box<RustType>),
+    Vec(Box<RustType>),
+    /// A generated item or a path to an existing type
+    Named(String),
+}
+
+impl RustType {
+    fn named(path: &str) -> Self {
+        RustType::Named(path.to_string())
+    }
+
+    fn optional(self) -> Self {
+        match self {
+            RustType::Option(_) => self,
+            other => RustType::Option(Box::new(other)),
+        }
+    }
+
+    pub fn render(&self) -> String {
+        match self {
+            RustType::String => "String".to_string(),
+            RustType::Number => "f64".to_string(),
+            RustType::Bool => "bool".to_string(),
+            RustType::Value => "serde_json::Value".to_string(),
+            RustType::Option(inner) => format!("Option<{}>", inner.render()),
+            RustType::Vec(inner) => format!("Vec<{}>", inner.render()),
+            RustType::Named(name) => name.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    /// Rust field name
+    pub name: String,
+    /// Attribute name in the dataset
+    pub attribute: String,
+    pub ty: RustType,
+}
+
+/// A generated Rust item
+#[derive(Debug, Clone, PartialEq)]
+pub enum Item {
+    Struct {
+        name: String,
+        /// `_type` of a document, which derives `SanityDocument`
+        document: Option<String>,
+        fields: Vec<Field>,
+    },
+    /// Objects in a polymorphic array, tagged by `_type`
+    TaggedEnum {
+        name: String,
+        variants: Vec<(String, String, RustType)>,
+    },
+    /// A list of string options
+    StringEnum {
+        name: String,
+        values: Vec<String>,
+    },
+    Alias {
+        name: String,
+        ty: RustType,
+    },
+    /// A query string constant
+    Query {
+        name: String,
+        query: String,
+    },
+}
+
+impl Item {
+    pub fn name(&self) -> &str {
+        match self {
+            Item::Struct { name, .. }
+            | Item::TaggedEnum { name, .. }
+            | Item::StringEnum { name, .. }
+            | Item::Alias { name, .. }
+            | Item::Query { name, .. } => name,
+        }
+    }
+}
+
+/// Turns schema type nodes into Rust items
+pub struct Generator<'a> {
+    schema: &'a Schema,
+    items: Vec<Item>,
+    names: HashSet<String>,
+}
+
+impl<'a> Generator<'a> {
+    pub fn new(schema: &'a Schema) -> Self {
+        let mut names = HashSet::new();
+        for entry in &schema.0 {
+            names.insert(type_name(entry.name()));
+        }
+        Self {
+            schema,
+            items: Vec::new(),
+            names,
+        }
+    }
+
+    /// Generate an item for every document and type declaration in the schema
+    pub fn schema_items(&mut self) -> &mut Self {
+        for entry in &self.schema.0 {
+            if builtin(entry.name()).is_some() {
+                continue;
+            }
+            let name = type_name(entry.name());
+            match entry {
+                SchemaType::Document {
+                    name: document,
+                    attributes,
+                } => {
+                    self.struct_item(&name, attributes, Some(document.clone()), false);
+                }
+                SchemaType::Type { value, .. } => {
+                    self.declare(&name, value);
+                }
+            }
+        }
+        self
+    }
+
+    /// Generate an item named `name` for a type node
+    ///
+    /// Objects become structs, every other shape a type alias.
+    pub fn declare(&mut self, name: &str, node: &TypeNode) -> &mut Self {
+        // Free the name up so an object or enum generated for the node can take it
+        self.names.remove(name);
+        let ty = self.rust_type(node, name);
+        self.names.insert(name.to_string());
+        if ty != RustType::Named(name.to_string()) {
+            self.items.push(Item::Alias {
+                name: name.to_string(),
+                ty,
+            });
+        }
+        self
+    }
+
+    /// Reserve a name so generated helper types do not collide with it
+    pub fn reserve(&mut self, name: &str) -> &mut Self {
+        self.names.insert(name.to_string());
+        self
+    }
+
+    pub fn items(&self) -> &[Item] {
+        &self.items
+    }
+
+    /// Render the generated items as a Rust module
+    pub fn render(&self) -> String {
+        render(&self.items)
+    }
+
+    fn rust_type(&mut self, node: &TypeNode, hint: &str) -> RustType {
+        match node {
+            TypeNode::String { .. } => RustType::String,
+            TypeNode::Number { .. } => RustType::Number,
+            TypeNode::Boolean { .. } => RustType::Bool,
+            TypeNode::Null => RustType::Option(Box::new(RustType::Value)),
+            TypeNode::Unknown => RustType::Value,
+            TypeNode::Inline { name } => match builtin(name) {
+                Some(path) => RustType::named(path),
+                None if self.schema.get(name).is_some() => RustType::Named(type_name(name)),
+                None => RustType::Value,
+            },
+            TypeNode::Array { of } => {
+                RustType::Vec(Box::new(self.rust_type(of, &format!("{}Item", hint))))
+            }
+            TypeNode::Union { of } => self.union_type(of, hint),
+            TypeNode::Object { .. } => self.object_type(node, hint, false),
+        }
+    }
+
+    /// Generate a struct for an object, `variant` when it is wrapped by a tagged enum
+    fn object_type(&mut self, node: &TypeNode, hint: &str, variant: bool) -> RustType {
+        let TypeNode::Object {
+            attributes,
+            rest,
+            dereferences_to,
+        } = node
+        else {
+            return self.rust_type(node, hint);
+        };
+        if dereferences_to.is_some() {
+            return RustType::named("sanity_rs::types::Reference");
+        }
+        if let Some(path) = known_object(node) {
+            return RustType::named(path);
+        }
+        let mut attributes = attributes.clone();
+        if let Some(rest) = rest {
+            self.merge_rest(&mut attributes, rest);
+        }
+        let name = self.unique_name(hint);
+        self.struct_item(&name, &attributes, None, variant);
+        RustType::Named(name)
+    }
+
+    fn union_type(&mut self, of: &[TypeNode], hint: &str) -> RustType {
+        let members: Vec<&TypeNode> = of
+            .iter()
+            .filter(|member| **member != TypeNode::Null)
+            .collect();
+        let nullable = members.len() < of.len();
+        let ty = if members.is_empty() {
+            RustType::Value
+        } else if members.len() == 1 {
+            self.rust_type(members[0], hint)
+        } else if members
+            .iter()
+            .all(|member| matches!(member, TypeNode::String { value: Some(_) }))
+        {
+            let values = members
+                .iter()
+                .filter_map(|member| match member {
+                    TypeNode::String { value } => value.clone(),
+                    _ => None,
+                })
+                .collect();
+            let name = self.unique_name(hint);
+            self.items.push(Item::StringEnum {
+                name: name.clone(),
+                values,
+            });
+            RustType::Named(name)
+        } else if members.iter().all(|member| is_reference(member)) {
+            RustType::named("sanity_rs::types::Reference")
+        } else if members
+            .iter()
+            .all(|member| matches!(member, TypeNode::String { .. }))
+        {
+            RustType::String
+        } else if members
+            .iter()
+            .all(|member| matches!(member, TypeNode::Number { .. }))
+        {
+            RustType::Number
+        } else {
+            match self.tagged_enum(&members, hint) {
+                Some(ty) => ty,
+                None => RustType::Value,
+            }
+        };
+        if nullable {
+            ty.optional()
+        } else {
+            ty
+        }
+    }
+
+    /// Generate an enum tagged by `_type` when every member has a known tag
+    fn tagged_enum(&mut self, members: &[&TypeNode], hint: &str) -> Option<RustType> {
+        let mut tags = Vec::new();
+        for member in members {
+            // Only objects carry a `_type`, an alias of an array or a scalar cannot be tagged
+            let tag = match member {
+                TypeNode::Inline { name } => match self.schema.resolve(name) {
+                    Some(resolved @ TypeNode::Object { .. }) => {
+                        resolved.type_tag().unwrap_or(name).to_string()
+                    }
+                    _ => return None,
+                },
+                TypeNode::Object { .. } => member.type_tag()?.to_string(),
+                _ => return None,
+            };
+            if tags.contains(&tag) {
+                return None;
+            }
+            tags.push(tag);
+        }
+        let name = self.unique_name(hint);
+        let mut variants = Vec::new();
+        for (member, tag) in members.iter().zip(tags) {
+            let variant = type_name(&tag);
+            let ty = self.object_type(member, &format!("{}{}", name, variant), true);
+            variants.push((variant, tag, ty));
+        }
+        self.items.push(Item::TaggedEnum {
+            name: name.clone(),
+            variants,
+        });
+        Some(RustType::Named(name))
+    }
+
+    fn struct_item(
+        &mut self,
+        name: &str,
+        attributes: &BTreeMap<String, ObjectAttribute>,
+        document: Option<String>,
+        variant: bool,
+    ) {
+        self.names.insert(name.to_string());
+        let mut fields = Vec::new();
+        for (attribute, ObjectAttribute { value, optional }) in attributes {
+            // The `_type` of a variant is consumed by the enum tag of polymorphic arrays
+            if attribute == "_type" && variant {
+                continue;
+            }
+            let hint = format!("{}{}", name, type_name(attribute));
+            let mut ty = match attribute.as_str() {
+                "_createdAt" | "_updatedAt" if document.is_some() => {
+                    RustType::named("sanity_rs::types::DateTime")
+                }
+                _ => self.rust_type(value, &hint),
+            };
+            if *optional {
+                ty = ty.optional();
+            }
+            fields.push(Field {
+                name: field_name(attribute),
+                attribute: attribute.clone(),
+                ty,
+            });
+        }
+        self.items.push(Item::Struct {
+            name: name.to_string(),
+            document,
+            fields,
+        });
+    }
+
+    fn merge_rest(&self, attributes: &mut BTreeMap<String, ObjectAttribute>, rest: &TypeNode) {
+        let resolved = match rest {
+            TypeNode::Inline { name } => self.schema.resolve(name),
+            other => Some(other.clone()),
+        };
+        if let Some(TypeNode::Object {
+            attributes: inner, ..
+        }) = resolved
+        {
+            for (key, value) in inner {
+                attributes.entry(key).or_insert(value);
+            }
+        }
+    }
+
+    fn unique_name(&mut self, hint: &str) -> String {
+        let mut name = hint.to_string();
+        let mut suffix = 2;
+        while self.names.contains(&name) {
+            name = format!("{}{}", hint, suffix);
+            suffix += 1;
+        }
+        self.names.insert(name.clone());
+        name
+    }
+}
+
+/// Generate Rust source for every type in a schema export
+pub fn generate(schema: &Schema) -> String {
+    Generator::new(schema).schema_items().render()
+}
+
+/// Render items as a Rust module
+pub fn render(items: &[Item]) -> String {
+    let mut out = String::from(
+        "// Generated by sanity-codegen, do not edit.\n\
+         use sanity_rs::document::{Projection, SanityDocument};\n\
+         use serde::{Deserialize, Serialize};\n",
+    );
+    for item in items {
+        out.push('\n');
+        render_item(&mut out, item);
+    }
+    out
+}
+
+fn render_item(out: &mut String, item: &Item) {
+    match item {
+        Item::Struct {
+            name,
+            document,
+            fields,
+        } => {
+            match document {
+                Some(document) => {
+                    out.push_str(
+                        "#[derive(Debug, Clone, Serialize, Deserialize, SanityDocument)]\n",
+                    );
+                    let _ = writeln!(out, "#[sanity(type = {:?})]", document);
+                }
+                None => {
+                    out.push_str("#[derive(Debug, Clone, Serialize, Deserialize, Projection)]\n")
+                }
+            }
+            let _ = writeln!(out, "pub struct {} {{", name);
+            for field in fields {
+                let mut serde = Vec::new();
+                if field.name.trim_start_matches("r#") != field.attribute {
+                    serde.push(format!("rename = {:?}", field.attribute));
+                }
+                if let RustType::Option(_) = field.ty {
+                    serde.push("default".to_string());
+                    serde.push("skip_serializing_if = \"Option::is_none\"".to_string());
+                }
+                if !serde.is_empty() {
+                    let _ = writeln!(out, "    #[serde({})]", serde.join(", "));
+                }
+                let _ = writeln!(out, "    pub {}: {},", field.name, field.ty.render());
+            }
+            out.push_str("}\n");
+        }
+        Item::TaggedEnum { name, variants } => {
+            // The `_type` stays in the value handed to a variant, which serde's internal
+            // tagging would strip from documents and the primitives that keep it
+            out.push_str("#[derive(Debug, Clone)]\n");
+            let _ = writeln!(out, "pub enum {} {{", name);
+            for (variant, _, ty) in variants {
+                let _ = writeln!(out, "    {}({}),", variant, ty.render());
+            }
+            out.push_str("}\n\n");
+            let _ = writeln!(out, "impl Serialize for {} {{", name);
+            out.push_str(
+                "    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {\n",
+            );
+            out.push_str("        match self {\n");
+            for (variant, tag, _) in variants {
+                let _ = writeln!(
+                    out,
+                    "            Self::{}(value) => sanity_rs::document::serialize_tagged({:?}, value, serializer),",
+                    variant, tag
+                );
+            }
+            out.push_str("        }\n    }\n}\n\n");
+            let _ = writeln!(out, "impl<'de> Deserialize<'de> for {} {{", name);
+            out.push_str(
+                "    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {\n",
+            );
+            out.push_str(
+                "        let (tag, value) = sanity_rs::document::deserialize_tagged(deserializer)?;\n",
+            );
+            out.push_str("        let item = match tag.as_str() {\n");
+            for (variant, tag, _) in variants {
+                let _ = writeln!(
+                    out,
+                    "            {:?} => Deserialize::deserialize(value).map(Self::{}),",
+                    tag, variant
+                );
+            }
+            let tags: Vec<String> = variants
+                .iter()
+                .map(|(_, tag, _)| format!("{:?}", tag))
+                .collect();
+            let _ = writeln!(
+                out,
+                "            _ => return Err(serde::de::Error::unknown_variant(&tag, &[{}])),",
+                tags.join(", ")
+            );
+            out.push_str(
+                "        };\n        item.map_err(serde::de::Error::custom)\n    }\n}\n\n",
+            );
+            let _ = writeln!(out, "impl Projection for {} {{}}", name);
+        }
+        Item::StringEnum { name, values } => {
+            out.push_str("#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]\n");
+            let _ = writeln!(out, "pub enum {} {{", name);
+            for value in values {
+                let variant = type_name(value);
+                if &variant != value {
+                    let _ = writeln!(out, "    #[serde(rename = {:?})]", value);
+                }
+                let _ = writeln!(out, "    {},", variant);
+            }
+            out.push_str("}\n\n");
+            let _ = writeln!(out, "impl Projection for {} {{}}", name);
+        }
+        Item::Alias { name, ty } => {
+            let _ = writeln!(out, "pub type {} = {};", name, ty.render());
+        }
+        Item::Query { name, query } => {
+            let mut hashes = String::from("#");
+            while query.contains(&format!("\"{}", hashes)) {
+                hashes.push('#');
+            }
+            let _ = writeln!(
+                out,
+                "pub const {}: &str = r{}\"{}\"{};",
+                name, hashes, query, hashes
+            );
+        }
+    }
+}
+
+fn builtin(name: &str) -> Option<&'static str> {
+    BUILTINS
+        .iter()
+        .find(|(schema_name, _)| *schema_name == name)
+        .map(|(_, path)| *path)
+}
+
+fn is_reference(node: &TypeNode) -> bool {
+    matches!(
+        node,
+        TypeNode::Object {
+            dereferences_to: Some(_),
+            ..
+        }
+    )
+}
+
+/// Objects that map onto a primitive in `sanity_rs::types`
+fn known_object(node: &TypeNode) -> Option<&'static str> {
+    let TypeNode::Object { attributes, .. } = node else {
+        return None;
+    };
+    let only = |allowed: &[&str]| attributes.keys().all(|key| allowed.contains(&key.as_str()));
+    match node.type_tag() {
+        Some("block") if attributes.contains_key("children") => Some("sanity_rs::types::Block"),
+        Some("span") => Some("sanity_rs::types::Span"),
+        Some("slug") => Some("sanity_rs::types::Slug"),
+        Some("geopoint") => Some("sanity_rs::types::Geopoint"),
+        _ if attributes.contains_key("asset") => {
+            let asset = match &attributes["asset"].value {
+                TypeNode::Object {
+                    dereferences_to, ..
+                } => dereferences_to.as_deref(),
+                _ => None,
+            };
+            match asset {
+                Some("sanity.imageAsset")
+                    if only(&["_type", "_key", "asset", "hotspot", "crop", "media"]) =>
+                {
+                    Some("sanity_rs::types::Image")
+                }
+                Some("sanity.fileAsset") if only(&["_type", "_key", "asset", "media"]) => {
+                    Some("sanity_rs::types::File")
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// `blockContent` and `sanity.imageAsset` become `BlockContent` and `SanityImageAsset`
+pub fn type_name(name: &str) -> String {
+    let name: String = name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect();
+    match name.chars().next() {
+        None => "Unnamed".to_string(),
+        Some(first) if first.is_ascii_digit() => format!("Type{}", name),
+        Some(_) => name,
+    }
+}
+
+/// `publishedAt` becomes `published_at`, `_type` becomes `type_name`
+pub fn field_name(attribute: &str) -> String {
+    if attribute == "_type" {
+        return "type_name".to_string();
+    }
+    let mut name = String::new();
+    let mut previous_lower = false;
+    for c in attribute.trim_start_matches('_').chars() {
+        if c.is_ascii_uppercase() {
+            if previous_lower {
+                name.push('_');
+            }
+            name.push(c.to_ascii_lowercase());
+            previous_lower = false;
+        } else if c.is_ascii_alphanumeric() {
+            name.push(c);
+            previous_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+        } else {
+            if !name.is_empty() && !name.ends_with('_') {
+                name.push('_');
+            }
+            previous_lower = false;
+        }
+    }
+    let name = name.trim_end_matches('_').to_string();
+    match name.as_str() {
+        "" => "field".to_string(),
+        "self" | "super" | "crate" | "Self" => format!("{}_", name),
+        _ if name.starts_with(|c: char| c.is_ascii_digit()) => format!("field_{}", name),
+        _ if KEYWORDS.contains(&name.as_str()) => format!("r#{}", name),
+        _ => name,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SCHEMA: &str = r#"[
+      {
+        "name": "post",
+        "type": "document",
+        "attributes": {
+          "_id": { "type": "objectAttribute", "value": { "type": "string" } },
+          "_type": { "type": "objectAttribute", "value": { "type": "string", "value": "post" } },
+          "_createdAt": { "type": "objectAttribute", "value": { "type": "string" } },
+          "title": { "type": "objectAttribute", "value": { "type": "string" }, "optional": true },
+          "slug": { "type": "objectAttribute", "value": { "type": "inline", "name": "slug" }, "optional": true },
+          "status": {
+            "type": "objectAttribute",
+            "value": { "type": "union", "of": [
+              { "type": "string", "value": "draft" },
+              { "type": "string", "value": "in-review" }
+            ] }
+          },
+          "author": {
+            "type": "objectAttribute",
+            "value": {
+              "type": "object",
+              "attributes": {
+                "_ref": { "type": "objectAttribute", "value": { "type": "string" } },
+                "_type": { "type": "objectAttribute", "value": { "type": "string", "value": "reference" } }
+              },
+              "dereferencesTo": "author"
+            },
+            "optional": true
+          },
+          "body": { "type": "objectAttribute", "value": { "type": "inline", "name": "blockContent" }, "optional": true }
+        }
+      },
+      {
+        "name": "blockContent",
+        "type": "type",
+        "value": {
+          "type": "array",
+          "of": {
+            "type": "union",
+            "of": [
+              {
+                "type": "object",
+                "attributes": {
+                  "_key": { "type": "objectAttribute", "value": { "type": "string" } },
+                  "_type": { "type": "objectAttribute", "value": { "type": "string", "value": "block" } },
+                  "children": { "type": "objectAttribute", "value": { "type": "array", "of": { "type": "unknown" } } }
+                }
+              },
+              {
+                "type": "object",
+                "attributes": {
+                  "_key": { "type": "objectAttribute", "value": { "type": "string" } },
+                  "_type": { "type": "objectAttribute", "value": { "type": "string", "value": "image" } },
+                  "asset": {
+                    "type": "objectAttribute",
+                    "value": {
+                      "type": "object",
+                      "attributes": { "_ref": { "type": "objectAttribute", "value": { "type": "string" } } },
+                      "dereferencesTo": "sanity.imageAsset"
+                    },
+                    "optional": true
+                  },
+                  "alt": { "type": "objectAttribute", "value": { "type": "string" }, "optional": true }
+                }
+              },
+              { "type": "inline", "name": "code" }
+            ]
+          }
+        }
+      },
+      {
+        "name": "code",
+        "type": "type",
+        "value": {
+          "type": "object",
+          "attributes": {
+            "_type": { "type": "objectAttribute", "value": { "type": "string", "value": "code" } },
+            "language": { "type": "objectAttribute", "value": { "type": "string" }, "optional": true },
+            "code": { "type": "objectAttribute", "value": { "type": "string" } }
+          }
+        }
+      },
+      {
+        "name": "slug",
+        "type": "type",
+        "value": {
+          "type": "object",
+          "attributes": { "current": { "type": "objectAttribute", "value": { "type": "string" } } }
+        }
+      }
+    ]"#;
+
+    #[test]
+    fn generate_schema() {
+        let schema = Schema::from_json(SCHEMA).unwrap();
+        let expected = r#"// Generated by sanity-codegen, do not edit.
+use sanity_rs::document::{Projection, SanityDocument};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PostStatus {
+    #[serde(rename = "draft")]
+    Draft,
+    #[serde(rename = "in-review")]
+    InReview,
+}
+
+impl Projection for PostStatus {}
+
+#[derive(Debug, Clone, Serialize, Deserialize, SanityDocument)]
+#[sanity(type = "post")]
+pub struct Post {
+    #[serde(rename = "_createdAt")]
+    pub created_at: sanity_rs::types::DateTime,
+    #[serde(rename = "_id")]
+    pub id: String,
+    #[serde(rename = "_type")]
+    pub type_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<sanity_rs::types::Reference>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<BlockContent>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slug: Option<sanity_rs::types::Slug>,
+    pub status: PostStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Projection)]
+pub struct BlockContentItemImage {
+    #[serde(rename = "_key")]
+    pub key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alt: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asset: Option<sanity_rs::types::Reference>,
+}
+
+#[derive(Debug, Clone)]
+pub enum BlockContentItem {
+    Block(sanity_rs::types::Block),
+    Image(BlockContentItemImage),
+    Code(Code),
+}
+
+impl Serialize for BlockContentItem {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Block(value) => sanity_rs::document::serialize_tagged("block", value, serializer),
+            Self::Image(value) => sanity_rs::document::serialize_tagged("image", value, serializer),
+            Self::Code(value) => sanity_rs::document::serialize_tagged("code", value, serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for BlockContentItem {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (tag, value) = sanity_rs::document::deserialize_tagged(deserializer)?;
+        let item = match tag.as_str() {
+            "block" => Deserialize::deserialize(value).map(Self::Block),
+            "image" => Deserialize::deserialize(value).map(Self::Image),
+            "code" => Deserialize::deserialize(value).map(Self::Code),
+            _ => return Err(serde::de::Error::unknown_variant(&tag, &["block", "image", "code"])),
+        };
+        item.map_err(serde::de::Error::custom)
+    }
+}
+
+impl Projection for BlockContentItem {}
+
+pub type BlockContent = Vec<BlockContentItem>;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Projection)]
+pub struct Code {
+    #[serde(rename = "_type")]
+    pub type_name: String,
+    pub code: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+}
+"#;
+        assert_eq!(generate(&schema), expected);
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(type_name("blockContent"), "BlockContent");
+        assert_eq!(type_name("sanity.imageAsset"), "SanityImageAsset");
+        assert_eq!(type_name("in-review"), "InReview");
+        assert_eq!(type_name("3d"), "Type3d");
+        assert_eq!(field_name("publishedAt"), "published_at");
+        assert_eq!(field_name("_createdAt"), "created_at");
+        assert_eq!(field_name("_type"), "type_name");
+        assert_eq!(field_name("type"), "r#type");
+        assert_eq!(field_name("seoURL"), "seo_url");
+        assert_eq!(field_name("self"), "self_");
+    }
+
+    #[test]
+    fn nullable_union() {
+        let schema = Schema(vec![SchemaType::Type {
+            name: "maybeTags".to_string(),
+            value: TypeNode::union(vec![TypeNode::Null, TypeNode::array(TypeNode::string())]),
+        }]);
+        assert!(generate(&schema).ends_with("pub type MaybeTags = Option<Vec<String>>;\n"));
+    }
+}
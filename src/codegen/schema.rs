@@ -0,0 +1,299 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+use crate::error::CodegenError;
+
+/// A schema export as produced by `sanity schema extract`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Schema(pub Vec<SchemaType>);
+
+impl Schema {
+    pub fn from_json(json: &str) -> Result<Self, CodegenError> {
+        serde_json::from_str(json).map_err(CodegenError::SchemaParsingError)
+    }
+
+    /// Look up a document or type declaration by name
+    pub fn get(&self, name: &str) -> Option<&SchemaType> {
+        self.0.iter().find(|entry| entry.name() == name)
+    }
+
+    /// Every document type in the schema
+    pub fn documents(&self) -> impl Iterator<Item = (&String, &BTreeMap<String, ObjectAttribute>)> {
+        self.0.iter().filter_map(|entry| match entry {
+            SchemaType::Document { name, attributes } => Some((name, attributes)),
+            SchemaType::Type { .. } => None,
+        })
+    }
+
+    /// The type a named entry resolves to, documents resolve to their object shape
+    pub fn resolve(&self, name: &str) -> Option<TypeNode> {
+        self.get(name).map(|entry| match entry {
+            SchemaType::Document { attributes, .. } => TypeNode::Object {
+                attributes: attributes.clone(),
+                rest: None,
+                dereferences_to: None,
+            },
+            SchemaType::Type { value, .. } => value.clone(),
+        })
+    }
+}
+
+/// A top level entry of the schema export
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum SchemaType {
+    Document {
+        name: String,
+        attributes: BTreeMap<String, ObjectAttribute>,
+    },
+    Type {
+        name: String,
+        value: TypeNode,
+    },
+}
+
+impl SchemaType {
+    pub fn name(&self) -> &str {
+        match self {
+            SchemaType::Document { name, .. } | SchemaType::Type { name, .. } => name,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ObjectAttribute {
+    pub value: TypeNode,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub optional: bool,
+}
+
+impl ObjectAttribute {
+    pub fn new(value: TypeNode, optional: bool) -> Self {
+        Self { value, optional }
+    }
+}
+
+/// The shape of a value
+///
+/// Literal values are kept in `value`, e.g. the `_type` of an object.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum TypeNode {
+    String {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        value: Option<String>,
+    },
+    Number {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        value: Option<f64>,
+    },
+    Boolean {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        value: Option<bool>,
+    },
+    Null,
+    Unknown,
+    Object {
+        #[serde(default)]
+        attributes: BTreeMap<String, ObjectAttribute>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        rest: Option<Box<TypeNode>>,
+        #[serde(
+            rename = "dereferencesTo",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        dereferences_to: Option<String>,
+    },
+    Array {
+        of: Box<TypeNode>,
+    },
+    Union {
+        of: Vec<TypeNode>,
+    },
+    Inline {
+        name: String,
+    },
+}
+
+impl TypeNode {
+    pub fn string() -> Self {
+        TypeNode::String { value: None }
+    }
+
+    pub fn number() -> Self {
+        TypeNode::Number { value: None }
+    }
+
+    pub fn boolean() -> Self {
+        TypeNode::Boolean { value: None }
+    }
+
+    pub fn object(attributes: BTreeMap<String, ObjectAttribute>) -> Self {
+        TypeNode::Object {
+            attributes,
+            rest: None,
+            dereferences_to: None,
+        }
+    }
+
+    pub fn array(of: TypeNode) -> Self {
+        TypeNode::Array { of: Box::new(of) }
+    }
+
+    /// Build a union, flattening nested unions and dropping duplicates
+    pub fn union(members: Vec<TypeNode>) -> Self {
+        let mut of: Vec<TypeNode> = Vec::new();
+        for member in members {
+            let nested = match member {
+                TypeNode::Union { of } => of,
+                other => vec![other],
+            };
+            for node in nested {
+                if !of.contains(&node) {
+                    of.push(node);
+                }
+            }
+        }
+        match of.len() {
+            0 => TypeNode::Null,
+            1 => of.remove(0),
+            _ => TypeNode::Union { of },
+        }
+    }
+
+    /// The type of a JSON literal
+    pub fn literal(value: &Value) -> Self {
+        match value {
+            Value::Null => TypeNode::Null,
+            Value::Bool(value) => TypeNode::Boolean {
+                value: Some(*value),
+            },
+            Value::Number(value) => TypeNode::Number {
+                value: value.as_f64(),
+            },
+            Value::String(value) => TypeNode::String {
+                value: Some(value.clone()),
+            },
+            Value::Array(values) => TypeNode::array(TypeNode::union(
+                values.iter().map(TypeNode::literal).collect(),
+            )),
+            Value::Object(map) => TypeNode::object(literal_attributes(map)),
+        }
+    }
+
+    /// Whether a value of this type can be null
+    pub fn is_nullable(&self) -> bool {
+        match self {
+            TypeNode::Null | TypeNode::Unknown => true,
+            TypeNode::Union { of } => of.iter().any(TypeNode::is_nullable),
+            _ => false,
+        }
+    }
+
+    /// The same type without null
+    pub fn non_null(&self) -> TypeNode {
+        match self {
+            TypeNode::Union { of } => TypeNode::union(
+                of.iter()
+                    .filter(|member| **member != TypeNode::Null)
+                    .cloned()
+                    .collect(),
+            ),
+            other => other.clone(),
+        }
+    }
+
+    /// The literal `_type` of an object, if it has one
+    pub fn type_tag(&self) -> Option<&str> {
+        match self {
+            TypeNode::Object { attributes, .. } => match attributes.get("_type") {
+                Some(ObjectAttribute {
+                    value: TypeNode::String { value: Some(tag) },
+                    ..
+                }) => Some(tag),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+fn literal_attributes(map: &Map<String, Value>) -> BTreeMap<String, ObjectAttribute> {
+    map.iter()
+        .map(|(key, value)| {
+            (
+                key.clone(),
+                ObjectAttribute::new(TypeNode::literal(value), false),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_schema_export() {
+        let schema = Schema::from_json(
+            r#"[
+              {
+                "name": "post",
+                "type": "document",
+                "attributes": {
+                  "_id": { "type": "objectAttribute", "value": { "type": "string" } },
+                  "_type": { "type": "objectAttribute", "value": { "type": "string", "value": "post" } },
+                  "author": {
+                    "type": "objectAttribute",
+                    "value": {
+                      "type": "object",
+                      "attributes": {
+                        "_ref": { "type": "objectAttribute", "value": { "type": "string" } },
+                        "_type": { "type": "objectAttribute", "value": { "type": "string", "value": "reference" } }
+                      },
+                      "dereferencesTo": "author"
+                    },
+                    "optional": true
+                  }
+                }
+              },
+              { "name": "slug", "type": "type", "value": { "type": "inline", "name": "sanity.slug" } }
+            ]"#,
+        )
+        .unwrap();
+        let (name, attributes) = schema.documents().next().unwrap();
+        assert_eq!(name, "post");
+        assert!(attributes["author"].optional);
+        match &attributes["author"].value {
+            TypeNode::Object {
+                dereferences_to, ..
+            } => assert_eq!(dereferences_to.as_deref(), Some("author")),
+            other => panic!("expected an object, got {:?}", other),
+        }
+        assert_eq!(
+            schema.resolve("slug"),
+            Some(TypeNode::Inline {
+                name: "sanity.slug".to_string()
+            })
+        );
+        assert_eq!(schema.resolve("post").unwrap().type_tag(), Some("post"));
+    }
+
+    #[test]
+    fn union_flattens() {
+        let union = TypeNode::union(vec![
+            TypeNode::string(),
+            TypeNode::union(vec![TypeNode::Null, TypeNode::string()]),
+        ]);
+        assert_eq!(
+            union,
+            TypeNode::Union {
+                of: vec![TypeNode::string(), TypeNode::Null]
+            }
+        );
+        assert_eq!(TypeNode::union(vec![TypeNode::Null]), TypeNode::Null);
+    }
+}
@@ -0,0 +1,591 @@
+//! Infer the result type of GROQ queries from a schema export
+//!
+//! Every named query becomes a `const` holding the query and a `<Name>Result` type,
+//! next to the types generated for the schema itself, so a query and the struct it is
+//! deserialized into cannot drift apart.
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::schema::{ObjectAttribute, Schema, TypeNode};
+use super::{type_name, Generator, Item};
+use crate::error::CodegenError;
+use crate::groq::ast::{ArrayElement, BinaryOp, Expr, ObjectAttribute as ProjectionAttribute};
+use crate::groq::parse;
+
+/// Inline types are resolved at most this deep, which guards against cyclic aliases
+const MAX_DEPTH: usize = 16;
+
+/// Evaluates the type of GROQ expressions against a schema
+pub struct TypeEvaluator<'a> {
+    schema: &'a Schema,
+}
+
+impl<'a> TypeEvaluator<'a> {
+    pub fn new(schema: &'a Schema) -> Self {
+        Self { schema }
+    }
+
+    /// The result type of a query
+    pub fn query(&self, query: &str) -> Result<TypeNode, CodegenError> {
+        let expr = parse(query)?;
+        Ok(self.evaluate(&expr, &TypeNode::Unknown))
+    }
+
+    /// The type of `expr` evaluated with `this` as the current value
+    pub fn evaluate(&self, expr: &Expr, this: &TypeNode) -> TypeNode {
+        match expr {
+            Expr::Everything => TypeNode::array(TypeNode::union(
+                self.schema
+                    .documents()
+                    .map(|(name, _)| TypeNode::Inline { name: name.clone() })
+                    .collect(),
+            )),
+            Expr::This => this.clone(),
+            Expr::Param(_) => TypeNode::Unknown,
+            Expr::Literal(value) => TypeNode::literal(value),
+            Expr::Array(elements) => {
+                let members = elements
+                    .iter()
+                    .map(|element| match element {
+                        ArrayElement::Value(expr) => self.evaluate(expr, this),
+                        ArrayElement::Splat(expr) => match self.evaluate(expr, this) {
+                            TypeNode::Array { of } => *of,
+                            other => other,
+                        },
+                    })
+                    .collect();
+                TypeNode::array(TypeNode::union(members))
+            }
+            Expr::Object(attributes) => self.project(this, attributes, 0),
+            Expr::Attribute(name) => self.attribute(this, name, 0),
+            Expr::Group(inner) | Expr::Asc(inner) | Expr::Desc(inner) => self.evaluate(inner, this),
+            Expr::AccessAttribute(base, name) => {
+                let base = self.evaluate(base, this);
+                self.map(&base, &|node| self.attribute(node, name, 0))
+            }
+            Expr::AccessElement(base, _) => match self.evaluate(base, this) {
+                TypeNode::Array { of } => TypeNode::union(vec![*of, TypeNode::Null]),
+                TypeNode::Unknown => TypeNode::Unknown,
+                _ => TypeNode::Null,
+            },
+            Expr::Slice { base, .. } => match self.evaluate(base, this) {
+                array @ TypeNode::Array { .. } => array,
+                TypeNode::Unknown => TypeNode::Unknown,
+                _ => TypeNode::Null,
+            },
+            // A parameter may turn out to be an index or an attribute name
+            Expr::Filter(_, condition) if matches!(**condition, Expr::Param(_)) => {
+                TypeNode::Unknown
+            }
+            Expr::Filter(base, condition) => match self.evaluate(base, this) {
+                TypeNode::Array { of } => match type_filter(condition) {
+                    Some(tags) => TypeNode::array(self.narrow(&of, &tags)),
+                    None => TypeNode::Array { of },
+                },
+                TypeNode::Unknown => TypeNode::Unknown,
+                _ => TypeNode::Null,
+            },
+            Expr::ArrayTraversal(base) => match self.evaluate(base, this) {
+                // Traversing an array of arrays flattens it
+                TypeNode::Array { of } => match *of {
+                    TypeNode::Array { of } => TypeNode::Array { of },
+                    TypeNode::Union { of }
+                        if of.iter().any(|m| matches!(m, TypeNode::Array { .. })) =>
+                    {
+                        TypeNode::array(TypeNode::union(
+                            of.into_iter()
+                                .filter(|member| *member != TypeNode::Null)
+                                .map(|member| match member {
+                                    TypeNode::Array { of } => *of,
+                                    other => other,
+                                })
+                                .collect(),
+                        ))
+                    }
+                    of => TypeNode::Array { of: Box::new(of) },
+                },
+                TypeNode::Unknown => TypeNode::Unknown,
+                _ => TypeNode::Null,
+            },
+            Expr::Projection(base, attributes) => {
+                let base = self.evaluate(base, this);
+                self.map(&base, &|node| self.project(node, attributes, 0))
+            }
+            Expr::Deref(base, name) => {
+                let base = self.evaluate(base, this);
+                self.map(&base, &|node| {
+                    let target = self.deref(node, 0);
+                    match name {
+                        Some(name) => self.map(&target, &|node| self.attribute(node, name, 0)),
+                        None => target,
+                    }
+                })
+            }
+            Expr::PipeCall { base, .. } => self.evaluate(base, this),
+            Expr::FuncCall {
+                namespace,
+                name,
+                args,
+            } => self.function(namespace, name, args, this),
+            Expr::Pair(_, value) => self.evaluate(value, this),
+            Expr::Range { .. } | Expr::Parent(_) => TypeNode::Unknown,
+            Expr::And(..) | Expr::Or(..) | Expr::Not(_) => TypeNode::boolean(),
+            Expr::Neg(_) | Expr::Pos(_) => TypeNode::number(),
+            Expr::Binary(op, left, right) => match op {
+                BinaryOp::Add => {
+                    let left = self.evaluate(left, this);
+                    let right = self.evaluate(right, this);
+                    match (&left, &right) {
+                        (TypeNode::String { .. }, TypeNode::String { .. }) => TypeNode::string(),
+                        (TypeNode::Number { .. }, TypeNode::Number { .. }) => TypeNode::number(),
+                        (TypeNode::Array { of: a }, TypeNode::Array { of: b }) => {
+                            TypeNode::array(TypeNode::union(vec![*a.clone(), *b.clone()]))
+                        }
+                        _ => TypeNode::Unknown,
+                    }
+                }
+                BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem | BinaryOp::Pow => {
+                    TypeNode::number()
+                }
+                _ => TypeNode::boolean(),
+            },
+        }
+    }
+
+    /// Apply `f` to every element of an array and every member of a union
+    fn map(&self, node: &TypeNode, f: &dyn Fn(&TypeNode) -> TypeNode) -> TypeNode {
+        match node {
+            TypeNode::Array { of } => TypeNode::array(self.map(of, f)),
+            TypeNode::Union { of } => {
+                TypeNode::union(of.iter().map(|member| self.map(member, f)).collect())
+            }
+            TypeNode::Null => TypeNode::Null,
+            other => f(other),
+        }
+    }
+
+    fn resolve(&self, node: &TypeNode, depth: usize) -> TypeNode {
+        match node {
+            TypeNode::Inline { name } if depth < MAX_DEPTH => match self.schema.resolve(name) {
+                Some(resolved) => self.resolve(&resolved, depth + 1),
+                None => TypeNode::Unknown,
+            },
+            other => other.clone(),
+        }
+    }
+
+    fn attribute(&self, node: &TypeNode, name: &str, depth: usize) -> TypeNode {
+        match self.resolve(node, depth) {
+            TypeNode::Object {
+                attributes, rest, ..
+            } => match attributes.get(name) {
+                Some(ObjectAttribute { value, optional }) => {
+                    if *optional {
+                        TypeNode::union(vec![value.clone(), TypeNode::Null])
+                    } else {
+                        value.clone()
+                    }
+                }
+                None => match rest {
+                    Some(rest) if depth < MAX_DEPTH => self.attribute(&rest, name, depth + 1),
+                    _ => TypeNode::Null,
+                },
+            },
+            TypeNode::Union { of } => TypeNode::union(
+                of.iter()
+                    .map(|member| self.attribute(member, name, depth + 1))
+                    .collect(),
+            ),
+            TypeNode::Unknown => TypeNode::Unknown,
+            _ => TypeNode::Null,
+        }
+    }
+
+    fn deref(&self, node: &TypeNode, depth: usize) -> TypeNode {
+        match self.resolve(node, depth) {
+            TypeNode::Object {
+                dereferences_to: Some(target),
+                ..
+            } => TypeNode::union(vec![TypeNode::Inline { name: target }, TypeNode::Null]),
+            TypeNode::Union { of } => TypeNode::union(
+                of.iter()
+                    .map(|member| self.deref(member, depth + 1))
+                    .collect(),
+            ),
+            TypeNode::Unknown => TypeNode::Unknown,
+            _ => TypeNode::Null,
+        }
+    }
+
+    fn project(
+        &self,
+        node: &TypeNode,
+        attributes: &[ProjectionAttribute],
+        depth: usize,
+    ) -> TypeNode {
+        let resolved = self.resolve(node, depth);
+        if let TypeNode::Union { of } = &resolved {
+            return TypeNode::union(
+                of.iter()
+                    .map(|member| self.project(member, attributes, depth + 1))
+                    .collect(),
+            );
+        }
+        if !matches!(resolved, TypeNode::Object { .. } | TypeNode::Unknown) {
+            return TypeNode::Null;
+        }
+        let mut result = BTreeMap::new();
+        for attribute in attributes {
+            match attribute {
+                ProjectionAttribute::Value(name, expr) => {
+                    let value = self.evaluate(expr, node);
+                    result.insert(name.clone(), ObjectAttribute::new(value, false));
+                }
+                ProjectionAttribute::Splat(expr) => {
+                    let value = self.evaluate(expr, node);
+                    result.extend(self.object_attributes(&value, false));
+                }
+                ProjectionAttribute::Conditional(condition, expr) => {
+                    // The attributes are only there when the condition holds
+                    let value = self.evaluate(expr, node);
+                    let attributes = match type_filter(condition) {
+                        Some(tags) if self.narrow(node, &tags) == TypeNode::Null => BTreeMap::new(),
+                        _ => self.object_attributes(&value, true),
+                    };
+                    for (name, attribute) in attributes {
+                        result.entry(name).or_insert(attribute);
+                    }
+                }
+            }
+        }
+        TypeNode::object(result)
+    }
+
+    fn object_attributes(
+        &self,
+        node: &TypeNode,
+        optional: bool,
+    ) -> BTreeMap<String, ObjectAttribute> {
+        match self.resolve(node, 0) {
+            TypeNode::Object { attributes, .. } => attributes
+                .into_iter()
+                .map(|(name, attribute)| {
+                    let optional = attribute.optional || optional;
+                    (name, ObjectAttribute::new(attribute.value, optional))
+                })
+                .collect(),
+            _ => BTreeMap::new(),
+        }
+    }
+
+    /// Keep the members of a union whose `_type` is one of `tags`
+    fn narrow(&self, node: &TypeNode, tags: &BTreeSet<String>) -> TypeNode {
+        let members = match node {
+            TypeNode::Union { of } => of.clone(),
+            other => vec![other.clone()],
+        };
+        TypeNode::union(
+            members
+                .into_iter()
+                .filter(|member| match self.resolve(member, 0).type_tag() {
+                    Some(tag) => tags.contains(tag),
+                    None => true,
+                })
+                .collect(),
+        )
+    }
+
+    fn function(&self, namespace: &str, name: &str, args: &[Expr], this: &TypeNode) -> TypeNode {
+        let arg = |index: usize| {
+            args.get(index)
+                .map(|arg| self.evaluate(arg, this))
+                .unwrap_or(TypeNode::Null)
+        };
+        match (namespace, name) {
+            // `count` is null unless it is given an array
+            ("global", "count") => match self.resolve(&arg(0), 0) {
+                TypeNode::Array { .. } => TypeNode::number(),
+                _ => TypeNode::union(vec![TypeNode::number(), TypeNode::Null]),
+            },
+            ("global", "length") | ("global", "round") => {
+                TypeNode::union(vec![TypeNode::number(), TypeNode::Null])
+            }
+            ("global", "defined") | ("global", "references") | ("global", "boost") => {
+                TypeNode::boolean()
+            }
+            ("global", "coalesce") => {
+                let mut members = Vec::new();
+                for index in 0..args.len() {
+                    let value = arg(index);
+                    if !value.is_nullable() {
+                        members.push(value);
+                        return TypeNode::union(members);
+                    }
+                    members.push(value.non_null());
+                }
+                members.push(TypeNode::Null);
+                TypeNode::union(members)
+            }
+            ("global", "select") => {
+                let mut members = Vec::new();
+                let mut fallback = false;
+                for arg in args {
+                    match arg {
+                        Expr::Pair(_, value) => members.push(self.evaluate(value, this)),
+                        other => {
+                            members.push(self.evaluate(other, this));
+                            fallback = true;
+                            break;
+                        }
+                    }
+                }
+                if !fallback {
+                    members.push(TypeNode::Null);
+                }
+                TypeNode::union(members)
+            }
+            ("global", "lower")
+            | ("global", "upper")
+            | ("global", "string")
+            | ("global", "identity")
+            | ("global", "path")
+            | ("global", "now")
+            | ("global", "dateTime")
+            | ("pt", "text")
+            | ("array", "join")
+            | ("sanity", "projectId")
+            | ("sanity", "dataset") => TypeNode::string(),
+            ("string", "startsWith") => TypeNode::boolean(),
+            ("string", "split") => TypeNode::array(TypeNode::string()),
+            ("array", "compact") | ("array", "unique") => arg(0).non_null(),
+            ("math", _) => TypeNode::union(vec![TypeNode::number(), TypeNode::Null]),
+            _ => TypeNode::Unknown,
+        }
+    }
+}
+
+/// The `_type` values a filter condition restricts documents to
+fn type_filter(condition: &Expr) -> Option<BTreeSet<String>> {
+    fn tag(expr: &Expr) -> Option<String> {
+        match expr {
+            Expr::Literal(serde_json::Value::String(value)) => Some(value.clone()),
+            _ => None,
+        }
+    }
+    let is_type = |expr: &Expr| matches!(expr, Expr::Attribute(name) if name == "_type");
+    match condition {
+        Expr::Binary(BinaryOp::Eq, left, right) if is_type(left) => tag(right).map(|t| [t].into()),
+        Expr::Binary(BinaryOp::Eq, left, right) if is_type(right) => tag(left).map(|t| [t].into()),
+        Expr::Binary(BinaryOp::In, left, right) if is_type(left) => match right.as_ref() {
+            Expr::Array(elements) => elements
+                .iter()
+                .map(|element| match element {
+                    ArrayElement::Value(expr) => tag(expr),
+                    ArrayElement::Splat(_) => None,
+                })
+                .collect(),
+            _ => None,
+        },
+        Expr::Group(inner) => type_filter(inner),
+        Expr::And(left, right) => match (type_filter(left), type_filter(right)) {
+            (Some(left), Some(right)) => Some(left.intersection(&right).cloned().collect()),
+            (Some(tags), None) | (None, Some(tags)) => Some(tags),
+            (None, None) => None,
+        },
+        Expr::Or(left, right) => match (type_filter(left), type_filter(right)) {
+            (Some(left), Some(right)) => Some(left.union(&right).cloned().collect()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Generate the schema types plus a result type and query constant for every named query
+pub fn generate(schema: &Schema, queries: &[(String, String)]) -> Result<String, CodegenError> {
+    let evaluator = TypeEvaluator::new(schema);
+    let mut generator = Generator::new(schema);
+    generator.schema_items();
+    for (name, _) in queries {
+        generator.reserve(&result_name(name));
+    }
+    let mut constants = Vec::new();
+    for (name, query) in queries {
+        let node = evaluator.query(query)?;
+        generator.declare(&result_name(name), &node);
+        constants.push(Item::Query {
+            name: constant_name(name),
+            query: query.clone(),
+        });
+    }
+    let mut items = generator.items().to_vec();
+    items.extend(constants);
+    Ok(super::render(&items))
+}
+
+/// `postQuery` becomes `PostQueryResult`
+fn result_name(name: &str) -> String {
+    format!("{}Result", type_name(name))
+}
+
+/// `postQuery` becomes `POST_QUERY`
+fn constant_name(name: &str) -> String {
+    super::field_name(name)
+        .trim_start_matches("r#")
+        .to_uppercase()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SCHEMA: &str = r#"[
+      {
+        "name": "post",
+        "type": "document",
+        "attributes": {
+          "_id": { "type": "objectAttribute", "value": { "type": "string" } },
+          "_type": { "type": "objectAttribute", "value": { "type": "string", "value": "post" } },
+          "title": { "type": "objectAttribute", "value": { "type": "string" }, "optional": true },
+          "tags": { "type": "objectAttribute", "value": { "type": "array", "of": { "type": "string" } }, "optional": true },
+          "author": {
+            "type": "objectAttribute",
+            "value": {
+              "type": "object",
+              "attributes": {
+                "_ref": { "type": "objectAttribute", "value": { "type": "string" } },
+                "_type": { "type": "objectAttribute", "value": { "type": "string", "value": "reference" } }
+              },
+              "dereferencesTo": "author"
+            },
+            "optional": true
+          }
+        }
+      },
+      {
+        "name": "author",
+        "type": "document",
+        "attributes": {
+          "_id": { "type": "objectAttribute", "value": { "type": "string" } },
+          "_type": { "type": "objectAttribute", "value": { "type": "string", "value": "author" } },
+          "name": { "type": "objectAttribute", "value": { "type": "string" } }
+        }
+      }
+    ]"#;
+
+    fn evaluate(query: &str) -> TypeNode {
+        let schema = Schema::from_json(SCHEMA).unwrap();
+        TypeEvaluator::new(&schema).query(query).unwrap()
+    }
+
+    fn nullable(node: TypeNode) -> TypeNode {
+        TypeNode::union(vec![node, TypeNode::Null])
+    }
+
+    #[test]
+    fn infer_filters() {
+        assert_eq!(
+            evaluate(r#"*[_type == "post"]"#),
+            TypeNode::array(TypeNode::Inline {
+                name: "post".to_string()
+            })
+        );
+        assert_eq!(
+            evaluate(r#"*[_type == "author" && defined(name)][0]"#),
+            nullable(TypeNode::Inline {
+                name: "author".to_string()
+            })
+        );
+        assert_eq!(
+            evaluate(r#"*[_type == "post"].tags[]"#),
+            TypeNode::array(TypeNode::string())
+        );
+        assert_eq!(
+            evaluate(r#"count(*[_type in ["post", "author"]])"#),
+            TypeNode::number()
+        );
+    }
+
+    #[test]
+    fn infer_projections() {
+        let node = evaluate(
+            r#"*[_type == "post"][0]{
+                title,
+                "authorName": author->name,
+                "label": coalesce(title, "Untitled"),
+                "tagCount": count(tags),
+                _type == "post" => { tags }
+            }"#,
+        );
+        let mut attributes = BTreeMap::new();
+        attributes.insert(
+            "authorName".to_string(),
+            ObjectAttribute::new(nullable(TypeNode::string()), false),
+        );
+        attributes.insert(
+            "label".to_string(),
+            ObjectAttribute::new(
+                TypeNode::union(vec![
+                    TypeNode::string(),
+                    TypeNode::String {
+                        value: Some("Untitled".to_string()),
+                    },
+                ]),
+                false,
+            ),
+        );
+        attributes.insert(
+            "tagCount".to_string(),
+            ObjectAttribute::new(nullable(TypeNode::number()), false),
+        );
+        attributes.insert(
+            "tags".to_string(),
+            ObjectAttribute::new(nullable(TypeNode::array(TypeNode::string())), true),
+        );
+        attributes.insert(
+            "title".to_string(),
+            ObjectAttribute::new(nullable(TypeNode::string()), false),
+        );
+        assert_eq!(node, nullable(TypeNode::object(attributes)));
+    }
+
+    #[test]
+    fn generate_query_types() {
+        let schema = Schema::from_json(SCHEMA).unwrap();
+        let queries = vec![
+            (
+                "postsQuery".to_string(),
+                r#"*[_type == "post"]{ _id, _type, title, author->{ name } }"#.to_string(),
+            ),
+            (
+                "authorQuery".to_string(),
+                r#"*[_type == "author" && _id == $id][0]"#.to_string(),
+            ),
+        ];
+        let code = generate(&schema, &queries).unwrap();
+        let expected = r##"#[derive(Debug, Clone, Serialize, Deserialize, Projection)]
+pub struct PostsQueryResultItemAuthor {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Projection)]
+pub struct PostsQueryResultItem {
+    #[serde(rename = "_id")]
+    pub id: String,
+    #[serde(rename = "_type")]
+    pub type_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<PostsQueryResultItemAuthor>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+pub type PostsQueryResult = Vec<PostsQueryResultItem>;
+
+pub type AuthorQueryResult = Option<Author>;
+
+pub const POSTS_QUERY: &str = r#"*[_type == "post"]{ _id, _type, title, author->{ name } }"#;
+
+pub const AUTHOR_QUERY: &str = r#"*[_type == "author" && _id == $id][0]"#;
+"##;
+        assert!(code.ends_with(expected), "{}", code);
+    }
+}
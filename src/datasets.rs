@@ -0,0 +1,424 @@
+//! Manage the datasets and dataset aliases of the project, this needs a token with
+//! administrator rights
+//!
+//! ```no_run
+//! # use std::time::Duration;
+//! # use sanity_rs::client::SanityClient;
+//! # use sanity_rs::datasets::AclMode;
+//! # use sanity_rs::error::RequestError;
+//! # async fn preview(client: &SanityClient) -> Result<(), RequestError> {
+//! let datasets = client.datasets();
+//! let job = datasets.copy("production", "pr-42", true).await?;
+//! datasets.wait_for_job(&job.job_id, Duration::from_secs(2), 150, tokio::time::sleep).await?;
+//! datasets.link_alias("preview", "pr-42").await?;
+//! # datasets.create("scratch", AclMode::Private).await?;
+//! # Ok(())
+//! # }
+//! ```
+use std::future::Future;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::client::SanityClient;
+use crate::error::RequestError;
+
+/// Who can read the documents of a dataset
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AclMode {
+    /// Anyone can query published documents
+    Public,
+    /// Only authenticated requests can query
+    Private,
+    /// Kept for datasets created before private ones existed
+    Custom,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Dataset {
+    pub name: String,
+    pub acl_mode: AclMode,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub created_by_user_id: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<Value>,
+}
+
+/// An alias pointing at a dataset, `dataset_name` is `None` while unlinked
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Alias {
+    pub name: String,
+    pub dataset_name: Option<String>,
+}
+
+/// A started dataset copy
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CopyJob {
+    pub job_id: String,
+    #[serde(default)]
+    pub dataset_name: Option<String>,
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Pending,
+    Running,
+    Completed,
+    Failed,
+}
+
+/// Status of a job such as a dataset copy
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Job {
+    pub id: String,
+    pub state: JobState,
+    /// Percentage done, when the API reports it
+    #[serde(default)]
+    pub progress: Option<f64>,
+}
+
+/// Dataset administration for the project of a client, created with `SanityClient::datasets`
+pub struct Datasets<'a> {
+    client: &'a SanityClient,
+}
+
+impl SanityClient {
+    pub fn datasets(&self) -> Datasets<'_> {
+        Datasets { client: self }
+    }
+}
+
+impl Datasets<'_> {
+    pub async fn list(&self) -> Result<Vec<Dataset>, RequestError> {
+        let datasets = self.call("GET", "datasets", None).await?;
+        Ok(serde_json::from_value(datasets)?)
+    }
+
+    pub async fn create(&self, name: &str, acl_mode: AclMode) -> Result<(), RequestError> {
+        validate_name(name)?;
+        let body = json!({ "aclMode": acl_mode });
+        self.call("PUT", &format!("datasets/{}", name), Some(body))
+            .await?;
+        Ok(())
+    }
+
+    /// Delete a dataset and all of its documents, this cannot be undone
+    pub async fn delete(&self, name: &str) -> Result<(), RequestError> {
+        validate_name(name)?;
+        self.call("DELETE", &format!("datasets/{}", name), None)
+            .await?;
+        Ok(())
+    }
+
+    /// Start copying `source` into a new dataset `target`, optionally without document history
+    pub async fn copy(
+        &self,
+        source: &str,
+        target: &str,
+        skip_history: bool,
+    ) -> Result<CopyJob, RequestError> {
+        validate_name(source)?;
+        validate_name(target)?;
+        let body = json!({ "targetDataset": target, "skipHistory": skip_history });
+        let job = self
+            .call("PUT", &format!("datasets/{}/copy", source), Some(body))
+            .await?;
+        Ok(serde_json::from_value(job)?)
+    }
+
+    pub async fn job(&self, job_id: &str) -> Result<Job, RequestError> {
+        let job = self.call("GET", &format!("jobs/{}", job_id), None).await?;
+        Ok(serde_json::from_value(job)?)
+    }
+
+    /// Poll a job every `interval` until it completes, a failed job is an error and so is one
+    /// still running after `max_attempts` polls
+    ///
+    /// `sleep` comes from the async runtime, e.g. `tokio::time::sleep`.
+    pub async fn wait_for_job<F, S>(
+        &self,
+        job_id: &str,
+        interval: Duration,
+        max_attempts: usize,
+        sleep: S,
+    ) -> Result<Job, RequestError>
+    where
+        S: Fn(Duration) -> F,
+        F: Future<Output = ()>,
+    {
+        for attempt in 1..=max_attempts {
+            let job = self.job(job_id).await?;
+            match job.state {
+                JobState::Completed => return Ok(job),
+                JobState::Failed => {
+                    return Err(RequestError::JobFailed(job_id.to_string()));
+                }
+                JobState::Pending | JobState::Running if attempt < max_attempts => {
+                    sleep(interval).await
+                }
+                JobState::Pending | JobState::Running => {}
+            }
+        }
+        Err(RequestError::JobTimedOut {
+            job_id: job_id.to_string(),
+            attempts: max_attempts,
+        })
+    }
+
+    pub async fn aliases(&self) -> Result<Vec<Alias>, RequestError> {
+        let aliases = self.call("GET", "aliases", None).await?;
+        Ok(serde_json::from_value(aliases)?)
+    }
+
+    /// Create an alias, linked to `dataset` if given
+    pub async fn create_alias(
+        &self,
+        name: &str,
+        dataset: Option<&str>,
+    ) -> Result<(), RequestError> {
+        let name = alias_name(name)?;
+        if let Some(dataset) = dataset {
+            validate_name(dataset)?;
+        }
+        let body = dataset.map(|dataset| json!({ "datasetName": dataset }));
+        self.call("PUT", &format!("aliases/{}", name), body).await?;
+        Ok(())
+    }
+
+    /// Point an existing alias at `dataset`
+    pub async fn link_alias(&self, name: &str, dataset: &str) -> Result<(), RequestError> {
+        let name = alias_name(name)?;
+        validate_name(dataset)?;
+        let body = json!({ "datasetName": dataset });
+        self.call("PATCH", &format!("aliases/{}", name), Some(body))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn unlink_alias(&self, name: &str) -> Result<(), RequestError> {
+        let name = alias_name(name)?;
+        self.call("PATCH", &format!("aliases/{}/unlink", name), None)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn delete_alias(&self, name: &str) -> Result<(), RequestError> {
+        let name = alias_name(name)?;
+        self.call("DELETE", &format!("aliases/{}", name), None)
+            .await?;
+        Ok(())
+    }
+
+    async fn call(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<Value>,
+    ) -> Result<Value, RequestError> {
+        let mut request = self.client.request(method, self.client.endpoint(path)?);
+        if let Some(body) = body {
+            request = request
+                .header("Content-Type", "application/json")
+                .body(serde_json::to_vec(&body)?);
+        }
+        self.client.send_json(request).await
+    }
+}
+
+/// Dataset names are up to 64 lowercase letters, digits, `_` and `-`, starting with a letter
+/// or a digit
+fn validate_name(name: &str) -> Result<(), RequestError> {
+    let valid = !name.is_empty()
+        && name.len() <= 64
+        && name.starts_with(|c: char| c.is_ascii_lowercase() || c.is_ascii_digit())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-');
+    match valid {
+        true => Ok(()),
+        false => Err(RequestError::InvalidName(name.to_string())),
+    }
+}
+
+/// Aliases follow the dataset rules, the `~` they are queried with is optional
+fn alias_name(name: &str) -> Result<&str, RequestError> {
+    let name = name.strip_prefix('~').unwrap_or(name);
+    validate_name(name)?;
+    Ok(name)
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::transport::{test_client, MockTransport};
+
+    fn sent(mock: &MockTransport) -> Vec<(String, String, Option<Value>)> {
+        mock.requests()
+            .into_iter()
+            .map(|request| {
+                let body = request
+                    .body
+                    .map(|body| serde_json::from_slice(&body).unwrap());
+                (request.method, request.url.path().to_string(), body)
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn manages_datasets() -> Result<(), RequestError> {
+        let mock = Arc::new(
+            MockTransport::new()
+                .route(
+                    "GET",
+                    "/v2022-03-07/datasets",
+                    r#"[{"name":"production","aclMode":"public","createdAt":"2024-01-01T00:00:00Z"},{"name":"secrets","aclMode":"private"}]"#,
+                )
+                .route(
+                    "PUT",
+                    "/v2022-03-07/datasets/pr-42",
+                    r#"{"datasetName":"pr-42","aclMode":"private"}"#,
+                )
+                .route("DELETE", "/v2022-03-07/datasets/pr-42", r#"{"deleted":true}"#)
+                .route(
+                    "PUT",
+                    "/v2022-03-07/datasets/production/copy",
+                    r#"{"datasetName":"production","message":"Starting copying dataset production to pr-43...","aclMode":"public","jobId":"job-1"}"#,
+                ),
+        );
+        let client = test_client(&mock);
+        let datasets = client.datasets();
+
+        let list = datasets.list().await?;
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[1].name, "secrets");
+        assert_eq!(list[1].acl_mode, AclMode::Private);
+
+        datasets.create("pr-42", AclMode::Private).await?;
+        datasets.delete("pr-42").await?;
+        let job = datasets.copy("production", "pr-43", true).await?;
+        assert_eq!(job.job_id, "job-1");
+
+        assert_eq!(
+            sent(&mock)[1..],
+            [
+                (
+                    "PUT".to_string(),
+                    "/v2022-03-07/datasets/pr-42".to_string(),
+                    Some(json!({"aclMode": "private"}))
+                ),
+                (
+                    "DELETE".to_string(),
+                    "/v2022-03-07/datasets/pr-42".to_string(),
+                    None
+                ),
+                (
+                    "PUT".to_string(),
+                    "/v2022-03-07/datasets/production/copy".to_string(),
+                    Some(json!({"targetDataset": "pr-43", "skipHistory": true}))
+                )
+            ]
+        );
+        assert_eq!(mock.requests()[0].headers["Authorization"], "Bearer token");
+
+        assert!(matches!(
+            datasets.create("Not valid", AclMode::Public).await,
+            Err(RequestError::InvalidName(_))
+        ));
+        assert_eq!(mock.requests().len(), 4);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn waits_for_jobs() -> Result<(), RequestError> {
+        let done = Arc::new(MockTransport::new().route(
+            "GET",
+            "/v2022-03-07/jobs/job-1",
+            r#"{"id":"job-1","state":"completed","progress":99.5}"#,
+        ));
+        let job = test_client(&done)
+            .datasets()
+            .wait_for_job("job-1", Duration::from_millis(1), 3, tokio::time::sleep)
+            .await?;
+        assert_eq!(job.state, JobState::Completed);
+        assert_eq!(job.progress, Some(99.5));
+
+        let failed = Arc::new(MockTransport::new().route(
+            "GET",
+            "/v2022-03-07/jobs/job-2",
+            r#"{"id":"job-2","state":"failed"}"#,
+        ));
+        assert!(matches!(
+            test_client(&failed)
+                .datasets()
+                .wait_for_job("job-2", Duration::from_millis(1), 3, tokio::time::sleep)
+                .await,
+            Err(RequestError::JobFailed(_))
+        ));
+
+        let running = Arc::new(MockTransport::new().route(
+            "GET",
+            "/v2022-03-07/jobs/job-3",
+            r#"{"id":"job-3","state":"running","progress":10}"#,
+        ));
+        assert!(matches!(
+            test_client(&running)
+                .datasets()
+                .wait_for_job("job-3", Duration::from_millis(1), 3, tokio::time::sleep)
+                .await,
+            Err(RequestError::JobTimedOut { attempts: 3, .. })
+        ));
+        assert_eq!(running.requests().len(), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn manages_aliases() -> Result<(), RequestError> {
+        let mock = Arc::new(
+            MockTransport::new()
+                .route(
+                    "GET",
+                    "/v2022-03-07/aliases",
+                    r#"[{"name":"preview","datasetName":"pr-42"},{"name":"old","datasetName":null}]"#,
+                )
+                .route("PUT", "/v2022-03-07/aliases/preview", "{}")
+                .route("PATCH", "/v2022-03-07/aliases/preview", "{}")
+                .route("PATCH", "/v2022-03-07/aliases/preview/unlink", "{}")
+                .route("DELETE", "/v2022-03-07/aliases/preview", r#"{"deleted":true}"#),
+        );
+        let client = test_client(&mock);
+        let datasets = client.datasets();
+
+        let aliases = datasets.aliases().await?;
+        assert_eq!(aliases[0].dataset_name.as_deref(), Some("pr-42"));
+        assert_eq!(aliases[1].dataset_name, None);
+
+        datasets.create_alias("~preview", None).await?;
+        datasets.link_alias("preview", "pr-42").await?;
+        datasets.unlink_alias("preview").await?;
+        datasets.delete_alias("~preview").await?;
+        let sent = sent(&mock);
+        assert_eq!(sent[1].0, "PUT");
+        assert_eq!(sent[1].2, None);
+        assert_eq!(sent[2].2, Some(json!({"datasetName": "pr-42"})));
+        assert_eq!(sent[3].1, "/v2022-03-07/aliases/preview/unlink");
+        assert_eq!(sent[4].0, "DELETE");
+        assert!(matches!(
+            datasets.create_alias("preview", Some("Pr 42")).await,
+            Err(RequestError::InvalidName(_))
+        ));
+        assert_eq!(mock.requests().len(), 5);
+        Ok(())
+    }
+}
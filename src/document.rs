@@ -0,0 +1,349 @@
+use std::future::Future;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+
+use crate::client::{QueryResult, SanityClient};
+use crate::error::RequestError;
+use crate::orm::ORM;
+use crate::types::{Date, DateTime, Document, File, Geopoint, Image, Reference, Slug};
+
+#[cfg(feature = "derive")]
+pub use sanity_rs_derive::{Projection, SanityDocument};
+
+/// Types that know which GROQ projection selects them
+///
+/// Scalars and opaque objects are fetched as a whole and return `None`.
+/// Structs deriving `Projection` or `SanityDocument` list their fields.
+pub trait Projection {
+    /// Projection fields for this type, e.g. `["_id", "author->{name}"]`
+    fn fields() -> Option<Vec<String>> {
+        None
+    }
+
+    /// Whether the value is an array which has to be traversed with `[]`
+    fn is_array() -> bool {
+        false
+    }
+
+    /// Render the projection, e.g. `{_id,author->{name}}`
+    fn projection() -> String {
+        match Self::fields() {
+            Some(fields) => format!("{{{}}}", fields.join(",")),
+            None => String::new(),
+        }
+    }
+}
+
+/// A document type stored under `TYPE_NAME` with a projection derived from its fields
+///
+/// A dereferenced field has the type of the document it points to, not `Reference`:
+///
+/// ```compile_fail
+/// use sanity_rs::document::SanityDocument;
+/// use sanity_rs::types::Reference;
+///
+/// #[derive(serde::Deserialize, SanityDocument)]
+/// struct Post {
+///     #[sanity(deref)]
+///     author: Option<Reference>,
+/// }
+/// ```
+pub trait SanityDocument: Projection + DeserializeOwned {
+    /// The `_type` of the document
+    const TYPE_NAME: &'static str;
+
+    /// Query for every document of this type
+    fn query_all() -> String {
+        format!(
+            "*[_type == {}]{}",
+            string_literal(Self::TYPE_NAME),
+            Self::projection()
+        )
+    }
+
+    /// Query for a single document of this type
+    fn query_by_id(id: &str) -> String {
+        format!(
+            "*[_type == {} && _id == {}][0]{}",
+            string_literal(Self::TYPE_NAME),
+            string_literal(id),
+            Self::projection()
+        )
+    }
+
+    /// Fetch a document by id, `None` if it does not exist or has another type
+    fn fetch_by_id(
+        client: &mut SanityClient,
+        id: &str,
+    ) -> impl Future<Output = Result<Option<Self>, RequestError>> {
+        let query = Self::query_by_id(id);
+        async move {
+            let result: QueryResult<Option<Self>> = client
+                .query_with_params(&query, &Map::new())
+                .await?
+                .json()?;
+            Ok(result.result)
+        }
+    }
+
+    /// Fetch every document of this type
+    fn fetch_all(
+        client: &mut SanityClient,
+    ) -> impl Future<Output = Result<Vec<Self>, RequestError>> {
+        let query = Self::query_all();
+        async move {
+            let result: QueryResult<Vec<Self>> = client
+                .query_with_params(&query, &Map::new())
+                .await?
+                .json()?;
+            Ok(result.result)
+        }
+    }
+}
+
+/// Render a single projection field
+///
+/// Used by the derive macros, `deref` follows references with `->`. The attribute `name` is
+/// aliased when serde expects it under another `key`, e.g. `"title": heading`.
+pub fn field<T: Projection + ?Sized>(key: &str, name: &str, deref: bool) -> String {
+    let traversal = if T::is_array() && (deref || T::fields().is_some()) {
+        "[]"
+    } else {
+        ""
+    };
+    let arrow = if deref { "->" } else { "" };
+    let alias = if key == name {
+        String::new()
+    } else {
+        format!("{}: ", string_literal(key))
+    };
+    format!("{}{}{}{}{}", alias, name, traversal, arrow, T::projection())
+}
+
+/// Serialize `value` with `tag` as its `_type`
+///
+/// Used by the enums `sanity-codegen` generates for polymorphic arrays.
+pub fn serialize_tagged<T, S>(tag: &str, value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Serialize + ?Sized,
+    S: Serializer,
+{
+    let mut value = serde_json::to_value(value).map_err(serde::ser::Error::custom)?;
+    if let Value::Object(map) = &mut value {
+        map.insert("_type".to_string(), Value::String(tag.to_string()));
+    }
+    value.serialize(serializer)
+}
+
+/// Read an object along with its `_type`, which stays in the object
+///
+/// Used by the enums `sanity-codegen` generates for polymorphic arrays.
+pub fn deserialize_tagged<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<(String, Value), D::Error> {
+    let value = Value::deserialize(deserializer)?;
+    match value.get("_type").and_then(Value::as_str) {
+        Some(tag) => Ok((tag.to_string(), value)),
+        None => Err(serde::de::Error::missing_field("_type")),
+    }
+}
+
+fn string_literal(value: &str) -> String {
+    Value::String(value.to_string()).to_string()
+}
+
+macro_rules! opaque_projection {
+    ($($ty:ty),*) => {
+        $(impl Projection for $ty {})*
+    };
+}
+
+opaque_projection!(
+    String,
+    str,
+    bool,
+    char,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    f32,
+    f64,
+    Value,
+    Date,
+    DateTime,
+    Reference,
+    Slug,
+    Image,
+    File,
+    Geopoint,
+    crate::types::Block,
+    crate::types::Span
+);
+
+impl<T: Projection> Projection for Option<T> {
+    fn fields() -> Option<Vec<String>> {
+        T::fields()
+    }
+
+    fn is_array() -> bool {
+        T::is_array()
+    }
+}
+
+impl<T: Projection> Projection for Box<T> {
+    fn fields() -> Option<Vec<String>> {
+        T::fields()
+    }
+
+    fn is_array() -> bool {
+        T::is_array()
+    }
+}
+
+impl<T: Projection> Projection for Vec<T> {
+    fn fields() -> Option<Vec<String>> {
+        T::fields()
+    }
+
+    fn is_array() -> bool {
+        true
+    }
+}
+
+impl<T: Projection> Projection for Document<T> {
+    fn fields() -> Option<Vec<String>> {
+        let mut fields: Vec<String> = ["_id", "_type", "_rev", "_createdAt", "_updatedAt"]
+            .iter()
+            .map(|field| field.to_string())
+            .collect();
+        match T::fields() {
+            Some(inner) => fields.extend(inner),
+            None => fields.push("...".to_string()),
+        }
+        Some(fields)
+    }
+}
+
+impl<T: SanityDocument> SanityDocument for Document<T> {
+    const TYPE_NAME: &'static str = T::TYPE_NAME;
+}
+
+#[cfg(all(test, feature = "derive"))]
+mod test {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::transport::{test_client, MockTransport};
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[allow(dead_code)]
+    #[derive(Debug, Deserialize, Projection)]
+    struct Category {
+        title: String,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Debug, Deserialize, Projection)]
+    #[serde(rename_all = "camelCase")]
+    struct Author {
+        name: String,
+        profile_image: Option<Image>,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Debug, Deserialize, SanityDocument)]
+    struct Post {
+        #[serde(rename = "_id")]
+        id: String,
+        title: String,
+        slug: Slug,
+        #[sanity(deref)]
+        author: Author,
+        #[sanity(deref)]
+        categories: Vec<Category>,
+        tags: Vec<String>,
+        related: Vec<Reference>,
+        #[sanity(deref)]
+        sponsor: Option<Author>,
+        #[serde(skip)]
+        cached: Option<String>,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Debug, Deserialize, SanityDocument)]
+    #[sanity(type = "landing-page")]
+    struct LandingPage {
+        #[sanity(rename = "heading")]
+        title: String,
+        sections: Vec<Category>,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Debug, Deserialize, SanityDocument)]
+    struct BlogPost {
+        title: String,
+    }
+
+    #[test]
+    fn type_names() {
+        assert_eq!(Post::TYPE_NAME, "post");
+        assert_eq!(BlogPost::TYPE_NAME, "blogPost");
+        assert_eq!(LandingPage::TYPE_NAME, "landing-page");
+    }
+
+    #[test]
+    fn derive_projection() {
+        assert_eq!(
+            Post::projection(),
+            "{_id,title,slug,author->{name,profileImage},categories[]->{title},tags,related,sponsor->{name,profileImage}}"
+        );
+        assert_eq!(
+            LandingPage::projection(),
+            r#"{"title": heading,sections[]{title}}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn fetch_through_projection() -> Result<(), RequestError> {
+        let mock = Arc::new(MockTransport::new().dataset(vec![json!({
+            "_id": "home",
+            "_type": "landing-page",
+            "heading": "Welcome",
+            "title": "Not the heading",
+            "sections": [{"_key": "a", "title": "Intro", "body": "..."}]
+        })]));
+        let mut client = test_client(&mock);
+        let pages = LandingPage::fetch_all(&mut client).await?;
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].title, "Welcome");
+        assert_eq!(pages[0].sections[0].title, "Intro");
+        let page = LandingPage::fetch_by_id(&mut client, "home").await?;
+        assert_eq!(page.unwrap().title, "Welcome");
+        Ok(())
+    }
+
+    #[test]
+    fn document_queries() {
+        assert_eq!(BlogPost::query_all(), r#"*[_type == "blogPost"]{title}"#);
+        assert_eq!(
+            BlogPost::query_by_id(r#"a"b"#),
+            r#"*[_type == "blogPost" && _id == "a\"b"][0]{title}"#
+        );
+        assert_eq!(
+            Document::<BlogPost>::projection(),
+            "{_id,_type,_rev,_createdAt,_updatedAt,title}"
+        );
+    }
+}
@@ -0,0 +1,147 @@
+//! Draft, published and version ids
+//!
+//! Sanity keeps the draft of a document `abc` as `drafts.abc` and its version in release `r`
+//! as `versions.r.abc`, the published document is `abc` itself.
+//!
+//! ```
+//! use sanity_rs::drafts::{draft_id, is_draft, published_id, version_id};
+//!
+//! assert_eq!(draft_id("abc"), "drafts.abc");
+//! assert_eq!(published_id("drafts.abc"), "abc");
+//! assert_eq!(version_id("spring", "drafts.abc"), "versions.spring.abc");
+//! assert!(is_draft("drafts.abc"));
+//! ```
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+pub const DRAFTS_PREFIX: &str = "drafts.";
+pub const VERSION_PREFIX: &str = "versions.";
+
+/// Id of the draft of a document, from any of its ids
+pub fn draft_id(id: &str) -> String {
+    format!("{}{}", DRAFTS_PREFIX, published_id(id))
+}
+
+/// Id of the published document, from any of its ids
+pub fn published_id(id: &str) -> String {
+    if let Some(id) = id.strip_prefix(DRAFTS_PREFIX) {
+        return id.to_string();
+    }
+    match id
+        .strip_prefix(VERSION_PREFIX)
+        .and_then(|rest| rest.split_once('.'))
+    {
+        Some((_, id)) => id.to_string(),
+        None => id.to_string(),
+    }
+}
+
+/// Id of the version of a document in `release`
+pub fn version_id(release: &str, id: &str) -> String {
+    format!("{}{}.{}", VERSION_PREFIX, release, published_id(id))
+}
+
+pub fn is_draft(id: &str) -> bool {
+    id.starts_with(DRAFTS_PREFIX)
+}
+
+pub fn is_version(id: &str) -> bool {
+    version_of(id).is_some()
+}
+
+pub fn is_published(id: &str) -> bool {
+    !is_draft(id) && !is_version(id)
+}
+
+/// The release of a version id, e.g. `spring` for `versions.spring.abc`
+pub fn version_of(id: &str) -> Option<&str> {
+    let (release, _) = id.strip_prefix(VERSION_PREFIX)?.split_once('.')?;
+    Some(release)
+}
+
+/// Overlay drafts on published documents like the `previewDrafts` perspective does
+///
+/// Each document appears once under its published `_id`, as its draft when there is one,
+/// with the id it was stored under in `_originalId`. Documents are kept in the order their
+/// first id appears, versions and values without an `_id` are left out.
+pub fn overlay_drafts(documents: Vec<Value>) -> Vec<Value> {
+    let mut overlaid: Vec<Value> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for mut document in documents {
+        let Some(id) = document
+            .get("_id")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+        else {
+            continue;
+        };
+        if is_version(&id) {
+            continue;
+        }
+        let published = published_id(&id);
+        if let Value::Object(object) = &mut document {
+            object.insert("_id".to_string(), Value::String(published.clone()));
+            object.insert("_originalId".to_string(), Value::String(id.clone()));
+        }
+        match positions.get(&published) {
+            Some(position) if is_draft(&id) => overlaid[*position] = document,
+            Some(_) => {}
+            None => {
+                positions.insert(published, overlaid.len());
+                overlaid.push(document);
+            }
+        }
+    }
+    overlaid
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn ids() {
+        assert_eq!(draft_id("abc"), "drafts.abc");
+        assert_eq!(draft_id("drafts.abc"), "drafts.abc");
+        assert_eq!(draft_id("versions.spring.abc"), "drafts.abc");
+        assert_eq!(published_id("versions.spring.abc"), "abc");
+        assert_eq!(published_id("abc"), "abc");
+        assert_eq!(version_id("spring", "abc"), "versions.spring.abc");
+        assert_eq!(version_of("versions.spring.abc"), Some("spring"));
+        assert_eq!(version_of("drafts.abc"), None);
+        assert_eq!(version_of("versions.abc"), None);
+        assert!(is_version("versions.spring.abc"));
+        assert!(is_published("abc"));
+        assert!(!is_published("drafts.abc"));
+        assert!(!is_draft("abc"));
+    }
+
+    #[test]
+    fn overlays_drafts() {
+        let documents = vec![
+            json!({"_id": "a", "title": "Published A"}),
+            json!({"_id": "b", "title": "Published B"}),
+            json!({"_id": "drafts.a", "title": "Draft A"}),
+            json!({"_id": "drafts.c", "title": "Draft C"}),
+            json!({"_id": "versions.spring.b", "title": "Spring B"}),
+            json!({"title": "No id"}),
+        ];
+        assert_eq!(
+            overlay_drafts(documents),
+            [
+                json!({"_id": "a", "_originalId": "drafts.a", "title": "Draft A"}),
+                json!({"_id": "b", "_originalId": "b", "title": "Published B"}),
+                json!({"_id": "c", "_originalId": "drafts.c", "title": "Draft C"}),
+            ]
+        );
+
+        // A draft listed before its published document still wins
+        let documents = vec![
+            json!({"_id": "drafts.a", "title": "Draft A"}),
+            json!({"_id": "a", "title": "Published A"}),
+        ];
+        assert_eq!(overlay_drafts(documents)[0]["title"], "Draft A");
+    }
+}
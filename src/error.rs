@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::groq::ast::Span;
+
 #[derive(Error, Debug)]
 pub enum RequestError {
     #[error("URL Parsing error: {0}")]
@@ -16,6 +18,27 @@ pub enum RequestError {
 
     #[error("Request error: {0}")]
     StringParsingError(String),
+
+    #[error("Query parsing error: {0}")]
+    QueryParsingError(#[from] ParseError),
+
+    #[error("Transport error: {0}")]
+    TransportError(String),
+
+    #[error("API error {status}: {body}")]
+    ApiError { status: u16, body: String },
+
+    #[error("IO error: {0}")]
+    IOError(#[from] std::io::Error),
+
+    #[error("Invalid dataset or alias name: {0}")]
+    InvalidName(String),
+
+    #[error("Job {0} failed")]
+    JobFailed(String),
+
+    #[error("Job {job_id} did not finish after {attempts} attempts")]
+    JobTimedOut { job_id: String, attempts: usize },
 }
 
 #[derive(Error, Debug)]
@@ -32,3 +55,60 @@ pub enum ConfigurationError {
     #[error("Missing dataset")]
     MissingDataset,
 }
+
+#[derive(Error, Debug)]
+pub enum CodegenError {
+    #[error("Schema parsing error: {0}")]
+    SchemaParsingError(#[from] serde_json::Error),
+
+    #[error("IO error: {0}")]
+    IOError(#[from] std::io::Error),
+
+    #[error("Query parsing error: {0}")]
+    QueryParsingError(#[from] ParseError),
+
+    #[error("Query {0} is not a string")]
+    InvalidQuery(String),
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ParseError {
+    #[error("Unexpected {found} at {span}, expected {expected}")]
+    UnexpectedToken {
+        found: String,
+        expected: String,
+        span: Span,
+    },
+
+    #[error("Unexpected end of query at {span}, expected {expected}")]
+    UnexpectedEnd { expected: String, span: Span },
+
+    #[error("Invalid token at {span}: {message}")]
+    InvalidToken { message: String, span: Span },
+}
+
+impl ParseError {
+    /// The byte range of the query the error points at
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::UnexpectedToken { span, .. }
+            | ParseError::UnexpectedEnd { span, .. }
+            | ParseError::InvalidToken { span, .. } => *span,
+        }
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum EvalError {
+    #[error("Query parsing error: {0}")]
+    QueryParsingError(#[from] ParseError),
+
+    #[error("Missing parameter ${0}")]
+    MissingParam(String),
+
+    #[error("Unknown function {namespace}::{name}")]
+    UnknownFunction { namespace: String, name: String },
+
+    #[error("Invalid arguments to {function}: {message}")]
+    InvalidArguments { function: String, message: String },
+}
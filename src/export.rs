@@ -0,0 +1,596 @@
+//! Export a dataset as NDJSON, optionally with its assets as a tar.gz archive
+//!
+//! ```no_run
+//! # use sanity_rs::client::SanityClient;
+//! # use sanity_rs::error::RequestError;
+//! # async fn backup(client: &SanityClient) -> Result<(), RequestError> {
+//! let mut documents = client.export("production").types(&["post"]).drafts(false).stream().await?;
+//! while let Some(document) = documents.next_value().await {
+//!     println!("{}", document?["_id"]);
+//! }
+//!
+//! let archive = std::fs::File::create("production.tar.gz")?;
+//! client.export("production").archive(archive).await?;
+//! # Ok(())
+//! # }
+//! ```
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use chrono::Utc;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use url::Url;
+
+use crate::assets::asset_path;
+use crate::client::SanityClient;
+use crate::error::{RequestError, URLError};
+use crate::transport::{ChunkStream, Request};
+
+const IMAGE_ASSET: &str = "sanity.imageAsset";
+const FILE_ASSET: &str = "sanity.fileAsset";
+
+/// Options of an export, created with `SanityClient::export`
+pub struct Export<'a> {
+    client: &'a SanityClient,
+    dataset: String,
+    types: Vec<String>,
+    drafts: bool,
+}
+
+/// Counts of what went into an archive
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ArchiveSummary {
+    pub documents: usize,
+    pub assets: usize,
+}
+
+impl SanityClient {
+    /// Export every document of `dataset`
+    pub fn export(&self, dataset: &str) -> Export<'_> {
+        Export {
+            client: self,
+            dataset: dataset.to_string(),
+            types: Vec::new(),
+            drafts: true,
+        }
+    }
+}
+
+impl<'a> Export<'a> {
+    /// Only export documents of these types
+    pub fn types(mut self, types: &[&str]) -> Self {
+        self.types = types.iter().map(|t| t.to_string()).collect();
+        self
+    }
+
+    /// Whether to export drafts, they are by default
+    pub fn drafts(mut self, drafts: bool) -> Self {
+        self.drafts = drafts;
+        self
+    }
+
+    /// Start the export, documents are read as they arrive
+    pub async fn stream(&self) -> Result<ExportStream, RequestError> {
+        let mut url = self
+            .client
+            .endpoint(&format!("data/export/{}", self.dataset))?;
+        if !self.types.is_empty() {
+            url.query_pairs_mut()
+                .append_pair("types", &self.types.join(","));
+        }
+        let request = self.client.request("GET", url);
+        let response = self.client.transport().stream(request).await?;
+        if response.status >= 400 {
+            let status = response.status;
+            let body = String::from_utf8_lossy(&response.bytes().await?).into_owned();
+            return Err(RequestError::ApiError { status, body });
+        }
+        Ok(ExportStream {
+            body: response.body,
+            buffer: Vec::new(),
+            done: false,
+            types: self.types.clone(),
+            drafts: self.drafts,
+        })
+    }
+
+    /// Write the documents and their assets into a tar.gz archive laid out like the one
+    /// `sanity dataset export` produces
+    ///
+    /// The archive holds a `<dataset>-export-<time>` directory with `data.ndjson`,
+    /// `assets.json` and the asset files under `images/` and `files/`. Asset fields point
+    /// at those files with `_sanityAsset` instead of referencing the asset documents, which
+    /// are left out of `data.ndjson`. Documents and assets are buffered in temporary files
+    /// rather than in memory until they are written to the archive.
+    pub async fn archive<W: Write>(&self, writer: W) -> Result<ArchiveSummary, RequestError> {
+        let now = Utc::now();
+        let root = format!(
+            "{}-export-{}",
+            self.dataset,
+            now.format("%Y-%m-%dt%H-%M-%S-%3fz")
+        );
+        let mtime = now.timestamp().max(0) as u64;
+        let mut documents = self.stream().await?;
+        let mut data = Spool::new()?;
+        let mut data_writer = BufWriter::new(&data.file);
+        let mut summary = ArchiveSummary::default();
+        let mut assets = BTreeMap::new();
+        let mut referenced = BTreeSet::new();
+        while let Some(document) = documents.next_value().await {
+            let mut document = document?;
+            let type_name = document.get("_type").and_then(Value::as_str);
+            if matches!(type_name, Some(IMAGE_ASSET | FILE_ASSET)) {
+                if let Some(id) = document.get("_id").and_then(Value::as_str) {
+                    assets.insert(id.to_string(), document);
+                }
+                continue;
+            }
+            rewrite_assets(&mut document, &mut referenced);
+            serde_json::to_writer(&mut data_writer, &document)?;
+            data_writer.write_all(b"\n")?;
+            summary.documents += 1;
+        }
+        data_writer.flush()?;
+        drop(data_writer);
+
+        let mut tar = tar::Builder::new(GzEncoder::new(writer, Compression::default()));
+        let mut metadata = Map::new();
+        let ids: BTreeSet<String> = assets.keys().cloned().chain(referenced).collect();
+        for id in ids {
+            let Some(path) = asset_path(&id) else {
+                continue;
+            };
+            let url = match assets.get(&id).and_then(|asset| asset.get("url")?.as_str()) {
+                Some(url) => Url::parse(url).map_err(URLError::InvalidURL)?,
+                None => self.cdn_url(&path)?,
+            };
+            let mut response = self.client.transport().stream(self.download(url)?).await?;
+            if response.status >= 400 {
+                return Err(RequestError::ApiError {
+                    status: response.status,
+                    body: format!("cannot download {}", id),
+                });
+            }
+            let mut file = Spool::new()?;
+            while let Some(chunk) = response.body.next_chunk().await? {
+                file.file.write_all(&chunk)?;
+            }
+            file.append_to(&mut tar, &format!("{}/{}", root, path), mtime)?;
+            if let Some(asset) = assets.remove(&id) {
+                metadata.insert(id, asset_metadata(asset));
+            }
+            summary.assets += 1;
+        }
+        data.append_to(&mut tar, &format!("{}/data.ndjson", root), mtime)?;
+        let metadata = serde_json::to_vec_pretty(&metadata)?;
+        append(
+            &mut tar,
+            &format!("{}/assets.json", root),
+            mtime,
+            metadata.len() as u64,
+            metadata.as_slice(),
+        )?;
+        tar.into_inner()?.finish()?;
+        Ok(summary)
+    }
+
+    /// A download of an asset, the token is only sent to the API and CDN of the project
+    fn download(&self, url: Url) -> Result<Request, RequestError> {
+        let api = self.client.endpoint("")?;
+        let trusted = url.scheme() == "https"
+            && (url.host_str() == Some("cdn.sanity.io") || url.host_str() == api.host_str());
+        Ok(if trusted {
+            self.client.request("GET", url)
+        } else {
+            Request::get(url)
+        })
+    }
+
+    /// Url of an asset on the CDN when its asset document is not in the export
+    fn cdn_url(&self, path: &str) -> Result<Url, RequestError> {
+        let url = format!(
+            "https://cdn.sanity.io/{}/{}/{}/{}",
+            path.split('/').next().unwrap_or_default(),
+            self.client.config().project_id,
+            self.dataset,
+            path.rsplit('/').next().unwrap_or_default()
+        );
+        Ok(Url::parse(&url).map_err(URLError::InvalidURL)?)
+    }
+}
+
+/// Add a regular file to the archive, `data` is read as it is written
+fn append<W: Write>(
+    tar: &mut tar::Builder<W>,
+    path: &str,
+    mtime: u64,
+    size: u64,
+    data: impl Read,
+) -> io::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(tar::EntryType::Regular);
+    header.set_mode(0o644);
+    header.set_mtime(mtime);
+    header.set_size(size);
+    tar.append_data(&mut header, path, data)
+}
+
+/// A temporary file holding an archive entry until its size is known, removed when dropped
+struct Spool {
+    path: PathBuf,
+    file: File,
+}
+
+impl Spool {
+    fn new() -> io::Result<Self> {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "sanity-rs-export-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        Ok(Self { path, file })
+    }
+
+    fn append_to<W: Write>(
+        &mut self,
+        tar: &mut tar::Builder<W>,
+        path: &str,
+        mtime: u64,
+    ) -> io::Result<()> {
+        let size = self.file.seek(SeekFrom::End(0))?;
+        self.file.seek(SeekFrom::Start(0))?;
+        append(tar, path, mtime, size, &self.file)
+    }
+}
+
+impl Drop for Spool {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Documents of an export, deserialized one line at a time
+pub struct ExportStream {
+    body: Box<dyn ChunkStream>,
+    buffer: Vec<u8>,
+    done: bool,
+    types: Vec<String>,
+    drafts: bool,
+}
+
+impl ExportStream {
+    /// The next document, `None` at the end of the export
+    pub async fn next_value(&mut self) -> Option<Result<Value, RequestError>> {
+        loop {
+            let line = match self.buffer.iter().position(|byte| *byte == b'\n') {
+                Some(end) => {
+                    let line: Vec<u8> = self.buffer.drain(..=end).collect();
+                    Some(line)
+                }
+                None if self.done && !self.buffer.is_empty() => {
+                    Some(std::mem::take(&mut self.buffer))
+                }
+                None if self.done => return None,
+                None => None,
+            };
+            match line {
+                Some(line) => {
+                    if line.iter().all(u8::is_ascii_whitespace) {
+                        continue;
+                    }
+                    let document: Value = match serde_json::from_slice(&line) {
+                        Ok(document) => document,
+                        Err(e) => return Some(Err(e.into())),
+                    };
+                    if self.keep(&document) {
+                        return Some(Ok(document));
+                    }
+                }
+                None => match self.body.next_chunk().await {
+                    Ok(Some(chunk)) => self.buffer.extend(chunk),
+                    Ok(None) => self.done = true,
+                    Err(e) => return Some(Err(e)),
+                },
+            }
+        }
+    }
+
+    /// The next document deserialized as `T`
+    pub async fn next<T: DeserializeOwned>(&mut self) -> Option<Result<T, RequestError>> {
+        let document = self.next_value().await?;
+        Some(document.and_then(|document| Ok(serde_json::from_value(document)?)))
+    }
+
+    /// Write the remaining documents as NDJSON, returning how many were written
+    pub async fn write_ndjson<W: Write>(&mut self, mut writer: W) -> Result<usize, RequestError> {
+        let mut count = 0;
+        while let Some(document) = self.next_value().await {
+            serde_json::to_writer(&mut writer, &document?)?;
+            writer.write_all(b"\n")?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// The API filters types already, this also covers servers that do not
+    fn keep(&self, document: &Value) -> bool {
+        let id = document
+            .get("_id")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let type_name = document
+            .get("_type")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        (self.drafts || !id.starts_with("drafts."))
+            && (self.types.is_empty() || self.types.iter().any(|t| t == type_name))
+    }
+}
+
+/// Replace asset references with `_sanityAsset` pointers into the archive
+fn rewrite_assets(value: &mut Value, referenced: &mut BTreeSet<String>) {
+    match value {
+        Value::Object(object) => {
+            let asset = object
+                .get("asset")
+                .and_then(|asset| asset.get("_ref"))
+                .and_then(Value::as_str)
+                .and_then(|id| Some((id.to_string(), asset_path(id)?)));
+            if let Some((id, path)) = asset {
+                let kind = if path.starts_with("images/") {
+                    "image"
+                } else {
+                    "file"
+                };
+                object.remove("asset");
+                object.insert(
+                    "_sanityAsset".to_string(),
+                    Value::String(format!("{}@file://./{}", kind, path)),
+                );
+                referenced.insert(id);
+            }
+            for value in object.values_mut() {
+                rewrite_assets(value, referenced);
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                rewrite_assets(value, referenced);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The fields of an asset document which are restored on import
+fn asset_metadata(asset: Value) -> Value {
+    match asset {
+        Value::Object(mut object) => {
+            object.retain(|key, _| {
+                !key.starts_with('_') && !matches!(key.as_str(), "url" | "path" | "assetId")
+            });
+            Value::Object(object)
+        }
+        asset => asset,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::transport::{test_client, MockTransport};
+    use serde::Deserialize;
+    use serde_json::json;
+
+    const NDJSON: &str = r#"{"_id":"post-1","_type":"post","title":"Hello","image":{"_type":"image","asset":{"_ref":"image-abc123-10x20-png","_type":"reference"}}}
+{"_id":"drafts.post-1","_type":"post","title":"Hello draft"}
+{"_id":"author-1","_type":"author","name":"Ada","cv":{"_type":"file","asset":{"_ref":"file-def456-pdf","_type":"reference"}}}
+
+{"_id":"image-abc123-10x20-png","_type":"sanity.imageAsset","url":"https://cdn.sanity.io/images/project/production/abc123-10x20.png","originalFilename":"hello.png","path":"images/project/production/abc123-10x20.png"}
+{"_id":"post-2","_type":"post","title":"Last line without a newline"}"#;
+
+    fn mock() -> Arc<MockTransport> {
+        Arc::new(
+            MockTransport::new()
+                .route("GET", "/v2022-03-07/data/export/production", NDJSON)
+                .route(
+                    "GET",
+                    "/images/project/production/abc123-10x20.png",
+                    vec![0x89, b'P', b'N', b'G', 0xff],
+                )
+                .route(
+                    "GET",
+                    "/files/project/production/def456.pdf",
+                    b"%PDF-1.4".to_vec(),
+                ),
+        )
+    }
+
+    #[tokio::test]
+    async fn streams_documents() -> Result<(), RequestError> {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Post {
+            title: String,
+        }
+
+        let mock = mock();
+        let client = test_client(&mock);
+        let mut documents = client.export("production").stream().await?;
+        let mut ids = Vec::new();
+        while let Some(document) = documents.next_value().await {
+            ids.push(document?["_id"].as_str().unwrap().to_string());
+        }
+        assert_eq!(
+            ids,
+            [
+                "post-1",
+                "drafts.post-1",
+                "author-1",
+                "image-abc123-10x20-png",
+                "post-2"
+            ]
+        );
+
+        let export = client.export("production").types(&["post"]).drafts(false);
+        let mut posts = export.stream().await?;
+        assert_eq!(posts.next::<Post>().await.unwrap()?.title, "Hello");
+        assert_eq!(
+            posts.next::<Post>().await.unwrap()?.title,
+            "Last line without a newline"
+        );
+        assert!(posts.next::<Post>().await.is_none());
+        assert_eq!(
+            mock.requests().last().unwrap().url.as_str(),
+            "https://project.api.sanity.io/v2022-03-07/data/export/production?types=post"
+        );
+
+        let mut ndjson = Vec::new();
+        let count = client
+            .export("production")
+            .drafts(false)
+            .stream()
+            .await?
+            .write_ndjson(&mut ndjson)
+            .await?;
+        assert_eq!(count, 4);
+        assert_eq!(String::from_utf8(ndjson).unwrap().lines().count(), 4);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn api_errors() {
+        let mock = Arc::new(MockTransport::new().route_with_status(
+            "GET",
+            "/v2022-03-07/data/export/secret",
+            401,
+            r#"{"error":"Unauthorized"}"#,
+        ));
+        let client = test_client(&mock);
+        assert!(matches!(
+            client.export("secret").stream().await,
+            Err(RequestError::ApiError { status: 401, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn archives_assets() -> Result<(), RequestError> {
+        let mock = mock();
+        let client = test_client(&mock);
+        let mut archive = Vec::new();
+        let summary = client
+            .export("production")
+            .drafts(false)
+            .archive(&mut archive)
+            .await?;
+        assert_eq!(
+            summary,
+            ArchiveSummary {
+                documents: 3,
+                assets: 2
+            }
+        );
+        let mut entries = BTreeMap::new();
+        let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(archive.as_slice()));
+        for entry in tar.entries()? {
+            let mut entry = entry?;
+            let path = entry.path()?.to_string_lossy().into_owned();
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data)?;
+            entries.insert(path.split_once('/').unwrap().1.to_string(), data);
+        }
+        assert_eq!(
+            entries.keys().collect::<Vec<_>>(),
+            [
+                "assets.json",
+                "data.ndjson",
+                "files/def456.pdf",
+                "images/abc123-10x20.png"
+            ]
+        );
+        assert_eq!(entries["files/def456.pdf"], b"%PDF-1.4");
+        let data = String::from_utf8(entries["data.ndjson"].clone()).unwrap();
+        assert_eq!(data.lines().count(), 3);
+        assert!(data.contains(r#""_sanityAsset":"image@file://./images/abc123-10x20.png""#));
+        let metadata: Value = serde_json::from_slice(&entries["assets.json"])?;
+        assert_eq!(
+            metadata,
+            json!({"image-abc123-10x20-png": {"originalFilename": "hello.png"}})
+        );
+        let downloads: Vec<String> = mock
+            .requests()
+            .iter()
+            .map(|request| request.url.to_string())
+            .filter(|url| url.starts_with("https://cdn.sanity.io"))
+            .collect();
+        assert_eq!(
+            downloads,
+            [
+                "https://cdn.sanity.io/files/project/production/def456.pdf",
+                "https://cdn.sanity.io/images/project/production/abc123-10x20.png"
+            ]
+        );
+        assert!(mock
+            .requests()
+            .iter()
+            .all(|request| request.headers.contains_key("Authorization")));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn downloads_keep_the_token_on_sanity_hosts() -> Result<(), RequestError> {
+        let ndjson = r#"{"_id":"image-abc123-10x20-png","_type":"sanity.imageAsset","url":"https://assets.example.com/abc123-10x20.png"}"#;
+        let mock = Arc::new(
+            MockTransport::new()
+                .route("GET", "/v2022-03-07/data/export/production", ndjson)
+                .route("GET", "/abc123-10x20.png", vec![0x89, b'P', b'N', b'G']),
+        );
+        let client = test_client(&mock);
+        client.export("production").archive(Vec::new()).await?;
+        let download = mock.requests().pop().unwrap();
+        assert_eq!(
+            download.url.as_str(),
+            "https://assets.example.com/abc123-10x20.png"
+        );
+        assert!(!download.headers.contains_key("Authorization"));
+        Ok(())
+    }
+
+    #[test]
+    fn rewrites_asset_references() {
+        let mut document = json!({
+            "_id": "post-1",
+            "gallery": [{"_type": "image", "_key": "a", "asset": {"_ref": "image-abc-1x1-jpg", "_type": "reference"}}],
+            "author": {"_ref": "author-1", "_type": "reference"}
+        });
+        let mut referenced = BTreeSet::new();
+        rewrite_assets(&mut document, &mut referenced);
+        assert_eq!(
+            document,
+            json!({
+                "_id": "post-1",
+                "gallery": [{"_type": "image", "_key": "a", "_sanityAsset": "image@file://./images/abc-1x1.jpg"}],
+                "author": {"_ref": "author-1", "_type": "reference"}
+            })
+        );
+        assert_eq!(
+            referenced.into_iter().collect::<Vec<_>>(),
+            ["image-abc-1x1-jpg"]
+        );
+        assert_eq!(asset_path("file-def-pdf").as_deref(), Some("files/def.pdf"));
+        assert_eq!(asset_path("author-1"), None);
+    }
+}
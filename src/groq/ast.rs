@@ -0,0 +1,300 @@
+use std::fmt::{self, Display, Formatter};
+
+use serde_json::Value;
+
+/// A byte range in a query
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+/// A GROQ expression
+///
+/// `Display` renders the expression back to compact GROQ. Parentheses are only written
+/// for `Group`, so expressions built by hand must group operands themselves.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    /// `*`, every document in the dataset
+    Everything,
+    /// `@`, the current value
+    This,
+    /// `^`, `^.^`, ..., the value of an enclosing scope, counted in levels up
+    Parent(usize),
+    /// `$name`
+    Param(String),
+    Literal(Value),
+    Array(Vec<ArrayElement>),
+    Object(Vec<ObjectAttribute>),
+    /// A bare attribute of the current value, e.g. `title`
+    Attribute(String),
+    /// `(expr)`
+    Group(Box<Expr>),
+    /// `base.name` or `base["name"]`
+    AccessAttribute(Box<Expr>, String),
+    /// `base[index]`
+    AccessElement(Box<Expr>, Box<Expr>),
+    /// `base[start..end]`, `inclusive` for `..` and exclusive for `...`
+    Slice {
+        base: Box<Expr>,
+        start: Box<Expr>,
+        end: Box<Expr>,
+        inclusive: bool,
+    },
+    /// `base[condition]`
+    Filter(Box<Expr>, Box<Expr>),
+    /// `base[]`
+    ArrayTraversal(Box<Expr>),
+    /// `base{...}`
+    Projection(Box<Expr>, Vec<ObjectAttribute>),
+    /// `base->` or `base->name`
+    Deref(Box<Expr>, Option<String>),
+    /// `name(args)` or `namespace::name(args)`
+    FuncCall {
+        namespace: String,
+        name: String,
+        args: Vec<Expr>,
+    },
+    /// `base | name(args)`
+    PipeCall {
+        base: Box<Expr>,
+        name: String,
+        args: Vec<Expr>,
+    },
+    /// `condition => value`
+    Pair(Box<Expr>, Box<Expr>),
+    /// `start..end` outside of a slice, e.g. `x in 1..5`
+    Range {
+        start: Box<Expr>,
+        end: Box<Expr>,
+        inclusive: bool,
+    },
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    Pos(Box<Expr>),
+    /// `expr asc` in `order()`
+    Asc(Box<Expr>),
+    /// `expr desc` in `order()`
+    Desc(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Eq,
+    NotEq,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+    In,
+    Match,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArrayElement {
+    Value(Expr),
+    /// `...expr`
+    Splat(Expr),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjectAttribute {
+    /// `"name": value` or a bare expression whose name is inferred
+    Value(String, Expr),
+    /// `...` or `...expr`
+    Splat(Expr),
+    /// `condition => {...}`
+    Conditional(Expr, Expr),
+}
+
+impl Expr {
+    pub fn boxed(self) -> Box<Expr> {
+        Box::new(self)
+    }
+
+    /// The attribute name a projection gives a bare expression, e.g. `author->name` is `name`
+    pub fn inferred_name(&self) -> Option<&str> {
+        match self {
+            Expr::Attribute(name) | Expr::AccessAttribute(_, name) => Some(name),
+            Expr::Deref(_, Some(name)) => Some(name),
+            Expr::Deref(base, None)
+            | Expr::Projection(base, _)
+            | Expr::ArrayTraversal(base)
+            | Expr::Filter(base, _)
+            | Expr::AccessElement(base, _)
+            | Expr::Slice { base, .. }
+            | Expr::PipeCall { base, .. } => base.inferred_name(),
+            _ => None,
+        }
+    }
+}
+
+/// Whether `name` can be written as a bare attribute
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn write_list<T: Display>(f: &mut Formatter<'_>, items: &[T]) -> fmt::Result {
+    for (index, item) in items.iter().enumerate() {
+        if index > 0 {
+            f.write_str(",")?;
+        }
+        write!(f, "{}", item)?;
+    }
+    Ok(())
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Everything => f.write_str("*"),
+            Expr::This => f.write_str("@"),
+            Expr::Parent(levels) => f.write_str(&vec!["^"; (*levels).max(1)].join(".")),
+            Expr::Param(name) => write!(f, "${}", name),
+            Expr::Literal(value) => write!(f, "{}", value),
+            Expr::Array(elements) => {
+                f.write_str("[")?;
+                write_list(f, elements)?;
+                f.write_str("]")
+            }
+            Expr::Object(attributes) => {
+                f.write_str("{")?;
+                write_list(f, attributes)?;
+                f.write_str("}")
+            }
+            Expr::Attribute(name) if is_identifier(name) => f.write_str(name),
+            Expr::Attribute(name) => write!(f, "@[{}]", Value::String(name.clone())),
+            Expr::Group(inner) => write!(f, "({})", inner),
+            Expr::AccessAttribute(base, name) if is_identifier(name) => {
+                write!(f, "{}.{}", base, name)
+            }
+            Expr::AccessAttribute(base, name) => {
+                write!(f, "{}[{}]", base, Value::String(name.clone()))
+            }
+            Expr::AccessElement(base, index) => write!(f, "{}[{}]", base, index),
+            Expr::Slice {
+                base,
+                start,
+                end,
+                inclusive,
+            } => {
+                let range = if *inclusive { ".." } else { "..." };
+                write!(f, "{}[{}{}{}]", base, start, range, end)
+            }
+            Expr::Filter(base, condition) => write!(f, "{}[{}]", base, condition),
+            Expr::ArrayTraversal(base) => write!(f, "{}[]", base),
+            Expr::Projection(base, attributes) => {
+                write!(f, "{}{{", base)?;
+                write_list(f, attributes)?;
+                f.write_str("}")
+            }
+            Expr::Deref(base, Some(name)) => write!(f, "{}->{}", base, name),
+            Expr::Deref(base, None) => write!(f, "{}->", base),
+            Expr::FuncCall {
+                namespace,
+                name,
+                args,
+            } => {
+                if namespace != "global" {
+                    write!(f, "{}::", namespace)?;
+                }
+                write!(f, "{}(", name)?;
+                write_list(f, args)?;
+                f.write_str(")")
+            }
+            Expr::PipeCall { base, name, args } => {
+                write!(f, "{} | {}(", base, name)?;
+                write_list(f, args)?;
+                f.write_str(")")
+            }
+            Expr::Pair(condition, value) => write!(f, "{} => {}", condition, value),
+            Expr::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                let range = if *inclusive { ".." } else { "..." };
+                write!(f, "{}{}{}", start, range, end)
+            }
+            Expr::And(left, right) => write!(f, "{} && {}", left, right),
+            Expr::Or(left, right) => write!(f, "{} || {}", left, right),
+            Expr::Not(inner) => write!(f, "!{}", inner),
+            Expr::Neg(inner) => write!(f, "-{}", inner),
+            Expr::Pos(inner) => write!(f, "+{}", inner),
+            Expr::Asc(inner) => write!(f, "{} asc", inner),
+            Expr::Desc(inner) => write!(f, "{} desc", inner),
+            Expr::Binary(op, left, right) => write!(f, "{} {} {}", left, op, right),
+        }
+    }
+}
+
+impl Display for BinaryOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BinaryOp::Eq => "==",
+            BinaryOp::NotEq => "!=",
+            BinaryOp::Lt => "<",
+            BinaryOp::Lte => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Gte => ">=",
+            BinaryOp::In => "in",
+            BinaryOp::Match => "match",
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Rem => "%",
+            BinaryOp::Pow => "**",
+        })
+    }
+}
+
+impl Display for ArrayElement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ArrayElement::Value(expr) => write!(f, "{}", expr),
+            ArrayElement::Splat(expr) => write!(f, "...{}", expr),
+        }
+    }
+}
+
+impl Display for ObjectAttribute {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjectAttribute::Value(name, expr) if expr.inferred_name() == Some(name) => {
+                write!(f, "{}", expr)
+            }
+            ObjectAttribute::Value(name, expr) => {
+                write!(f, "{}: {}", Value::String(name.clone()), expr)
+            }
+            ObjectAttribute::Splat(Expr::This) => f.write_str("..."),
+            ObjectAttribute::Splat(expr) => write!(f, "...{}", expr),
+            ObjectAttribute::Conditional(condition, value) => {
+                write!(f, "{} => {}", condition, value)
+            }
+        }
+    }
+}
@@ -0,0 +1,638 @@
+//! Compose GROQ queries without string interpolation
+//!
+//! Values never end up in the query text: every value becomes a `$pN` parameter which is
+//! sent next to the query, so user input cannot change the shape of a query.
+//!
+//! ```
+//! use sanity_rs::groq::builder::{defined, eq, Projection, Query};
+//!
+//! let query = Query::of_type("post")
+//!     .filter(defined("slug.current") & eq("category", "news"))
+//!     .order_desc("publishedAt")
+//!     .slice(0..10)
+//!     .project(Projection::new().field("title").deref("author", Projection::new().field("name")))
+//!     .build();
+//! assert_eq!(
+//!     query.query,
+//!     "*[_type == $p0 && defined(slug.current) && category == $p1] | order(publishedAt desc)[0...10]{title,author->{name}}"
+//! );
+//! ```
+use std::fmt::Write;
+use std::ops::{BitAnd, BitOr, Not, Range};
+
+use serde_json::{Map, Value};
+
+use crate::document::SanityDocument;
+
+/// A filter condition
+///
+/// Fields are attribute paths such as `title`, `slug.current` or `author->name` and are
+/// written into the query as is. Values are always sent as parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    Compare {
+        field: String,
+        op: &'static str,
+        value: Value,
+    },
+    In {
+        field: String,
+        values: Value,
+    },
+    Match {
+        field: String,
+        pattern: Value,
+    },
+    Defined(String),
+    References(Value),
+    And(Vec<Condition>),
+    Or(Vec<Condition>),
+    Not(Box<Condition>),
+    /// Trusted GROQ inserted as is
+    Raw(String),
+}
+
+/// `field == value`
+pub fn eq(field: &str, value: impl Into<Value>) -> Condition {
+    compare(field, "==", value)
+}
+
+/// `field != value`
+pub fn neq(field: &str, value: impl Into<Value>) -> Condition {
+    compare(field, "!=", value)
+}
+
+/// `field > value`
+pub fn gt(field: &str, value: impl Into<Value>) -> Condition {
+    compare(field, ">", value)
+}
+
+/// `field >= value`
+pub fn gte(field: &str, value: impl Into<Value>) -> Condition {
+    compare(field, ">=", value)
+}
+
+/// `field < value`
+pub fn lt(field: &str, value: impl Into<Value>) -> Condition {
+    compare(field, "<", value)
+}
+
+/// `field <= value`
+pub fn lte(field: &str, value: impl Into<Value>) -> Condition {
+    compare(field, "<=", value)
+}
+
+/// `field in [values]`
+pub fn is_in<V: Into<Value>>(field: &str, values: impl IntoIterator<Item = V>) -> Condition {
+    Condition::In {
+        field: field.to_string(),
+        values: Value::Array(values.into_iter().map(Into::into).collect()),
+    }
+}
+
+/// `field match pattern`, full text matching with `*` wildcards
+pub fn matches(field: &str, pattern: impl Into<Value>) -> Condition {
+    Condition::Match {
+        field: field.to_string(),
+        pattern: pattern.into(),
+    }
+}
+
+/// `defined(field)`
+pub fn defined(field: &str) -> Condition {
+    Condition::Defined(field.to_string())
+}
+
+/// `references(id)`, `id` may also be a list of ids
+pub fn references(id: impl Into<Value>) -> Condition {
+    Condition::References(id.into())
+}
+
+/// Trusted GROQ used as a condition as is
+pub fn raw(groq: &str) -> Condition {
+    Condition::Raw(groq.to_string())
+}
+
+fn compare(field: &str, op: &'static str, value: impl Into<Value>) -> Condition {
+    Condition::Compare {
+        field: field.to_string(),
+        op,
+        value: value.into(),
+    }
+}
+
+impl Condition {
+    /// Both conditions hold, `&&`
+    pub fn and(self, other: Condition) -> Condition {
+        match self {
+            Condition::And(mut conditions) => {
+                conditions.push(other);
+                Condition::And(conditions)
+            }
+            condition => Condition::And(vec![condition, other]),
+        }
+    }
+
+    /// Either condition holds, `||`
+    pub fn or(self, other: Condition) -> Condition {
+        match self {
+            Condition::Or(mut conditions) => {
+                conditions.push(other);
+                Condition::Or(conditions)
+            }
+            condition => Condition::Or(vec![condition, other]),
+        }
+    }
+
+    fn write(&self, out: &mut String, params: &mut Params) {
+        match self {
+            Condition::Compare { field, op, value } => {
+                let _ = write!(out, "{} {} {}", field, op, params.push(value.clone()));
+            }
+            Condition::In { field, values } => {
+                let _ = write!(out, "{} in {}", field, params.push(values.clone()));
+            }
+            Condition::Match { field, pattern } => {
+                let _ = write!(out, "{} match {}", field, params.push(pattern.clone()));
+            }
+            Condition::Defined(field) => {
+                let _ = write!(out, "defined({})", field);
+            }
+            Condition::References(id) => {
+                let _ = write!(out, "references({})", params.push(id.clone()));
+            }
+            Condition::And(conditions) => write_joined(out, params, conditions, " && ", true),
+            Condition::Or(conditions) => write_joined(out, params, conditions, " || ", false),
+            Condition::Not(condition) => {
+                out.push('!');
+                let group = !matches!(
+                    **condition,
+                    Condition::Defined(_) | Condition::References(_) | Condition::Not(_)
+                );
+                if group {
+                    out.push('(');
+                }
+                condition.write(out, params);
+                if group {
+                    out.push(')');
+                }
+            }
+            Condition::Raw(groq) => out.push_str(groq),
+        }
+    }
+}
+
+fn write_joined(
+    out: &mut String,
+    params: &mut Params,
+    conditions: &[Condition],
+    separator: &str,
+    and: bool,
+) {
+    for (index, condition) in conditions.iter().enumerate() {
+        if index > 0 {
+            out.push_str(separator);
+        }
+        // `||` binds looser than `&&`, raw conditions are grouped to be safe
+        let group = matches!(condition, Condition::Raw(_))
+            || (and && matches!(condition, Condition::Or(_)));
+        if group {
+            out.push('(');
+        }
+        condition.write(out, params);
+        if group {
+            out.push(')');
+        }
+    }
+}
+
+impl BitAnd for Condition {
+    type Output = Condition;
+
+    fn bitand(self, other: Condition) -> Condition {
+        self.and(other)
+    }
+}
+
+impl BitOr for Condition {
+    type Output = Condition;
+
+    fn bitor(self, other: Condition) -> Condition {
+        self.or(other)
+    }
+}
+
+impl Not for Condition {
+    type Output = Condition;
+
+    fn not(self) -> Condition {
+        Condition::Not(Box::new(self))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Asc,
+    Desc,
+}
+
+/// The fields of a projection
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Projection {
+    fields: Vec<String>,
+}
+
+impl Projection {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Select an attribute
+    pub fn field(mut self, name: &str) -> Self {
+        self.fields.push(name.to_string());
+        self
+    }
+
+    /// Select several attributes
+    pub fn fields(mut self, names: &[&str]) -> Self {
+        self.fields
+            .extend(names.iter().map(|name| name.to_string()));
+        self
+    }
+
+    /// Select every attribute, `...`
+    pub fn all(mut self) -> Self {
+        self.fields.push("...".to_string());
+        self
+    }
+
+    /// `"name": expression`, the expression is trusted GROQ
+    pub fn alias(mut self, name: &str, expression: &str) -> Self {
+        self.fields.push(format!(
+            "{}: {}",
+            Value::String(name.to_string()),
+            expression
+        ));
+        self
+    }
+
+    /// Project a nested object, `name{...}`
+    pub fn nested(mut self, name: &str, projection: Projection) -> Self {
+        self.fields.push(format!("{}{}", name, projection.render()));
+        self
+    }
+
+    /// Project every object of an array, `name[]{...}`
+    pub fn nested_array(mut self, name: &str, projection: Projection) -> Self {
+        self.fields
+            .push(format!("{}[]{}", name, projection.render()));
+        self
+    }
+
+    /// Follow a reference, `name->{...}`
+    pub fn deref(mut self, name: &str, projection: Projection) -> Self {
+        self.fields
+            .push(format!("{}->{}", name, projection.render()));
+        self
+    }
+
+    /// Follow an array of references, `name[]->{...}`
+    pub fn deref_array(mut self, name: &str, projection: Projection) -> Self {
+        self.fields
+            .push(format!("{}[]->{}", name, projection.render()));
+        self
+    }
+
+    /// Render the projection, `{a,b}`
+    pub fn render(&self) -> String {
+        format!("{{{}}}", self.fields.join(","))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Slice {
+    Index(usize),
+    Range(Range<usize>),
+}
+
+/// A query over the documents in a dataset
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Query {
+    filters: Vec<Condition>,
+    order: Vec<(String, Direction)>,
+    slice: Option<Slice>,
+    projection: Option<String>,
+    count: bool,
+}
+
+/// A rendered query with the values of its parameters
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuiltQuery {
+    pub query: String,
+    pub params: Map<String, Value>,
+}
+
+impl Query {
+    /// Every document, `*`
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// Documents of one type, `*[_type == $p0]`
+    pub fn of_type(type_name: &str) -> Self {
+        Self::all().filter(eq("_type", type_name))
+    }
+
+    /// Documents of a type deriving `SanityDocument`, projected with its fields
+    pub fn document<T: SanityDocument>() -> Self {
+        let mut query = Self::of_type(T::TYPE_NAME);
+        if T::fields().is_some() {
+            query.projection = Some(T::projection());
+        }
+        query
+    }
+
+    /// Add a filter, several filters must all hold
+    pub fn filter(mut self, condition: Condition) -> Self {
+        match condition {
+            Condition::And(conditions) => self.filters.extend(conditions),
+            condition => self.filters.push(condition),
+        }
+        self
+    }
+
+    /// Order by a field, may be called several times for secondary orderings
+    pub fn order(mut self, field: &str, direction: Direction) -> Self {
+        self.order.push((field.to_string(), direction));
+        self
+    }
+
+    pub fn order_asc(self, field: &str) -> Self {
+        self.order(field, Direction::Asc)
+    }
+
+    pub fn order_desc(self, field: &str) -> Self {
+        self.order(field, Direction::Desc)
+    }
+
+    /// Keep the documents in `range`, `[start...end]`
+    pub fn slice(mut self, range: Range<usize>) -> Self {
+        self.slice = Some(Slice::Range(range));
+        self
+    }
+
+    /// Select a single document, `[index]`
+    pub fn index(mut self, index: usize) -> Self {
+        self.slice = Some(Slice::Index(index));
+        self
+    }
+
+    /// Select the first document, `[0]`
+    pub fn first(self) -> Self {
+        self.index(0)
+    }
+
+    pub fn project(mut self, projection: Projection) -> Self {
+        self.projection = Some(projection.render());
+        self
+    }
+
+    /// Count the matching documents instead of returning them, `count(...)`
+    pub fn count(mut self) -> Self {
+        self.count = true;
+        self
+    }
+
+    /// Render the query and collect its parameters
+    pub fn build(&self) -> BuiltQuery {
+        let mut params = Params::default();
+        let mut query = String::from("*");
+        if !self.filters.is_empty() {
+            query.push('[');
+            write_joined(&mut query, &mut params, &self.filters, " && ", true);
+            query.push(']');
+        }
+        if !self.order.is_empty() {
+            let order: Vec<String> = self
+                .order
+                .iter()
+                .map(|(field, direction)| match direction {
+                    Direction::Asc => format!("{} asc", field),
+                    Direction::Desc => format!("{} desc", field),
+                })
+                .collect();
+            let _ = write!(query, " | order({})", order.join(", "));
+        }
+        match &self.slice {
+            Some(Slice::Index(index)) => {
+                let _ = write!(query, "[{}]", index);
+            }
+            Some(Slice::Range(range)) => {
+                let _ = write!(query, "[{}...{}]", range.start, range.end);
+            }
+            None => {}
+        }
+        if self.count {
+            query = format!("count({})", query);
+        } else if let Some(projection) = &self.projection {
+            query.push_str(projection);
+        }
+        BuiltQuery {
+            query,
+            params: params.values,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Params {
+    values: Map<String, Value>,
+}
+
+impl Params {
+    /// Store a value and return its placeholder
+    fn push(&mut self, value: Value) -> String {
+        let name = format!("p{}", self.values.len());
+        self.values.insert(name.clone(), value);
+        format!("${}", name)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::groq::parse;
+    use serde_json::json;
+
+    fn assert_query(query: &Query, expected: &str, params: Value) {
+        let built = query.build();
+        assert_eq!(built.query, expected);
+        assert_eq!(Value::Object(built.params), params);
+        parse(&built.query).expect("the builder emits valid GROQ");
+    }
+
+    #[test]
+    fn everything() {
+        assert_query(&Query::all(), "*", json!({}));
+        assert_query(&Query::all().count(), "count(*)", json!({}));
+    }
+
+    #[test]
+    fn of_type() {
+        assert_query(
+            &Query::of_type("post"),
+            "*[_type == $p0]",
+            json!({ "p0": "post" }),
+        );
+    }
+
+    #[test]
+    fn comparisons() {
+        let query = Query::of_type("product")
+            .filter(gt("price", 10))
+            .filter(lte("price", 99.5))
+            .filter(neq("status", "archived"))
+            .filter(gte("stock", 1) & lt("rating", 5));
+        assert_query(
+            &query,
+            "*[_type == $p0 && price > $p1 && price <= $p2 && status != $p3 && stock >= $p4 && rating < $p5]",
+            json!({ "p0": "product", "p1": 10, "p2": 99.5, "p3": "archived", "p4": 1, "p5": 5 }),
+        );
+    }
+
+    #[test]
+    fn boolean_combinators() {
+        let query = Query::all().filter(
+            (eq("_type", "post") | eq("_type", "page")) & !defined("hidden") & !eq("draft", true),
+        );
+        assert_query(
+            &query,
+            "*[(_type == $p0 || _type == $p1) && !defined(hidden) && !(draft == $p2)]",
+            json!({ "p0": "post", "p1": "page", "p2": true }),
+        );
+        let query = Query::all().filter(eq("a", 1).or(eq("b", 2).and(eq("c", 3))));
+        assert_query(
+            &query,
+            "*[(a == $p0 || b == $p1 && c == $p2)]",
+            json!({ "p0": 1, "p1": 2, "p2": 3 }),
+        );
+    }
+
+    #[test]
+    fn membership_and_text() {
+        let query = Query::all()
+            .filter(is_in("_type", ["post", "page"]))
+            .filter(matches("title", "rust*"))
+            .filter(references("author-1"))
+            .filter(references(vec!["tag-1", "tag-2"]));
+        assert_query(
+            &query,
+            "*[_type in $p0 && title match $p1 && references($p2) && references($p3)]",
+            json!({
+                "p0": ["post", "page"],
+                "p1": "rust*",
+                "p2": "author-1",
+                "p3": ["tag-1", "tag-2"]
+            }),
+        );
+    }
+
+    #[test]
+    fn values_are_never_interpolated() {
+        let query = Query::of_type("post").filter(eq("slug.current", r#"x"] || true || ["#));
+        assert_query(
+            &query,
+            "*[_type == $p0 && slug.current == $p1]",
+            json!({ "p0": "post", "p1": r#"x"] || true || ["# }),
+        );
+    }
+
+    #[test]
+    fn order_and_slices() {
+        assert_query(
+            &Query::of_type("post")
+                .order_desc("publishedAt")
+                .order_asc("title")
+                .slice(10..20),
+            "*[_type == $p0] | order(publishedAt desc, title asc)[10...20]",
+            json!({ "p0": "post" }),
+        );
+        assert_query(
+            &Query::of_type("post")
+                .order("_createdAt", Direction::Asc)
+                .first(),
+            "*[_type == $p0] | order(_createdAt asc)[0]",
+            json!({ "p0": "post" }),
+        );
+        assert_query(
+            &Query::of_type("post").index(3),
+            "*[_type == $p0][3]",
+            json!({ "p0": "post" }),
+        );
+    }
+
+    #[test]
+    fn projections() {
+        let projection = Projection::new()
+            .fields(&["_id", "title"])
+            .alias("slug", "slug.current")
+            .deref(
+                "author",
+                Projection::new()
+                    .field("name")
+                    .nested("image", Projection::new().all()),
+            )
+            .deref_array("categories", Projection::new().field("title"))
+            .nested_array(
+                "gallery",
+                Projection::new()
+                    .field("alt")
+                    .deref("asset", Projection::new().field("url")),
+            );
+        assert_query(
+            &Query::of_type("post").project(projection),
+            r#"*[_type == $p0]{_id,title,"slug": slug.current,author->{name,image{...}},categories[]->{title},gallery[]{alt,asset->{url}}}"#,
+            json!({ "p0": "post" }),
+        );
+    }
+
+    #[test]
+    fn count_ignores_projection() {
+        assert_query(
+            &Query::of_type("post")
+                .filter(defined("publishedAt"))
+                .project(Projection::new().field("title"))
+                .count(),
+            "count(*[_type == $p0 && defined(publishedAt)])",
+            json!({ "p0": "post" }),
+        );
+    }
+
+    #[test]
+    fn raw_conditions() {
+        assert_query(
+            &Query::all().filter(raw("dateTime(publishedAt) < dateTime(now())") & eq("a", 1)),
+            "*[(dateTime(publishedAt) < dateTime(now())) && a == $p0]",
+            json!({ "p0": 1 }),
+        );
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derived_document() {
+        use crate::document::SanityDocument;
+        use serde::Deserialize;
+
+        #[allow(dead_code)]
+        #[derive(Debug, Deserialize, SanityDocument)]
+        struct Post {
+            title: String,
+        }
+
+        assert_query(
+            &Query::document::<Post>().filter(defined("title")).first(),
+            "*[_type == $p0 && defined(title)][0]{title}",
+            json!({ "p0": "post" }),
+        );
+    }
+}
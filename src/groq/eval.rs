@@ -0,0 +1,1229 @@
+//! Evaluate GROQ against documents held in memory
+//!
+//! ```
+//! use sanity_rs::groq::eval::Evaluator;
+//! use serde_json::json;
+//!
+//! let documents = vec![
+//!     json!({"_id": "a", "_type": "author", "name": "Ada"}),
+//!     json!({"_id": "p", "_type": "post", "title": "Hello", "author": {"_ref": "a"}}),
+//! ];
+//! let result = Evaluator::new(&documents)
+//!     .query(r#"*[_type == "post"][0]{title, "author": author->name}"#)
+//!     .unwrap();
+//! assert_eq!(result, json!({"title": "Hello", "author": "Ada"}));
+//! ```
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{Map, Value};
+
+use super::ast::{ArrayElement, BinaryOp, Expr, ObjectAttribute};
+use super::parse;
+use super::parser::number_value;
+use crate::error::EvalError;
+
+/// Runs queries against a dataset, references are resolved within the same dataset
+pub struct Evaluator<'a> {
+    documents: &'a [Value],
+    ids: HashMap<&'a str, usize>,
+    params: Map<String, Value>,
+}
+
+/// The value `@` refers to and the enclosing scopes `^` walks up
+struct Scope<'s> {
+    this: &'s Value,
+    parent: Option<&'s Scope<'s>>,
+}
+
+impl<'s> Scope<'s> {
+    fn nested(&'s self, this: &'s Value) -> Scope<'s> {
+        Scope {
+            this,
+            parent: Some(self),
+        }
+    }
+}
+
+impl<'a> Evaluator<'a> {
+    pub fn new(documents: &'a [Value]) -> Self {
+        let ids = documents
+            .iter()
+            .enumerate()
+            .filter_map(|(index, document)| Some((document.get("_id")?.as_str()?, index)))
+            .collect();
+        Self {
+            documents,
+            ids,
+            params: Map::new(),
+        }
+    }
+
+    /// Set the value of `$name`
+    pub fn param(mut self, name: &str, value: impl Into<Value>) -> Self {
+        self.params.insert(name.to_string(), value.into());
+        self
+    }
+
+    /// Set the values of several parameters, e.g. those of a `BuiltQuery`
+    pub fn params(mut self, params: &Map<String, Value>) -> Self {
+        self.params.extend(
+            params
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone())),
+        );
+        self
+    }
+
+    /// Parse and evaluate a query
+    pub fn query(&self, query: &str) -> Result<Value, EvalError> {
+        self.evaluate(&parse(query)?)
+    }
+
+    /// Evaluate a parsed query
+    pub fn evaluate(&self, expr: &Expr) -> Result<Value, EvalError> {
+        self.eval(
+            expr,
+            &Scope {
+                this: &Value::Null,
+                parent: None,
+            },
+        )
+    }
+
+    fn eval(&self, expr: &Expr, scope: &Scope) -> Result<Value, EvalError> {
+        Ok(match expr {
+            Expr::Everything => Value::Array(self.documents.to_vec()),
+            Expr::This => scope.this.clone(),
+            Expr::Parent(levels) => {
+                let mut current = scope;
+                for _ in 0..*levels {
+                    match current.parent {
+                        Some(parent) => current = parent,
+                        None => return Ok(Value::Null),
+                    }
+                }
+                current.this.clone()
+            }
+            Expr::Param(name) => self
+                .params
+                .get(name)
+                .cloned()
+                .ok_or_else(|| EvalError::MissingParam(name.clone()))?,
+            Expr::Literal(value) => value.clone(),
+            Expr::Array(elements) => {
+                let mut result = Vec::new();
+                for element in elements {
+                    match element {
+                        ArrayElement::Value(expr) => result.push(self.eval(expr, scope)?),
+                        ArrayElement::Splat(expr) => {
+                            if let Value::Array(values) = self.eval(expr, scope)? {
+                                result.extend(values);
+                            }
+                        }
+                    }
+                }
+                Value::Array(result)
+            }
+            Expr::Object(attributes) => self.object(attributes, scope)?,
+            Expr::Attribute(name) => attribute(scope.this, name),
+            Expr::Group(inner) => self.eval(inner, scope)?,
+            Expr::AccessAttribute(base, name) => match self.eval(base, scope)? {
+                Value::Array(values) => {
+                    Value::Array(values.iter().map(|value| attribute(value, name)).collect())
+                }
+                value => attribute(&value, name),
+            },
+            Expr::AccessElement(base, index) => {
+                element(self.eval(base, scope)?, &self.eval(index, scope)?)
+            }
+            Expr::Slice {
+                base,
+                start,
+                end,
+                inclusive,
+            } => {
+                let base = self.eval(base, scope)?;
+                let start = self.eval(start, scope)?.as_f64();
+                let end = self.eval(end, scope)?.as_f64();
+                match (base, start, end) {
+                    (Value::Array(values), Some(start), Some(end)) => {
+                        let (start, end) = slice_bounds(start, end, *inclusive, values.len());
+                        Value::Array(values[start..end].to_vec())
+                    }
+                    (Value::String(string), Some(start), Some(end)) => {
+                        let chars: Vec<char> = string.chars().collect();
+                        let (start, end) = slice_bounds(start, end, *inclusive, chars.len());
+                        Value::String(chars[start..end].iter().collect())
+                    }
+                    _ => Value::Null,
+                }
+            }
+            Expr::Filter(base, condition) => {
+                let keep = |value: &Value| -> Result<bool, EvalError> {
+                    Ok(self.eval(condition, &scope.nested(value))? == Value::Bool(true))
+                };
+                match base.as_ref() {
+                    // The dataset is filtered in place, only the documents that match are copied
+                    Expr::Everything if !is_constant(condition) => {
+                        let mut result = Vec::new();
+                        for document in self.documents {
+                            if keep(document)? {
+                                result.push(document.clone());
+                            }
+                        }
+                        Value::Array(result)
+                    }
+                    base => match self.eval(base, scope)? {
+                        // A parameter between brackets is an index or an attribute name once it
+                        // is known
+                        base if is_constant(condition) => match self.eval(condition, scope)? {
+                            index @ Value::Number(_) => element(base, &index),
+                            Value::String(name) => match base {
+                                Value::Array(values) => Value::Array(
+                                    values.iter().map(|value| attribute(value, &name)).collect(),
+                                ),
+                                value => attribute(&value, &name),
+                            },
+                            Value::Bool(true) if base.is_array() => base,
+                            Value::Bool(false) if base.is_array() => Value::Array(Vec::new()),
+                            _ => Value::Null,
+                        },
+                        Value::Array(values) => {
+                            let mut result = Vec::new();
+                            for value in values {
+                                if keep(&value)? {
+                                    result.push(value);
+                                }
+                            }
+                            Value::Array(result)
+                        }
+                        _ => Value::Null,
+                    },
+                }
+            }
+            Expr::ArrayTraversal(base) => match self.eval(base, scope)? {
+                Value::Array(values) => Value::Array(
+                    values
+                        .into_iter()
+                        .flat_map(|value| match value {
+                            Value::Array(inner) => inner,
+                            value => vec![value],
+                        })
+                        .collect(),
+                ),
+                _ => Value::Null,
+            },
+            Expr::Projection(base, attributes) => match self.eval(base, scope)? {
+                Value::Array(values) => {
+                    let mut result = Vec::new();
+                    for value in values {
+                        result.push(self.project(value, attributes, scope)?);
+                    }
+                    Value::Array(result)
+                }
+                value => self.project(value, attributes, scope)?,
+            },
+            Expr::Deref(base, name) => {
+                let follow = |value: &Value| {
+                    let target = self.dereference(value);
+                    match name {
+                        Some(name) => attribute(&target, name),
+                        None => target,
+                    }
+                };
+                match self.eval(base, scope)? {
+                    Value::Array(values) => Value::Array(values.iter().map(follow).collect()),
+                    value => follow(&value),
+                }
+            }
+            Expr::FuncCall {
+                namespace,
+                name,
+                args,
+            } => self.call(namespace, name, args, scope)?,
+            Expr::PipeCall { base, name, args } => {
+                let values = match self.eval(base, scope)? {
+                    Value::Array(values) => values,
+                    _ => return Ok(Value::Null),
+                };
+                match name.as_str() {
+                    "order" => Value::Array(self.order(values, args, scope)?),
+                    "score" => Value::Array(self.score(values, args, scope)?),
+                    _ => {
+                        return Err(EvalError::UnknownFunction {
+                            namespace: "pipe".to_string(),
+                            name: name.clone(),
+                        })
+                    }
+                }
+            }
+            // Only meaningful inside `select()`, `in` and `order()`
+            Expr::Pair(..) | Expr::Range { .. } => Value::Null,
+            Expr::Asc(inner) | Expr::Desc(inner) => self.eval(inner, scope)?,
+            Expr::And(left, right) => match (self.eval(left, scope)?, self.eval(right, scope)?) {
+                (Value::Bool(false), _) | (_, Value::Bool(false)) => Value::Bool(false),
+                (Value::Bool(true), Value::Bool(true)) => Value::Bool(true),
+                _ => Value::Null,
+            },
+            Expr::Or(left, right) => match (self.eval(left, scope)?, self.eval(right, scope)?) {
+                (Value::Bool(true), _) | (_, Value::Bool(true)) => Value::Bool(true),
+                (Value::Bool(false), Value::Bool(false)) => Value::Bool(false),
+                _ => Value::Null,
+            },
+            Expr::Not(inner) => match self.eval(inner, scope)? {
+                Value::Bool(value) => Value::Bool(!value),
+                _ => Value::Null,
+            },
+            Expr::Neg(inner) => match self.eval(inner, scope)?.as_f64() {
+                Some(number) => number_value(-number),
+                None => Value::Null,
+            },
+            Expr::Pos(inner) => match self.eval(inner, scope)? {
+                number @ Value::Number(_) => number,
+                _ => Value::Null,
+            },
+            Expr::Binary(BinaryOp::In, left, right) => {
+                let value = self.eval(left, scope)?;
+                match right.as_ref() {
+                    Expr::Range {
+                        start,
+                        end,
+                        inclusive,
+                    } => {
+                        let start = self.eval(start, scope)?;
+                        let end = self.eval(end, scope)?;
+                        match (compare(&start, &value), compare(&value, &end)) {
+                            (Some(lower), Some(upper)) => Value::Bool(
+                                lower != Ordering::Greater
+                                    && (upper == Ordering::Less
+                                        || (*inclusive && upper == Ordering::Equal)),
+                            ),
+                            _ => Value::Null,
+                        }
+                    }
+                    Expr::FuncCall { name, args, .. } if name == "path" => {
+                        match (&value, args.first().map(|arg| self.eval(arg, scope))) {
+                            (Value::String(id), Some(pattern)) => match pattern? {
+                                Value::String(pattern) => Value::Bool(path_matches(&pattern, id)),
+                                _ => Value::Null,
+                            },
+                            _ => Value::Bool(false),
+                        }
+                    }
+                    right => match self.eval(right, scope)? {
+                        Value::Array(values) => {
+                            Value::Bool(values.iter().any(|candidate| equal(&value, candidate)))
+                        }
+                        _ => Value::Null,
+                    },
+                }
+            }
+            Expr::Binary(op, left, right) => {
+                let (left_value, right_value) = (self.eval(left, scope)?, self.eval(right, scope)?);
+                match (op, is_date_time(left), is_date_time(right)) {
+                    (BinaryOp::Add | BinaryOp::Sub, true, _) | (BinaryOp::Sub, _, true) => {
+                        date_arithmetic(*op, &left_value, &right_value, is_date_time(right))
+                    }
+                    _ => binary(*op, left_value, right_value),
+                }
+            }
+        })
+    }
+
+    fn object(&self, attributes: &[ObjectAttribute], scope: &Scope) -> Result<Value, EvalError> {
+        let mut object = Map::new();
+        for attribute in attributes {
+            match attribute {
+                ObjectAttribute::Value(name, expr) => {
+                    object.insert(name.clone(), self.eval(expr, scope)?);
+                }
+                ObjectAttribute::Splat(expr) => {
+                    if let Value::Object(values) = self.eval(expr, scope)? {
+                        object.extend(values);
+                    }
+                }
+                ObjectAttribute::Conditional(condition, value) => {
+                    if self.eval(condition, scope)? == Value::Bool(true) {
+                        if let Value::Object(values) = self.eval(value, scope)? {
+                            object.extend(values);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(Value::Object(object))
+    }
+
+    fn project(
+        &self,
+        value: Value,
+        attributes: &[ObjectAttribute],
+        scope: &Scope,
+    ) -> Result<Value, EvalError> {
+        match value {
+            Value::Object(_) => self.object(attributes, &scope.nested(&value)),
+            _ => Ok(Value::Null),
+        }
+    }
+
+    /// The document a reference points at, or null
+    fn dereference(&self, value: &Value) -> Value {
+        value
+            .get("_ref")
+            .and_then(Value::as_str)
+            .and_then(|id| self.ids.get(id))
+            .map(|index| self.documents[*index].clone())
+            .unwrap_or(Value::Null)
+    }
+
+    fn order(
+        &self,
+        values: Vec<Value>,
+        args: &[Expr],
+        scope: &Scope,
+    ) -> Result<Vec<Value>, EvalError> {
+        let mut keyed = Vec::with_capacity(values.len());
+        for value in values {
+            let nested = scope.nested(&value);
+            let mut keys = Vec::with_capacity(args.len());
+            for arg in args {
+                keys.push(self.eval(arg, &nested)?);
+            }
+            keyed.push((keys, value));
+        }
+        keyed.sort_by(|(left, _), (right, _)| {
+            args.iter()
+                .zip(left.iter().zip(right.iter()))
+                .map(|(arg, (left, right))| {
+                    let ordering = order_values(left, right);
+                    match arg {
+                        Expr::Desc(_) => ordering.reverse(),
+                        _ => ordering,
+                    }
+                })
+                .find(|ordering| *ordering != Ordering::Equal)
+                .unwrap_or(Ordering::Equal)
+        });
+        Ok(keyed.into_iter().map(|(_, value)| value).collect())
+    }
+
+    /// Add `_score`, the number of conditions each document satisfies, the order is kept so
+    /// rank with `order(_score desc)`
+    fn score(
+        &self,
+        values: Vec<Value>,
+        args: &[Expr],
+        scope: &Scope,
+    ) -> Result<Vec<Value>, EvalError> {
+        let mut scored = Vec::with_capacity(values.len());
+        for value in values {
+            let nested = scope.nested(&value);
+            let mut score = 0;
+            for arg in args {
+                if self.eval(arg, &nested)? == Value::Bool(true) {
+                    score += 1;
+                }
+            }
+            match value {
+                Value::Object(mut object) => {
+                    object.insert("_score".to_string(), Value::from(score));
+                    scored.push(Value::Object(object));
+                }
+                value => scored.push(value),
+            }
+        }
+        Ok(scored)
+    }
+
+    fn call(
+        &self,
+        namespace: &str,
+        name: &str,
+        args: &[Expr],
+        scope: &Scope,
+    ) -> Result<Value, EvalError> {
+        let function = format!("{}::{}", namespace, name);
+        let arity = |count: usize| {
+            if args.len() == count {
+                Ok(())
+            } else {
+                Err(EvalError::InvalidArguments {
+                    function: function.clone(),
+                    message: format!("expected {} arguments, got {}", count, args.len()),
+                })
+            }
+        };
+        let mut values = Vec::with_capacity(args.len());
+        // `select()` evaluates its pairs lazily
+        if (namespace, name) != ("global", "select") {
+            for arg in args {
+                values.push(self.eval(arg, scope)?);
+            }
+        }
+        Ok(match (namespace, name) {
+            ("global", "count") => {
+                arity(1)?;
+                match &values[0] {
+                    Value::Array(values) => Value::from(values.len()),
+                    _ => Value::Null,
+                }
+            }
+            ("global", "defined") => {
+                arity(1)?;
+                Value::Bool(!values[0].is_null())
+            }
+            ("global", "coalesce") => values
+                .into_iter()
+                .find(|value| !value.is_null())
+                .unwrap_or(Value::Null),
+            ("global", "select") => {
+                for arg in args {
+                    match arg {
+                        Expr::Pair(condition, value) => {
+                            if self.eval(condition, scope)? == Value::Bool(true) {
+                                return self.eval(value, scope);
+                            }
+                        }
+                        fallback => return self.eval(fallback, scope),
+                    }
+                }
+                Value::Null
+            }
+            ("global", "references") => {
+                let ids: Vec<String> = values
+                    .iter()
+                    .flat_map(|value| match value {
+                        Value::Array(values) => values.clone(),
+                        value => vec![value.clone()],
+                    })
+                    .filter_map(|value| value.as_str().map(str::to_string))
+                    .collect();
+                Value::Bool(contains_reference(scope.this, &ids))
+            }
+            ("global", "dateTime") => {
+                arity(1)?;
+                match &values[0] {
+                    Value::String(string) => parse_date_time(string)
+                        .map(format_date_time)
+                        .unwrap_or(Value::Null),
+                    _ => Value::Null,
+                }
+            }
+            ("global", "now") => {
+                arity(0)?;
+                Value::String(Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true))
+            }
+            ("global", "length") => {
+                arity(1)?;
+                match &values[0] {
+                    Value::String(string) => Value::from(string.chars().count()),
+                    Value::Array(values) => Value::from(values.len()),
+                    _ => Value::Null,
+                }
+            }
+            ("global", "lower") | ("string", "lower") => {
+                arity(1)?;
+                map_string(&values[0], |string| string.to_lowercase())
+            }
+            ("global", "upper") | ("string", "upper") => {
+                arity(1)?;
+                map_string(&values[0], |string| string.to_uppercase())
+            }
+            ("global", "string") => {
+                arity(1)?;
+                match &values[0] {
+                    Value::String(_) => values[0].clone(),
+                    Value::Number(_) | Value::Bool(_) => Value::String(values[0].to_string()),
+                    _ => Value::Null,
+                }
+            }
+            ("global", "round") => {
+                let precision = match values.get(1) {
+                    Some(precision) => precision.as_i64().unwrap_or(0),
+                    None => 0,
+                };
+                match values.first().and_then(Value::as_f64) {
+                    Some(number) => {
+                        let factor = 10f64.powi(precision as i32);
+                        number_value((number * factor).round() / factor)
+                    }
+                    None => Value::Null,
+                }
+            }
+            ("global", "identity") => Value::Null,
+            ("string", "split") => {
+                arity(2)?;
+                match (&values[0], &values[1]) {
+                    (Value::String(string), Value::String(separator)) => {
+                        Value::Array(if separator.is_empty() {
+                            string
+                                .chars()
+                                .map(|c| Value::String(c.to_string()))
+                                .collect()
+                        } else {
+                            string.split(separator.as_str()).map(Value::from).collect()
+                        })
+                    }
+                    _ => Value::Null,
+                }
+            }
+            ("string", "startsWith") => {
+                arity(2)?;
+                match (&values[0], &values[1]) {
+                    (Value::String(string), Value::String(prefix)) => {
+                        Value::Bool(string.starts_with(prefix.as_str()))
+                    }
+                    _ => Value::Null,
+                }
+            }
+            ("array", "compact") => {
+                arity(1)?;
+                match &values[0] {
+                    Value::Array(values) => Value::Array(
+                        values
+                            .iter()
+                            .filter(|value| !value.is_null())
+                            .cloned()
+                            .collect(),
+                    ),
+                    _ => Value::Null,
+                }
+            }
+            ("array", "unique") => {
+                arity(1)?;
+                match &values[0] {
+                    Value::Array(values) => {
+                        let mut unique: Vec<Value> = Vec::new();
+                        for value in values {
+                            let primitive = !matches!(value, Value::Array(_) | Value::Object(_));
+                            if !primitive || !unique.iter().any(|seen| equal(seen, value)) {
+                                unique.push(value.clone());
+                            }
+                        }
+                        Value::Array(unique)
+                    }
+                    _ => Value::Null,
+                }
+            }
+            ("array", "join") => {
+                arity(2)?;
+                match (&values[0], &values[1]) {
+                    (Value::Array(values), Value::String(separator)) => {
+                        let strings: Option<Vec<String>> = values
+                            .iter()
+                            .map(|value| match value {
+                                Value::String(string) => Some(string.clone()),
+                                Value::Number(_) | Value::Bool(_) => Some(value.to_string()),
+                                _ => None,
+                            })
+                            .collect();
+                        strings
+                            .map(|strings| Value::String(strings.join(separator)))
+                            .unwrap_or(Value::Null)
+                    }
+                    _ => Value::Null,
+                }
+            }
+            ("array", "intersects") => {
+                arity(2)?;
+                match (&values[0], &values[1]) {
+                    (Value::Array(left), Value::Array(right)) => Value::Bool(
+                        left.iter()
+                            .any(|value| right.iter().any(|other| equal(value, other))),
+                    ),
+                    _ => Value::Null,
+                }
+            }
+            ("math", "sum" | "avg" | "min" | "max") => {
+                arity(1)?;
+                let numbers: Option<Vec<f64>> = match &values[0] {
+                    Value::Array(values) => values
+                        .iter()
+                        .filter(|value| !value.is_null())
+                        .map(Value::as_f64)
+                        .collect(),
+                    _ => None,
+                };
+                match (name, numbers) {
+                    ("sum", Some(numbers)) => number_value(numbers.iter().sum()),
+                    (_, Some(numbers)) if numbers.is_empty() => Value::Null,
+                    ("avg", Some(numbers)) => {
+                        number_value(numbers.iter().sum::<f64>() / numbers.len() as f64)
+                    }
+                    ("min", Some(numbers)) => {
+                        number_value(numbers.into_iter().fold(f64::MAX, f64::min))
+                    }
+                    ("max", Some(numbers)) => {
+                        number_value(numbers.into_iter().fold(f64::MIN, f64::max))
+                    }
+                    _ => Value::Null,
+                }
+            }
+            ("pt", "text") => {
+                arity(1)?;
+                let blocks = match &values[0] {
+                    Value::Array(blocks) => blocks.clone(),
+                    block @ Value::Object(_) => vec![block.clone()],
+                    _ => return Ok(Value::Null),
+                };
+                let texts: Vec<String> = blocks
+                    .iter()
+                    .filter_map(|block| block.get("children")?.as_array())
+                    .map(|children| {
+                        children
+                            .iter()
+                            .filter_map(|child| child.get("text")?.as_str())
+                            .collect()
+                    })
+                    .collect();
+                Value::String(texts.join("\n\n"))
+            }
+            _ => {
+                return Err(EvalError::UnknownFunction {
+                    namespace: namespace.to_string(),
+                    name: name.to_string(),
+                })
+            }
+        })
+    }
+}
+
+fn attribute(value: &Value, name: &str) -> Value {
+    match value {
+        Value::Object(object) => object.get(name).cloned().unwrap_or(Value::Null),
+        _ => Value::Null,
+    }
+}
+
+fn map_string(value: &Value, f: impl Fn(&str) -> String) -> Value {
+    match value {
+        Value::String(string) => Value::String(f(string)),
+        _ => Value::Null,
+    }
+}
+
+/// The element at `index`, negative indices count from the end
+fn element(base: Value, index: &Value) -> Value {
+    match (base, index.as_f64()) {
+        (Value::Array(values), Some(index)) if index.fract() == 0.0 => {
+            resolve_index(index, values.len())
+                .and_then(|index| values.get(index).cloned())
+                .unwrap_or(Value::Null)
+        }
+        _ => Value::Null,
+    }
+}
+
+/// Whether an expression has the same value for every document, e.g. `$index + 1`
+fn is_constant(expr: &Expr) -> bool {
+    match expr {
+        Expr::Param(_) => true,
+        Expr::Literal(_) => true,
+        Expr::Group(inner) | Expr::Neg(inner) | Expr::Pos(inner) => is_constant(inner),
+        Expr::Binary(_, left, right) => is_constant(left) && is_constant(right),
+        _ => false,
+    }
+}
+
+/// An index into a list of `len` elements, negative indices count from the end
+fn resolve_index(index: f64, len: usize) -> Option<usize> {
+    let index = index as i64;
+    let index = if index < 0 { len as i64 + index } else { index };
+    (0..len as i64).contains(&index).then_some(index as usize)
+}
+
+fn slice_bounds(start: f64, end: f64, inclusive: bool, len: usize) -> (usize, usize) {
+    let clamp = |index: f64| {
+        let index = index as i64;
+        let index = if index < 0 { len as i64 + index } else { index };
+        index.clamp(0, len as i64) as usize
+    };
+    let start = clamp(start);
+    let end = if inclusive {
+        clamp(end).saturating_add(1).min(len)
+    } else {
+        clamp(end)
+    };
+    (start, end.max(start))
+}
+
+/// Equality of primitives, numbers compare by value
+fn equal(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Number(left), Value::Number(right)) => left.as_f64() == right.as_f64(),
+        (Value::Array(_) | Value::Object(_), _) | (_, Value::Array(_) | Value::Object(_)) => false,
+        (left, right) => left == right,
+    }
+}
+
+/// Ordering of two values of the same comparable type
+fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::Number(left), Value::Number(right)) => left.as_f64()?.partial_cmp(&right.as_f64()?),
+        (Value::String(left), Value::String(right)) => Some(left.cmp(right)),
+        (Value::Bool(left), Value::Bool(right)) => Some(left.cmp(right)),
+        _ => None,
+    }
+}
+
+/// The total ordering used by `order()`: numbers, strings, booleans, anything else, null
+fn order_values(left: &Value, right: &Value) -> Ordering {
+    let rank = |value: &Value| match value {
+        Value::Number(_) => 0,
+        Value::String(_) => 1,
+        Value::Bool(_) => 2,
+        Value::Null => 4,
+        _ => 3,
+    };
+    compare(left, right).unwrap_or_else(|| rank(left).cmp(&rank(right)))
+}
+
+fn binary(op: BinaryOp, left: Value, right: Value) -> Value {
+    let numbers = left.as_f64().zip(right.as_f64());
+    match op {
+        BinaryOp::Eq => match (&left, &right) {
+            (Value::Array(_) | Value::Object(_), _) | (_, Value::Array(_) | Value::Object(_)) => {
+                Value::Null
+            }
+            _ => Value::Bool(equal(&left, &right)),
+        },
+        BinaryOp::NotEq => match binary(BinaryOp::Eq, left, right) {
+            Value::Bool(equal) => Value::Bool(!equal),
+            other => other,
+        },
+        BinaryOp::Lt | BinaryOp::Lte | BinaryOp::Gt | BinaryOp::Gte => {
+            match compare(&left, &right) {
+                Some(ordering) => Value::Bool(match op {
+                    BinaryOp::Lt => ordering == Ordering::Less,
+                    BinaryOp::Lte => ordering != Ordering::Greater,
+                    BinaryOp::Gt => ordering == Ordering::Greater,
+                    _ => ordering != Ordering::Less,
+                }),
+                None => Value::Null,
+            }
+        }
+        BinaryOp::Match => {
+            let patterns = match &right {
+                Value::Array(values) => values.clone(),
+                value => vec![value.clone()],
+            };
+            let texts = match &left {
+                Value::Array(values) => values.clone(),
+                value => vec![value.clone()],
+            };
+            let words: Vec<String> = texts
+                .iter()
+                .filter_map(Value::as_str)
+                .flat_map(|text| tokens(text, false))
+                .collect();
+            let patterns: Vec<String> = patterns
+                .iter()
+                .filter_map(Value::as_str)
+                .flat_map(|pattern| tokens(pattern, true))
+                .collect();
+            Value::Bool(
+                !patterns.is_empty()
+                    && patterns.iter().all(|pattern| {
+                        words
+                            .iter()
+                            .any(|word| glob_matches(pattern.as_bytes(), word.as_bytes()))
+                    }),
+            )
+        }
+        BinaryOp::Add => match (left, right) {
+            (Value::String(left), Value::String(right)) => Value::String(left + &right),
+            (Value::Array(mut left), Value::Array(right)) => {
+                left.extend(right);
+                Value::Array(left)
+            }
+            (Value::Object(mut left), Value::Object(right)) => {
+                left.extend(right);
+                Value::Object(left)
+            }
+            _ => numbers
+                .map(|(left, right)| number_value(left + right))
+                .unwrap_or(Value::Null),
+        },
+        BinaryOp::Sub => numbers
+            .map(|(left, right)| number_value(left - right))
+            .unwrap_or(Value::Null),
+        BinaryOp::Mul => numbers
+            .map(|(left, right)| number_value(left * right))
+            .unwrap_or(Value::Null),
+        BinaryOp::Div => match numbers {
+            Some((_, 0.0)) => Value::Null,
+            Some((left, right)) => number_value(left / right),
+            None => Value::Null,
+        },
+        BinaryOp::Rem => match numbers {
+            Some((_, 0.0)) => Value::Null,
+            Some((left, right)) => number_value(left % right),
+            None => Value::Null,
+        },
+        BinaryOp::Pow => numbers
+            .map(|(left, right)| number_value(left.powf(right)))
+            .unwrap_or(Value::Null),
+        // Handled in `Evaluator::eval`, `in` needs the unevaluated right hand side
+        BinaryOp::In => Value::Null,
+    }
+}
+
+/// Lowercased words of a text, patterns keep their `*` wildcards
+fn tokens(text: &str, pattern: bool) -> Vec<String> {
+    text.split(|c: char| !(c.is_alphanumeric() || (pattern && c == '*')))
+        .filter(|token| !token.is_empty() && *token != "*")
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Match `text` against a pattern where `*` matches any run of bytes
+///
+/// Only the last `*` is retried, which is enough since an earlier one can match whatever a
+/// later one would have.
+fn glob_matches(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and of the text it currently stops at
+    let mut star = None;
+    while t < text.len() {
+        if pattern.get(p) == Some(&b'*') {
+            star = Some((p, t));
+            p += 1;
+        } else if pattern.get(p) == Some(&text[t]) {
+            p += 1;
+            t += 1;
+        } else if let Some((star_p, star_t)) = star {
+            star = Some((star_p, star_t + 1));
+            p = star_p + 1;
+            t = star_t + 1;
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+/// Match a document id against a `path()` pattern, `*` stops at `.` and `**` does not
+fn path_matches(pattern: &str, id: &str) -> bool {
+    fn matches(pattern: &[u8], id: &[u8]) -> bool {
+        match pattern {
+            [] => id.is_empty(),
+            [b'*', b'*', rest @ ..] => (0..=id.len()).any(|skip| matches(rest, &id[skip..])),
+            [b'*', rest @ ..] => {
+                let segment = id.iter().position(|c| *c == b'.').unwrap_or(id.len());
+                (0..=segment).any(|skip| matches(rest, &id[skip..]))
+            }
+            [c, rest @ ..] => id.first() == Some(c) && matches(rest, &id[1..]),
+        }
+    }
+    matches(pattern.as_bytes(), id.as_bytes())
+}
+
+fn contains_reference(value: &Value, ids: &[String]) -> bool {
+    match value {
+        Value::Object(object) => {
+            matches!(object.get("_ref"), Some(Value::String(id)) if ids.contains(id))
+                || object.values().any(|value| contains_reference(value, ids))
+        }
+        Value::Array(values) => values.iter().any(|value| contains_reference(value, ids)),
+        _ => false,
+    }
+}
+
+/// Whether an expression yields a datetime, strings only take part in date arithmetic
+/// once they went through `dateTime()`
+fn is_date_time(expr: &Expr) -> bool {
+    match expr {
+        Expr::FuncCall {
+            namespace, name, ..
+        } => namespace == "global" && name == "dateTime",
+        Expr::Group(inner) => is_date_time(inner),
+        Expr::Binary(BinaryOp::Add | BinaryOp::Sub, left, right) => {
+            is_date_time(left) && !is_date_time(right)
+        }
+        _ => false,
+    }
+}
+
+/// A datetime plus or minus seconds, or the seconds between two datetimes
+fn date_arithmetic(op: BinaryOp, left: &Value, right: &Value, right_date: bool) -> Value {
+    let Some(left) = left.as_str().and_then(parse_date_time) else {
+        return Value::Null;
+    };
+    if right_date {
+        return match (op, right.as_str().and_then(parse_date_time)) {
+            (BinaryOp::Sub, Some(right)) => {
+                number_value((left - right).num_milliseconds() as f64 / 1000.0)
+            }
+            _ => Value::Null,
+        };
+    }
+    let Some(seconds) = right.as_f64() else {
+        return Value::Null;
+    };
+    let duration = chrono::Duration::milliseconds((seconds * 1000.0) as i64);
+    format_date_time(match op {
+        BinaryOp::Add => left + duration,
+        _ => left - duration,
+    })
+}
+
+fn parse_date_time(string: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(string)
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+fn format_date_time(date: DateTime<Utc>) -> Value {
+    Value::String(date.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn dataset() -> Vec<Value> {
+        vec![
+            json!({"_id": "ada", "_type": "author", "name": "Ada Lovelace"}),
+            json!({"_id": "grace", "_type": "author", "name": "Grace Hopper"}),
+            json!({"_id": "rust", "_type": "category", "title": "Rust"}),
+            json!({"_id": "web", "_type": "category", "title": "Web"}),
+            json!({
+                "_id": "post-1",
+                "_type": "post",
+                "title": "Hello Rust",
+                "publishedAt": "2024-01-05T10:00:00Z",
+                "views": 120,
+                "author": {"_ref": "ada", "_type": "reference"},
+                "categories": [{"_ref": "rust", "_type": "reference", "_key": "a"}],
+                "body": [
+                    {"_type": "block", "children": [{"_type": "span", "text": "First "}, {"_type": "span", "text": "words"}]},
+                    {"_type": "block", "children": [{"_type": "span", "text": "Second"}]}
+                ]
+            }),
+            json!({
+                "_id": "post-2",
+                "_type": "post",
+                "title": "Serving the web",
+                "publishedAt": "2024-03-01T08:30:00+01:00",
+                "views": 40,
+                "author": {"_ref": "grace", "_type": "reference"},
+                "categories": [
+                    {"_ref": "rust", "_type": "reference", "_key": "a"},
+                    {"_ref": "web", "_type": "reference", "_key": "b"}
+                ]
+            }),
+            json!({
+                "_id": "drafts.post-3",
+                "_type": "post",
+                "title": "Unpublished",
+                "views": null,
+                "author": {"_ref": "ada", "_type": "reference"}
+            }),
+        ]
+    }
+
+    fn query(query: &str) -> Value {
+        Evaluator::new(&dataset()).query(query).unwrap()
+    }
+
+    #[test]
+    fn filters_and_projections() {
+        assert_eq!(
+            query(
+                r#"*[_type == "post" && !(_id in path("drafts.**"))]{_id, "author": author->name}"#
+            ),
+            json!([
+                {"_id": "post-1", "author": "Ada Lovelace"},
+                {"_id": "post-2", "author": "Grace Hopper"}
+            ])
+        );
+        assert_eq!(
+            query(r#"*[_type == "post" && views > 50].title"#),
+            json!(["Hello Rust"])
+        );
+        assert_eq!(
+            query(r#"*[_id == "post-2"][0]{..., "categories": categories[]->title}.categories"#),
+            json!(["Rust", "Web"])
+        );
+        assert_eq!(
+            query(
+                r#"*[_type == "author"]{name, "posts": count(*[_type == "post" && references(^._id)])}"#
+            ),
+            json!([
+                {"name": "Ada Lovelace", "posts": 2},
+                {"name": "Grace Hopper", "posts": 1}
+            ])
+        );
+        assert_eq!(
+            query(r#"*[_type == "post" && title match "hel*"]._id"#),
+            json!(["post-1"])
+        );
+        assert_eq!(
+            query(r#"*[_type == "post" && defined(views) && views in 100..200]._id"#),
+            json!(["post-1"])
+        );
+    }
+
+    #[test]
+    fn ordering_and_slicing() {
+        assert_eq!(
+            query(r#"*[_type == "post"] | order(views desc)._id"#),
+            json!(["drafts.post-3", "post-1", "post-2"])
+        );
+        assert_eq!(
+            query(r#"*[_type == "post"] | order(author._ref asc, title desc)[0...2].title"#),
+            json!(["Unpublished", "Hello Rust"])
+        );
+        assert_eq!(query(r#"*[_type == "category"][-1]._id"#), json!("web"));
+        assert_eq!(
+            query(r#"*[_type == "category"][0..1]._id"#),
+            json!(["rust", "web"])
+        );
+        assert_eq!(query(r#""sanity"[1...3]"#), json!("an"));
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(
+            query(
+                r#"*[_id == "post-1"][0]{"text": pt::text(body), "n": length(title), "slug": lower(array::join(string::split(title, " "), "-"))}"#
+            ),
+            json!({"text": "First words\n\nSecond", "n": 10, "slug": "hello-rust"})
+        );
+        assert_eq!(
+            query(
+                r#"*[_type == "post"]{"label": select(views > 100 => "hot", views > 10 => "warm", "cold"), "views": coalesce(views, 0)}"#
+            ),
+            json!([
+                {"label": "hot", "views": 120},
+                {"label": "warm", "views": 40},
+                {"label": "cold", "views": 0}
+            ])
+        );
+        assert_eq!(
+            query(
+                r#"{"sum": math::sum(*[_type == "post"].views), "avg": math::avg(*.views), "max": math::max(*.views), "min": math::min([])}"#
+            ),
+            json!({"sum": 160, "avg": 80, "max": 120, "min": null})
+        );
+        assert_eq!(
+            query(r#"array::unique(array::compact(*[_type == "post"].categories[]._ref))"#),
+            json!(["rust", "web"])
+        );
+        assert_eq!(
+            query(
+                r#"*[_type == "post" && dateTime(publishedAt) > dateTime("2024-02-01T00:00:00Z")]._id"#
+            ),
+            json!(["post-2"])
+        );
+        assert_eq!(
+            query(r#"dateTime("2024-03-01T08:30:00+01:00") - dateTime("2024-03-01T07:00:00Z")"#),
+            json!(1800)
+        );
+        assert_eq!(
+            query(r#"dateTime("2024-03-01T07:00:00Z") + 90"#),
+            json!("2024-03-01T07:01:30Z")
+        );
+        // Plain strings are not dates
+        assert_eq!(
+            query(r#""2024-03-01T07:00:00Z" - "2024-03-01T06:00:00Z""#),
+            Value::Null
+        );
+        assert_eq!(query(r#""2024-03-01T07:00:00Z" + 90"#), Value::Null);
+        assert_eq!(
+            query(r#"string::startsWith("drafts.post-3", "drafts.")"#),
+            json!(true)
+        );
+        assert_eq!(
+            query(r#"array::compact([1, null, 2]) + [3]"#),
+            json!([1, 2, 3])
+        );
+    }
+
+    #[test]
+    fn operators() {
+        assert_eq!(query("1 + 2 * 3 ** 2 - 10 % 4"), json!(17));
+        assert_eq!(query("7 / 2"), json!(3.5));
+        assert_eq!(query("1 / 0"), Value::Null);
+        assert_eq!(query(r#""a" + "b""#), json!("ab"));
+        assert_eq!(query(r#"{"a": 1} + {"b": 2}"#), json!({"a": 1, "b": 2}));
+        assert_eq!(query("1 == 1.0 && !(2 < 1) || false"), json!(true));
+        assert_eq!(query(r#"1 < "a""#), Value::Null);
+        assert_eq!(query("null && false"), json!(false));
+        assert_eq!(query("null || false"), Value::Null);
+        assert_eq!(query(r#""hello" match "h*l*o""#), json!(true));
+        assert_eq!(query(r#""hello" match "*ll*""#), json!(true));
+        assert_eq!(query(r#""hello" match "h*x*o""#), json!(false));
+        assert_eq!(
+            query(&format!(
+                r#""{}" match "a*a*a*a*a*a*a*a*a*a*a*a*b""#,
+                "a".repeat(200)
+            )),
+            json!(false)
+        );
+    }
+
+    #[test]
+    fn parameters() {
+        let documents = dataset();
+        let evaluator = Evaluator::new(&documents)
+            .param("type", "category")
+            .param("ids", json!(["web", "nope"]));
+        assert_eq!(
+            evaluator
+                .query("*[_type == $type && _id in $ids]._id")
+                .unwrap(),
+            json!(["web"])
+        );
+        let evaluator = evaluator.param("index", 1).param("field", "_id");
+        assert_eq!(
+            evaluator.query("*[_type == $type][$index]._id").unwrap(),
+            json!("web")
+        );
+        assert_eq!(
+            evaluator.query("*[_type == $type][-$index]._id").unwrap(),
+            json!("web")
+        );
+        assert_eq!(
+            evaluator.query("*[_type == $type][$field]").unwrap(),
+            json!(["rust", "web"])
+        );
+        assert_eq!(
+            evaluator.query("*[_type == $missing]").unwrap_err(),
+            EvalError::MissingParam("missing".to_string())
+        );
+        assert_eq!(
+            evaluator.query("nope::fn()").unwrap_err(),
+            EvalError::UnknownFunction {
+                namespace: "nope".to_string(),
+                name: "fn".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn builder_queries() {
+        use crate::groq::builder::{eq, references, Projection, Query};
+
+        let documents = dataset();
+        let built = Query::of_type("post")
+            .filter(references("rust") & !eq("author._ref", "grace"))
+            .order_desc("publishedAt")
+            .project(
+                Projection::new()
+                    .field("title")
+                    .deref("author", Projection::new().field("name")),
+            )
+            .build();
+        assert_eq!(
+            Evaluator::new(&documents)
+                .params(&built.params)
+                .query(&built.query)
+                .unwrap(),
+            json!([{"title": "Hello Rust", "author": {"name": "Ada Lovelace"}}])
+        );
+    }
+}
@@ -0,0 +1,7 @@
+//! GROQ query language support
+pub mod ast;
+pub mod builder;
+pub mod eval;
+pub mod parser;
+
+pub use parser::parse;
@@ -0,0 +1,821 @@
+use serde_json::{Number, Value};
+
+use super::ast::{ArrayElement, BinaryOp, Expr, ObjectAttribute, Span};
+use crate::error::ParseError;
+
+const PUNCTUATION: &[&str] = &[
+    "...", "..", "=>", "->", "==", "!=", "<=", ">=", "&&", "||", "**", "::", "*", "@", "[", "]",
+    "{", "}", "(", ")", ".", ",", ":", "|", "<", ">", "!", "+", "-", "/", "%", "^",
+];
+
+/// Words that end an expression instead of naming an attribute after `->`
+const OPERATOR_WORDS: &[&str] = &["in", "match", "asc", "desc"];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Param(String),
+    Number(f64),
+    String(String),
+    Punct(&'static str),
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Ident(name) => format!("identifier `{}`", name),
+            Token::Param(name) => format!("parameter `${}`", name),
+            Token::Number(number) => format!("number `{}`", number),
+            Token::String(string) => format!("string {:?}", string),
+            Token::Punct(punct) => format!("`{}`", punct),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Lexed {
+    token: Token,
+    span: Span,
+}
+
+fn tokenize(query: &str) -> Result<Vec<Lexed>, ParseError> {
+    let bytes = query.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+        if query[i..].starts_with("//") {
+            while i < bytes.len() && bytes[i] != b'\n' {
+                i += 1;
+            }
+            continue;
+        }
+        let start = i;
+        let token = if c.is_ascii_alphabetic() || c == b'_' {
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            Token::Ident(query[start..i].to_string())
+        } else if c == b'$' {
+            i += 1;
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            if i == start + 1 {
+                return Err(ParseError::InvalidToken {
+                    message: "expected a parameter name after `$`".to_string(),
+                    span: Span::new(start, i),
+                });
+            }
+            Token::Param(query[start + 1..i].to_string())
+        } else if c.is_ascii_digit() {
+            while i < bytes.len() && bytes[i].is_ascii_digit() {
+                i += 1;
+            }
+            // `1..2` is a range, not a fraction
+            if i + 1 < bytes.len() && bytes[i] == b'.' && bytes[i + 1].is_ascii_digit() {
+                i += 1;
+                while i < bytes.len() && bytes[i].is_ascii_digit() {
+                    i += 1;
+                }
+            }
+            if i < bytes.len() && (bytes[i] == b'e' || bytes[i] == b'E') {
+                let mut j = i + 1;
+                if j < bytes.len() && (bytes[j] == b'+' || bytes[j] == b'-') {
+                    j += 1;
+                }
+                if j < bytes.len() && bytes[j].is_ascii_digit() {
+                    i = j;
+                    while i < bytes.len() && bytes[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let number = query[start..i]
+                .parse::<f64>()
+                .map_err(|_| ParseError::InvalidToken {
+                    message: "invalid number".to_string(),
+                    span: Span::new(start, i),
+                })?;
+            Token::Number(number)
+        } else if c == b'"' || c == b'\'' {
+            let (string, end) = lex_string(query, start)?;
+            i = end;
+            Token::String(string)
+        } else {
+            match PUNCTUATION
+                .iter()
+                .find(|punct| query[i..].starts_with(**punct))
+            {
+                Some(punct) => {
+                    i += punct.len();
+                    Token::Punct(punct)
+                }
+                None => {
+                    let c = query[i..].chars().next().unwrap_or_default();
+                    return Err(ParseError::InvalidToken {
+                        message: format!("unexpected character `{}`", c),
+                        span: Span::new(start, start + c.len_utf8()),
+                    });
+                }
+            }
+        };
+        tokens.push(Lexed {
+            token,
+            span: Span::new(start, i),
+        });
+    }
+    Ok(tokens)
+}
+
+/// Lex a quoted string starting at `start`, returning the string and the end position
+fn lex_string(query: &str, start: usize) -> Result<(String, usize), ParseError> {
+    let quote = query.as_bytes()[start] as char;
+    let mut result = String::new();
+    let mut chars = query[start + 1..].char_indices();
+    while let Some((offset, c)) = chars.next() {
+        match c {
+            c if c == quote => return Ok((result, start + 1 + offset + 1)),
+            '\\' => {
+                let escaped = chars.next().map(|(_, c)| c);
+                match escaped {
+                    Some('n') => result.push('\n'),
+                    Some('t') => result.push('\t'),
+                    Some('r') => result.push('\r'),
+                    Some('b') => result.push('\u{8}'),
+                    Some('f') => result.push('\u{c}'),
+                    Some('u') => {
+                        let hex: String = chars.by_ref().take(4).map(|(_, c)| c).collect();
+                        let code = u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| ParseError::InvalidToken {
+                                message: "invalid unicode escape".to_string(),
+                                span: Span::new(
+                                    start + 1 + offset,
+                                    (start + 1 + offset + 6).min(query.len()),
+                                ),
+                            })?;
+                        result.push(code);
+                    }
+                    Some(other) => result.push(other),
+                    None => break,
+                }
+            }
+            c => result.push(c),
+        }
+    }
+    Err(ParseError::InvalidToken {
+        message: "unterminated string".to_string(),
+        span: Span::new(start, query.len()),
+    })
+}
+
+/// Parse a GROQ query into an expression
+pub fn parse(query: &str) -> Result<Expr, ParseError> {
+    let mut parser = Parser {
+        tokens: tokenize(query)?,
+        position: 0,
+        end: query.len(),
+    };
+    let expr = parser.expr()?;
+    match parser.tokens.get(parser.position) {
+        Some(lexed) => Err(ParseError::UnexpectedToken {
+            found: lexed.token.describe(),
+            expected: "end of query".to_string(),
+            span: lexed.span,
+        }),
+        None => Ok(expr),
+    }
+}
+
+struct Parser {
+    tokens: Vec<Lexed>,
+    position: usize,
+    /// Length of the query, where `UnexpectedEnd` points
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|lexed| &lexed.token)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens
+            .get(self.position + offset)
+            .map(|lexed| &lexed.token)
+    }
+
+    fn is_punct(&self, punct: &str) -> bool {
+        matches!(self.peek(), Some(Token::Punct(p)) if *p == punct)
+    }
+
+    fn is_word(&self, word: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(ident)) if ident == word)
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        if self.is_punct(punct) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_word(&mut self, word: &str) -> bool {
+        if self.is_word(word) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        if token.is_some() {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn error(&self, expected: &str) -> ParseError {
+        match self.tokens.get(self.position) {
+            Some(lexed) => ParseError::UnexpectedToken {
+                found: lexed.token.describe(),
+                expected: expected.to_string(),
+                span: lexed.span,
+            },
+            None => ParseError::UnexpectedEnd {
+                expected: expected.to_string(),
+                span: Span::new(self.end, self.end),
+            },
+        }
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), ParseError> {
+        if self.eat(punct) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("`{}`", punct)))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, ParseError> {
+        match self.peek() {
+            Some(Token::Ident(name)) => {
+                let name = name.clone();
+                self.position += 1;
+                Ok(name)
+            }
+            _ => Err(self.error("an identifier")),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, ParseError> {
+        let left = self.or()?;
+        if self.eat("=>") {
+            let right = self.expr()?;
+            return Ok(Expr::Pair(left.boxed(), right.boxed()));
+        }
+        Ok(left)
+    }
+
+    fn or(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.and()?;
+        while self.eat("||") {
+            left = Expr::Or(left.boxed(), self.and()?.boxed());
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.comparison()?;
+        while self.eat("&&") {
+            left = Expr::And(left.boxed(), self.comparison()?.boxed());
+        }
+        Ok(left)
+    }
+
+    fn comparison(&mut self) -> Result<Expr, ParseError> {
+        let left = self.range()?;
+        let op = match self.peek() {
+            Some(Token::Punct("==")) => BinaryOp::Eq,
+            Some(Token::Punct("!=")) => BinaryOp::NotEq,
+            Some(Token::Punct("<")) => BinaryOp::Lt,
+            Some(Token::Punct("<=")) => BinaryOp::Lte,
+            Some(Token::Punct(">")) => BinaryOp::Gt,
+            Some(Token::Punct(">=")) => BinaryOp::Gte,
+            Some(Token::Ident(word)) if word == "in" => BinaryOp::In,
+            Some(Token::Ident(word)) if word == "match" => BinaryOp::Match,
+            _ => return Ok(left),
+        };
+        self.position += 1;
+        let right = self.range()?;
+        Ok(Expr::Binary(op, left.boxed(), right.boxed()))
+    }
+
+    fn range(&mut self) -> Result<Expr, ParseError> {
+        let start = self.additive()?;
+        let inclusive = if self.eat("..") {
+            true
+        } else if self.eat("...") {
+            false
+        } else {
+            return Ok(start);
+        };
+        let end = self.additive()?;
+        Ok(Expr::Range {
+            start: start.boxed(),
+            end: end.boxed(),
+            inclusive,
+        })
+    }
+
+    fn additive(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.multiplicative()?;
+        loop {
+            let op = if self.eat("+") {
+                BinaryOp::Add
+            } else if self.eat("-") {
+                BinaryOp::Sub
+            } else {
+                return Ok(left);
+            };
+            left = Expr::Binary(op, left.boxed(), self.multiplicative()?.boxed());
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.unary()?;
+        loop {
+            let op = if self.eat("*") {
+                BinaryOp::Mul
+            } else if self.eat("/") {
+                BinaryOp::Div
+            } else if self.eat("%") {
+                BinaryOp::Rem
+            } else {
+                return Ok(left);
+            };
+            left = Expr::Binary(op, left.boxed(), self.unary()?.boxed());
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        if self.eat("-") {
+            return Ok(Expr::Neg(self.unary()?.boxed()));
+        }
+        if self.eat("+") {
+            return Ok(Expr::Pos(self.unary()?.boxed()));
+        }
+        let base = self.not()?;
+        if self.eat("**") {
+            let exponent = self.unary()?;
+            return Ok(Expr::Binary(BinaryOp::Pow, base.boxed(), exponent.boxed()));
+        }
+        Ok(base)
+    }
+
+    fn not(&mut self) -> Result<Expr, ParseError> {
+        if self.eat("!") {
+            return Ok(Expr::Not(self.not()?.boxed()));
+        }
+        self.postfix()
+    }
+
+    fn postfix(&mut self) -> Result<Expr, ParseError> {
+        let mut base = self.primary()?;
+        loop {
+            if self.eat(".") {
+                base = Expr::AccessAttribute(base.boxed(), self.ident()?);
+            } else if self.eat("[") {
+                if self.eat("]") {
+                    base = Expr::ArrayTraversal(base.boxed());
+                    continue;
+                }
+                let inner = self.expr()?;
+                self.expect("]")?;
+                base = match inner {
+                    Expr::Range {
+                        start,
+                        end,
+                        inclusive,
+                    } => Expr::Slice {
+                        base: base.boxed(),
+                        start,
+                        end,
+                        inclusive,
+                    },
+                    Expr::Literal(Value::String(name)) => Expr::AccessAttribute(base.boxed(), name),
+                    index @ (Expr::Literal(Value::Number(_)) | Expr::Neg(_)) => {
+                        Expr::AccessElement(base.boxed(), index.boxed())
+                    }
+                    condition => Expr::Filter(base.boxed(), condition.boxed()),
+                };
+            } else if self.is_punct("{") {
+                let attributes = self.object()?;
+                base = Expr::Projection(base.boxed(), attributes);
+            } else if self.eat("->") {
+                let name = match self.peek() {
+                    Some(Token::Ident(name)) if !OPERATOR_WORDS.contains(&name.as_str()) => {
+                        let name = name.clone();
+                        self.position += 1;
+                        Some(name)
+                    }
+                    _ => None,
+                };
+                base = Expr::Deref(base.boxed(), name);
+            } else if self.is_punct("|") && matches!(self.peek_at(1), Some(Token::Ident(_))) {
+                self.position += 1;
+                let name = self.ident()?;
+                let args = self.args()?;
+                base = Expr::PipeCall {
+                    base: base.boxed(),
+                    name,
+                    args,
+                };
+            } else {
+                return Ok(base);
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, ParseError> {
+        if self.is_punct("{") {
+            return Ok(Expr::Object(self.object()?));
+        }
+        let token = match self.peek() {
+            Some(token) => token.clone(),
+            None => return Err(self.error("an expression")),
+        };
+        match token {
+            Token::Punct("*") => {
+                self.position += 1;
+                Ok(Expr::Everything)
+            }
+            Token::Punct("@") => {
+                self.position += 1;
+                Ok(Expr::This)
+            }
+            Token::Punct("^") => {
+                self.position += 1;
+                let mut levels = 1;
+                while self.is_punct(".") && self.peek_at(1) == Some(&Token::Punct("^")) {
+                    self.position += 2;
+                    levels += 1;
+                }
+                Ok(Expr::Parent(levels))
+            }
+            Token::Punct("(") => {
+                self.position += 1;
+                let inner = self.expr()?;
+                self.expect(")")?;
+                Ok(Expr::Group(inner.boxed()))
+            }
+            Token::Punct("[") => {
+                self.position += 1;
+                self.array()
+            }
+            Token::Param(name) => {
+                self.position += 1;
+                Ok(Expr::Param(name))
+            }
+            Token::Number(number) => {
+                self.position += 1;
+                Ok(Expr::Literal(number_value(number)))
+            }
+            Token::String(string) => {
+                self.position += 1;
+                Ok(Expr::Literal(Value::String(string)))
+            }
+            Token::Ident(name) => {
+                self.position += 1;
+                match name.as_str() {
+                    "true" => return Ok(Expr::Literal(Value::Bool(true))),
+                    "false" => return Ok(Expr::Literal(Value::Bool(false))),
+                    "null" => return Ok(Expr::Literal(Value::Null)),
+                    _ => {}
+                }
+                if self.eat("::") {
+                    let function = self.ident()?;
+                    let args = self.args()?;
+                    return Ok(Expr::FuncCall {
+                        namespace: name,
+                        name: function,
+                        args,
+                    });
+                }
+                if self.is_punct("(") {
+                    let args = self.args()?;
+                    return Ok(Expr::FuncCall {
+                        namespace: "global".to_string(),
+                        name,
+                        args,
+                    });
+                }
+                Ok(Expr::Attribute(name))
+            }
+            _ => Err(self.error("an expression")),
+        }
+    }
+
+    fn args(&mut self) -> Result<Vec<Expr>, ParseError> {
+        self.expect("(")?;
+        let mut args = Vec::new();
+        loop {
+            if self.eat(")") {
+                return Ok(args);
+            }
+            let mut arg = self.expr()?;
+            if self.eat_word("asc") {
+                arg = Expr::Asc(arg.boxed());
+            } else if self.eat_word("desc") {
+                arg = Expr::Desc(arg.boxed());
+            }
+            args.push(arg);
+            if !self.eat(",") {
+                self.expect(")")?;
+                return Ok(args);
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Expr, ParseError> {
+        let mut elements = Vec::new();
+        loop {
+            if self.eat("]") {
+                return Ok(Expr::Array(elements));
+            }
+            if self.eat("...") {
+                elements.push(ArrayElement::Splat(self.expr()?));
+            } else {
+                elements.push(ArrayElement::Value(self.expr()?));
+            }
+            if !self.eat(",") {
+                self.expect("]")?;
+                return Ok(Expr::Array(elements));
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Vec<ObjectAttribute>, ParseError> {
+        self.expect("{")?;
+        let mut attributes = Vec::new();
+        loop {
+            if self.eat("}") {
+                return Ok(attributes);
+            }
+            if self.eat("...") {
+                if self.is_punct(",") || self.is_punct("}") {
+                    attributes.push(ObjectAttribute::Splat(Expr::This));
+                } else {
+                    attributes.push(ObjectAttribute::Splat(self.expr()?));
+                }
+            } else if matches!(
+                (self.peek(), self.peek_at(1)),
+                (Some(Token::String(_)), Some(Token::Punct(":")))
+            ) {
+                let name = match self.next() {
+                    Some(Token::String(name)) => name,
+                    _ => unreachable!(),
+                };
+                self.position += 1;
+                attributes.push(ObjectAttribute::Value(name, self.expr()?));
+            } else {
+                let position = self.position;
+                let expr = self.expr()?;
+                let attribute = match expr {
+                    Expr::Pair(condition, value) => {
+                        ObjectAttribute::Conditional(*condition, *value)
+                    }
+                    expr => match expr.inferred_name() {
+                        Some(name) => ObjectAttribute::Value(name.to_string(), expr),
+                        None => {
+                            return Err(ParseError::InvalidToken {
+                                message:
+                                    "cannot infer an attribute name, use `\"name\": expression`"
+                                        .to_string(),
+                                span: Span::new(
+                                    self.tokens[position].span.start,
+                                    self.tokens[self.position - 1].span.end,
+                                ),
+                            })
+                        }
+                    },
+                };
+                attributes.push(attribute);
+            }
+            if !self.eat(",") {
+                self.expect("}")?;
+                return Ok(attributes);
+            }
+        }
+    }
+}
+
+pub(crate) fn number_value(number: f64) -> Value {
+    if number.fract() == 0.0 && number.abs() < i64::MAX as f64 {
+        Value::Number(Number::from(number as i64))
+    } else {
+        Number::from_f64(number)
+            .map(Value::Number)
+            .unwrap_or(Value::Null)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn attribute(name: &str) -> Expr {
+        Expr::Attribute(name.to_string())
+    }
+
+    fn string(value: &str) -> Expr {
+        Expr::Literal(json!(value))
+    }
+
+    #[test]
+    fn parse_filter_and_projection() {
+        let expr =
+            parse(r#"*[_type == "post" && defined(slug)]{title, "author": author->name}"#).unwrap();
+        assert_eq!(
+            expr,
+            Expr::Projection(
+                Expr::Filter(
+                    Expr::Everything.boxed(),
+                    Expr::And(
+                        Expr::Binary(
+                            BinaryOp::Eq,
+                            attribute("_type").boxed(),
+                            string("post").boxed()
+                        )
+                        .boxed(),
+                        Expr::FuncCall {
+                            namespace: "global".to_string(),
+                            name: "defined".to_string(),
+                            args: vec![attribute("slug")],
+                        }
+                        .boxed(),
+                    )
+                    .boxed(),
+                )
+                .boxed(),
+                vec![
+                    ObjectAttribute::Value("title".to_string(), attribute("title")),
+                    ObjectAttribute::Value(
+                        "author".to_string(),
+                        Expr::Deref(attribute("author").boxed(), Some("name".to_string())),
+                    ),
+                ],
+            )
+        );
+    }
+
+    #[test]
+    fn parse_traversals() {
+        let expr = parse("*[_type == $type] | order(publishedAt desc) [0...10]").unwrap();
+        match expr {
+            Expr::Slice {
+                base,
+                start,
+                end,
+                inclusive,
+            } => {
+                assert!(!inclusive);
+                assert_eq!(*start, Expr::Literal(json!(0)));
+                assert_eq!(*end, Expr::Literal(json!(10)));
+                match *base {
+                    Expr::PipeCall { name, args, .. } => {
+                        assert_eq!(name, "order");
+                        assert_eq!(args, vec![Expr::Desc(attribute("publishedAt").boxed())]);
+                    }
+                    other => panic!("expected a pipe call, got {:?}", other),
+                }
+            }
+            other => panic!("expected a slice, got {:?}", other),
+        }
+        assert_eq!(
+            parse("categories[]->title").unwrap(),
+            Expr::Deref(
+                Expr::ArrayTraversal(attribute("categories").boxed()).boxed(),
+                Some("title".to_string())
+            )
+        );
+        assert_eq!(
+            parse("*[0]").unwrap(),
+            Expr::AccessElement(Expr::Everything.boxed(), Expr::Literal(json!(0)).boxed())
+        );
+    }
+
+    #[test]
+    fn parse_projection_attributes() {
+        let expr = parse(
+            r#"{..., "count": count(tags), _type == "post" => {title}, pt::text(body), 'x': 1.5}"#,
+        );
+        assert_eq!(
+            expr.unwrap_err(),
+            ParseError::InvalidToken {
+                message: "cannot infer an attribute name, use `\"name\": expression`".to_string(),
+                span: Span::new(56, 70),
+            }
+        );
+        let expr =
+            parse(r#"{..., "count": count(tags), _type == "post" => {title}, 'x': -1.5}"#).unwrap();
+        match expr {
+            Expr::Object(attributes) => {
+                assert_eq!(attributes[0], ObjectAttribute::Splat(Expr::This));
+                assert!(
+                    matches!(&attributes[1], ObjectAttribute::Value(name, _) if name == "count")
+                );
+                assert!(matches!(
+                    &attributes[2],
+                    ObjectAttribute::Conditional(_, Expr::Object(_))
+                ));
+                assert_eq!(
+                    attributes[3],
+                    ObjectAttribute::Value(
+                        "x".to_string(),
+                        Expr::Neg(Expr::Literal(json!(1.5)).boxed())
+                    )
+                );
+            }
+            other => panic!("expected an object, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            parse(r#"*[_type == "post""#).unwrap_err(),
+            ParseError::UnexpectedEnd {
+                expected: "`]`".to_string(),
+                span: Span::new(17, 17),
+            }
+        );
+        assert_eq!(
+            parse("*[_type == 'post'] }").unwrap_err(),
+            ParseError::UnexpectedToken {
+                found: "`}`".to_string(),
+                expected: "end of query".to_string(),
+                span: Span::new(19, 20),
+            }
+        );
+        assert!(matches!(
+            parse(r#""unterminated"#).unwrap_err(),
+            ParseError::InvalidToken {
+                span: Span { start: 0, end: 13 },
+                ..
+            }
+        ));
+        assert_eq!(
+            parse("*[title == \"a\" # b]").unwrap_err().span(),
+            Span::new(15, 16)
+        );
+    }
+
+    #[test]
+    fn parse_comments_and_parents() {
+        let expr = parse(
+            "// every post\n*[_type == 'post']{\n  // related posts\n  'related': *[references(^.^._id) && _id != ^._id]\n}",
+        )
+        .unwrap();
+        assert_eq!(
+            expr.to_string(),
+            r#"*[_type == "post"]{"related": *[references(^.^._id) && _id != ^._id]}"#
+        );
+        assert_eq!(parse("^").unwrap(), Expr::Parent(1));
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let queries = [
+            r#"*[_type == "post" && !(_id in path("drafts.**"))] | order(publishedAt desc, title asc)[0...10]{_id, title, "slug": slug.current, author->{name}, categories[]->title, ...}"#,
+            r#"count(*[_type in ["post", "page"] && title match $term])"#,
+            r#"*[_type == "movie"]{..., "cast": castMembers[characterName match "Ripley*"]{person->}, defined(poster) => {poster}}"#,
+            r#"*[price >= 10 && price < 2 ** 4 + -1.5 * (3 % 2)][0..2]"#,
+            r#"{"x": [1, ...$list, "a\"b\n"], "y": @["odd-name"], "z": coalesce(a, b)}"#,
+            r#"*[_type == "post"] | score(title match $q) | order(_score desc)[0]"#,
+            r#"*[0]["title"]"#,
+        ];
+        for query in queries {
+            let expr = parse(query).unwrap();
+            let printed = expr.to_string();
+            assert_eq!(parse(&printed).unwrap(), expr, "{}", printed);
+        }
+        assert_eq!(
+            parse("*[ _type == 'post' ] { title , 'n' : count( tags ) }")
+                .unwrap()
+                .to_string(),
+            r#"*[_type == "post"]{title,"n": count(tags)}"#
+        );
+    }
+}
@@ -0,0 +1,314 @@
+//! Documents as they were at a revision or time, and the transactions which changed them
+//!
+//! History needs a token with read access to the dataset.
+//!
+//! ```no_run
+//! # use sanity_rs::client::SanityClient;
+//! # use sanity_rs::error::RequestError;
+//! # use sanity_rs::history::At;
+//! # async fn undelete(client: &SanityClient) -> Result<(), RequestError> {
+//! let history = client.history("production");
+//! let transactions = history.transactions(&["post-1"]).reverse(true).limit(2).send().await?;
+//! // The latest transaction deleted the document, restore it as it was before
+//! let before = &transactions[1];
+//! history.restore("post-1", At::Revision(before.id.clone())).await?;
+//! # Ok(())
+//! # }
+//! ```
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::client::SanityClient;
+use crate::error::RequestError;
+use crate::import::parse_ndjson;
+use crate::mutation::Mutation;
+
+/// Which version of a document to fetch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum At {
+    /// The document as written by this transaction
+    Revision(String),
+    /// The document as it was at this time
+    Time(DateTime<Utc>),
+}
+
+/// An entry of the transaction log
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Transaction {
+    /// Id of the transaction, which is also the `_rev` of the documents it wrote
+    pub id: String,
+    pub timestamp: DateTime<Utc>,
+    /// Id of the user or token which made the change
+    pub author: String,
+    #[serde(rename = "documentIDs", default)]
+    pub document_ids: Vec<String>,
+    /// Left out when fetched with `exclude_content`
+    #[serde(default)]
+    pub mutations: Vec<Value>,
+    #[serde(default)]
+    pub effects: Map<String, Value>,
+}
+
+/// History of a dataset, created with `SanityClient::history`
+pub struct History<'a> {
+    client: &'a SanityClient,
+    dataset: String,
+}
+
+impl SanityClient {
+    pub fn history(&self, dataset: &str) -> History<'_> {
+        History {
+            client: self,
+            dataset: dataset.to_string(),
+        }
+    }
+}
+
+impl<'a> History<'a> {
+    /// A document as it was, `None` if it did not exist then
+    pub async fn document<T: DeserializeOwned>(
+        &self,
+        id: &str,
+        at: At,
+    ) -> Result<Option<T>, RequestError> {
+        Ok(self.documents(&[id], at).await?.into_iter().next())
+    }
+
+    /// Documents as they were, those which did not exist are left out
+    pub async fn documents<T: DeserializeOwned>(
+        &self,
+        ids: &[&str],
+        at: At,
+    ) -> Result<Vec<T>, RequestError> {
+        let mut url = self.client.endpoint(&format!(
+            "data/history/{}/documents/{}",
+            self.dataset,
+            ids.join(",")
+        ))?;
+        match at {
+            At::Revision(revision) => url.query_pairs_mut().append_pair("revision", &revision),
+            At::Time(time) => url.query_pairs_mut().append_pair("time", &timestamp(time)),
+        };
+        let response = self
+            .client
+            .send_json(self.client.request("GET", url))
+            .await?;
+        let documents = match response.get("documents") {
+            Some(Value::Array(documents)) => documents.clone(),
+            _ => Vec::new(),
+        };
+        documents
+            .into_iter()
+            .filter(|document| !document.is_null())
+            .map(|document| Ok(serde_json::from_value(document)?))
+            .collect()
+    }
+
+    /// Write a document back as it was, e.g. after it was deleted by mistake
+    ///
+    /// Returns the restored document, or `None` when it did not exist at that point.
+    pub async fn restore(&self, id: &str, at: At) -> Result<Option<Value>, RequestError> {
+        let Some(mut document) = self.document::<Value>(id, at).await? else {
+            return Ok(None);
+        };
+        if let Value::Object(object) = &mut document {
+            object.remove("_rev");
+        }
+        let mutation = Mutation::CreateOrReplace(document.clone());
+        self.client
+            .mutate_dataset(&self.dataset, &[mutation])
+            .await?;
+        Ok(Some(document))
+    }
+
+    /// The transactions which touched these documents, oldest first unless reversed
+    pub fn transactions(&self, ids: &[&str]) -> Transactions<'_> {
+        Transactions {
+            history: self,
+            ids: ids.iter().map(|id| id.to_string()).collect(),
+            params: Vec::new(),
+        }
+    }
+}
+
+/// Filters of a transaction log request
+pub struct Transactions<'a> {
+    history: &'a History<'a>,
+    ids: Vec<String>,
+    params: Vec<(&'static str, String)>,
+}
+
+impl Transactions<'_> {
+    fn param(mut self, name: &'static str, value: String) -> Self {
+        self.params.retain(|(n, _)| *n != name);
+        self.params.push((name, value));
+        self
+    }
+
+    pub fn from_time(self, time: DateTime<Utc>) -> Self {
+        self.param("fromTime", timestamp(time))
+    }
+
+    pub fn to_time(self, time: DateTime<Utc>) -> Self {
+        self.param("toTime", timestamp(time))
+    }
+
+    /// Start at this transaction, included
+    pub fn from_transaction(self, id: &str) -> Self {
+        self.param("fromTransaction", id.to_string())
+    }
+
+    /// End at this transaction, included
+    pub fn to_transaction(self, id: &str) -> Self {
+        self.param("toTransaction", id.to_string())
+    }
+
+    /// Only transactions made by these users or tokens
+    pub fn authors(self, authors: &[&str]) -> Self {
+        self.param("authors", authors.join(","))
+    }
+
+    /// Newest first
+    pub fn reverse(self, reverse: bool) -> Self {
+        self.param("reverse", reverse.to_string())
+    }
+
+    pub fn limit(self, limit: usize) -> Self {
+        self.param("limit", limit.to_string())
+    }
+
+    /// Leave out the mutations, which keeps the log small
+    pub fn exclude_content(self, exclude: bool) -> Self {
+        self.param("excludeContent", exclude.to_string())
+    }
+
+    pub async fn send(&self) -> Result<Vec<Transaction>, RequestError> {
+        let client = self.history.client;
+        let mut url = client.endpoint(&format!(
+            "data/history/{}/transactions/{}",
+            self.history.dataset,
+            self.ids.join(",")
+        ))?;
+        for (name, value) in &self.params {
+            url.query_pairs_mut().append_pair(name, value);
+        }
+        let response = client.transport().send(client.request("GET", url)).await?;
+        if response.status >= 400 {
+            return Err(RequestError::ApiError {
+                status: response.status,
+                body: response.body,
+            });
+        }
+        parse_ndjson(response.body.as_bytes())?
+            .into_iter()
+            .map(|transaction| Ok(serde_json::from_value(transaction)?))
+            .collect()
+    }
+}
+
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::transport::{test_client, MockTransport};
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    const TRANSACTIONS: &str = r#"{"id":"tx-2","timestamp":"2024-05-02T10:00:00.000Z","author":"p-editor","documentIDs":["post-1"],"mutations":[{"delete":{"id":"post-1"}}]}
+{"id":"tx-1","timestamp":"2024-05-01T09:30:00.000Z","author":"p-editor","documentIDs":["post-1"],"mutations":[{"create":{"_id":"post-1","_type":"post","title":"Hello"}}]}
+"#;
+
+    #[tokio::test]
+    async fn fetches_transactions() -> Result<(), RequestError> {
+        let mock = Arc::new(MockTransport::new().route(
+            "GET",
+            "/v2022-03-07/data/history/production/transactions/post-1",
+            TRANSACTIONS,
+        ));
+        let client = test_client(&mock);
+        let transactions = client
+            .history("production")
+            .transactions(&["post-1"])
+            .from_time(Utc.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap())
+            .reverse(true)
+            .limit(10)
+            .send()
+            .await?;
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(transactions[0].id, "tx-2");
+        assert_eq!(
+            transactions[1].timestamp,
+            Utc.with_ymd_and_hms(2024, 5, 1, 9, 30, 0).unwrap()
+        );
+        assert_eq!(transactions[1].document_ids, ["post-1"]);
+        assert_eq!(
+            mock.requests()[0].url.query(),
+            Some("fromTime=2024-05-01T00%3A00%3A00.000Z&reverse=true&limit=10")
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fetches_and_restores_revisions() -> Result<(), RequestError> {
+        #[derive(Debug, Deserialize)]
+        struct Post {
+            title: String,
+        }
+
+        let mock = Arc::new(
+            MockTransport::new()
+                .route(
+                    "GET",
+                    "/v2022-03-07/data/history/production/documents/post-1",
+                    r#"{"documents":[{"_id":"post-1","_type":"post","_rev":"tx-1","title":"Hello"}]}"#,
+                )
+                .route(
+                    "GET",
+                    "/v2022-03-07/data/history/production/documents/post-1,post-2",
+                    r#"{"documents":[{"_id":"post-1","_type":"post","_rev":"tx-1","title":"Hello"},null]}"#,
+                )
+                .route(
+                    "POST",
+                    "/v2022-03-07/data/mutate/production",
+                    r#"{"transactionId":"tx-3","results":[]}"#,
+                ),
+        );
+        let client = test_client(&mock);
+        let history = client.history("production");
+
+        let post: Option<Post> = history
+            .document("post-1", At::Revision("tx-1".to_string()))
+            .await?;
+        assert_eq!(post.unwrap().title, "Hello");
+        assert_eq!(mock.requests()[0].url.query(), Some("revision=tx-1"));
+
+        let time = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        let posts: Vec<Post> = history
+            .documents(&["post-1", "post-2"], At::Time(time))
+            .await?;
+        assert_eq!(posts.len(), 1);
+        assert_eq!(
+            mock.requests()[1].url.query(),
+            Some("time=2024-05-01T12%3A00%3A00.000Z")
+        );
+
+        let restored = history
+            .restore("post-1", At::Revision("tx-1".to_string()))
+            .await?;
+        assert_eq!(restored.unwrap().get("_rev"), None);
+        let mutation: Value =
+            serde_json::from_slice(mock.requests()[3].body.as_ref().unwrap()).unwrap();
+        assert_eq!(
+            mutation,
+            json!({"mutations": [{"createOrReplace": {"_id": "post-1", "_type": "post", "title": "Hello"}}]})
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,635 @@
+//! Import documents from NDJSON or an export archive into a dataset
+//!
+//! ```no_run
+//! # use sanity_rs::client::SanityClient;
+//! # use sanity_rs::error::RequestError;
+//! # use sanity_rs::import::ConflictMode;
+//! # async fn seed(client: &SanityClient) -> Result<(), RequestError> {
+//! let summary = client
+//!     .import("production")
+//!     .mode(ConflictMode::Replace)
+//!     .on_progress(|progress| println!("{:?} {}/{}", progress.stage, progress.done, progress.total))
+//!     .file("production.tar.gz")
+//!     .await?;
+//! println!("{} documents, {} assets", summary.documents, summary.assets);
+//! # Ok(())
+//! # }
+//! ```
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use flate2::read::GzDecoder;
+use serde_json::{json, Map, Value};
+use url::Url;
+
+use crate::assets::AssetKind;
+use crate::client::SanityClient;
+use crate::error::{RequestError, URLError};
+use crate::mutation::Mutation;
+use crate::transport::Request;
+
+/// What to do with documents whose id already exists in the dataset
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConflictMode {
+    /// Fail the batch holding the first existing id, batches before it stay written
+    #[default]
+    Fail,
+    /// Overwrite the existing documents
+    Replace,
+    /// Only create the documents which are missing
+    Missing,
+}
+
+impl ConflictMode {
+    fn mutation(&self, document: Value) -> Mutation {
+        match self {
+            ConflictMode::Fail => Mutation::Create(document),
+            ConflictMode::Replace => Mutation::CreateOrReplace(document),
+            ConflictMode::Missing => Mutation::CreateIfNotExists(document),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportStage {
+    UploadingAssets,
+    WritingDocuments,
+    /// References are written weak first so documents can point at later batches
+    StrengtheningReferences,
+}
+
+/// Reported after each uploaded asset and each written or strengthened batch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImportProgress {
+    pub stage: ImportStage,
+    pub done: usize,
+    pub total: usize,
+}
+
+/// Counts of what was imported
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    pub documents: usize,
+    pub assets: usize,
+}
+
+/// Options of an import, created with `SanityClient::import`
+pub struct Import<'a> {
+    client: &'a SanityClient,
+    dataset: String,
+    mode: ConflictMode,
+    batch_size: usize,
+    progress: Option<Box<dyn FnMut(ImportProgress) + Send + 'a>>,
+}
+
+impl SanityClient {
+    /// Import documents into `dataset`
+    pub fn import(&self, dataset: &str) -> Import<'_> {
+        Import {
+            client: self,
+            dataset: dataset.to_string(),
+            mode: ConflictMode::default(),
+            batch_size: 100,
+            progress: None,
+        }
+    }
+}
+
+/// The files of an archive, extracted to a temporary directory which is removed when dropped
+#[derive(Default)]
+struct AssetFiles {
+    dir: Option<PathBuf>,
+    files: HashMap<String, PathBuf>,
+}
+
+impl AssetFiles {
+    /// Write an archive entry to the temporary directory
+    fn extract(&mut self, path: &str, entry: &mut impl Read) -> std::io::Result<()> {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let dir = match &self.dir {
+            Some(dir) => dir.clone(),
+            None => {
+                let dir = std::env::temp_dir().join(format!(
+                    "sanity-rs-import-{}-{}",
+                    std::process::id(),
+                    COUNT.fetch_add(1, Ordering::Relaxed)
+                ));
+                std::fs::create_dir(&dir)?;
+                self.dir = Some(dir.clone());
+                dir
+            }
+        };
+        let file = dir.join(self.files.len().to_string());
+        std::io::copy(entry, &mut File::create(&file)?)?;
+        self.files.insert(path.to_string(), file);
+        Ok(())
+    }
+
+    /// Key the files by their path inside `root`, leaving out those outside of it
+    fn strip_prefix(&mut self, root: &str) {
+        self.files = std::mem::take(&mut self.files)
+            .into_iter()
+            .filter_map(|(path, file)| Some((path.strip_prefix(root)?.to_string(), file)))
+            .collect();
+    }
+
+    fn read(&self, path: &str) -> Option<std::io::Result<Vec<u8>>> {
+        self.files.get(path).map(std::fs::read)
+    }
+}
+
+impl Drop for AssetFiles {
+    fn drop(&mut self) {
+        if let Some(dir) = &self.dir {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+    }
+}
+
+impl<'a> Import<'a> {
+    pub fn mode(mut self, mode: ConflictMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// How many documents are written per transaction, 100 by default
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub fn on_progress(mut self, progress: impl FnMut(ImportProgress) + Send + 'a) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Import NDJSON, `_sanityAsset` pointers to `http(s)` urls are downloaded and uploaded
+    pub async fn ndjson(self, ndjson: &[u8]) -> Result<ImportSummary, RequestError> {
+        let documents = parse_ndjson(ndjson)?;
+        self.run(documents, AssetFiles::default(), Map::new()).await
+    }
+
+    /// Import an archive written by `Export::archive` or `sanity dataset export`
+    ///
+    /// Entries are read one at a time, asset files are kept in a temporary directory until
+    /// they are uploaded.
+    pub async fn archive(self, archive: impl Read) -> Result<ImportSummary, RequestError> {
+        // The export directory is the one holding `data.ndjson`, which may come after the
+        // assets, so entries are kept by their full path until it is known
+        let mut data: Option<(String, Vec<Value>)> = None;
+        let mut metadata = HashMap::new();
+        let mut files = AssetFiles::default();
+        for entry in tar::Archive::new(GzDecoder::new(archive)).entries()? {
+            let mut entry = entry?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let path = entry.path()?.to_string_lossy().into_owned();
+            if let Some(root) = path.strip_suffix("data.ndjson") {
+                if data
+                    .as_ref()
+                    .is_none_or(|(current, _)| root.len() < current.len())
+                {
+                    let mut ndjson = Vec::new();
+                    entry.read_to_end(&mut ndjson)?;
+                    data = Some((root.to_string(), parse_ndjson(&ndjson)?));
+                }
+            } else if let Some(root) = path.strip_suffix("assets.json") {
+                let assets: Map<String, Value> = serde_json::from_reader(&mut entry)?;
+                metadata.insert(root.to_string(), assets);
+            } else {
+                files.extract(&path, &mut entry)?;
+            }
+        }
+        let (root, documents) = data.ok_or_else(|| {
+            RequestError::StringParsingError("no data.ndjson in the archive".to_string())
+        })?;
+        files.strip_prefix(&root);
+        let metadata = metadata.remove(&root).unwrap_or_default();
+        self.run(documents, files, metadata).await
+    }
+
+    /// Import an NDJSON file or a tar.gz archive, told apart by their first bytes
+    pub async fn file(self, path: impl AsRef<Path>) -> Result<ImportSummary, RequestError> {
+        let mut file = BufReader::new(File::open(path)?);
+        let mut magic = [0; 2];
+        let read = file.read(&mut magic)?;
+        let start = std::io::Cursor::new(magic[..read].to_vec());
+        if magic == [0x1f, 0x8b] {
+            self.archive(start.chain(file)).await
+        } else {
+            let mut bytes = Vec::new();
+            start.chain(file).read_to_end(&mut bytes)?;
+            self.ndjson(&bytes).await
+        }
+    }
+
+    async fn run(
+        mut self,
+        mut documents: Vec<Value>,
+        files: AssetFiles,
+        metadata: Map<String, Value>,
+    ) -> Result<ImportSummary, RequestError> {
+        let mut pointers = Vec::new();
+        for document in &documents {
+            collect_pointers(document, &mut pointers);
+        }
+        let mut uploaded = HashMap::new();
+        for (done, pointer) in pointers.iter().enumerate() {
+            let id = self.upload(pointer, &files, &metadata).await?;
+            uploaded.insert(pointer.clone(), id);
+            self.report(ImportStage::UploadingAssets, done + 1, pointers.len());
+        }
+        let assets: Vec<&String> = uploaded.values().collect();
+        let mut strong = Vec::new();
+        for document in &mut documents {
+            rewrite_pointers(document, &uploaded);
+            let mut paths = Vec::new();
+            weaken_references(document, "", &assets, &mut paths);
+            if let Some(id) = document["_id"].as_str().filter(|_| !paths.is_empty()) {
+                strong.push(json!({"id": id, "unset": paths}));
+            }
+        }
+
+        let mut written = 0;
+        for batch in documents.chunks(self.batch_size) {
+            let mutations: Vec<Mutation> = batch
+                .iter()
+                .map(|document| self.mode.mutation(document.clone()))
+                .collect();
+            self.client
+                .mutate_dataset(&self.dataset, &mutations)
+                .await?;
+            written += batch.len();
+            self.report(ImportStage::WritingDocuments, written, documents.len());
+        }
+        let mut strengthened = 0;
+        for batch in strong.chunks(self.batch_size) {
+            let mutations: Vec<Mutation> = batch.iter().cloned().map(Mutation::Patch).collect();
+            self.client
+                .mutate_dataset(&self.dataset, &mutations)
+                .await?;
+            strengthened += batch.len();
+            self.report(
+                ImportStage::StrengtheningReferences,
+                strengthened,
+                strong.len(),
+            );
+        }
+        Ok(ImportSummary {
+            documents: written,
+            assets: uploaded.len(),
+        })
+    }
+
+    fn report(&mut self, stage: ImportStage, done: usize, total: usize) {
+        if let Some(progress) = &mut self.progress {
+            progress(ImportProgress { stage, done, total });
+        }
+    }
+
+    /// Upload the asset of a `_sanityAsset` pointer, returning the id of its new document
+    async fn upload(
+        &self,
+        pointer: &str,
+        files: &AssetFiles,
+        metadata: &Map<String, Value>,
+    ) -> Result<String, RequestError> {
+        let invalid = || RequestError::StringParsingError(format!("invalid asset {}", pointer));
+        let (kind, location) = pointer.split_once('@').ok_or_else(invalid)?;
+        let asset_kind = match kind {
+            "image" => AssetKind::Image,
+            "file" => AssetKind::File,
+            _ => return Err(invalid()),
+        };
+        let (file, bytes) = match location.strip_prefix("file://./") {
+            Some(path) => {
+                let bytes = files.read(path).ok_or_else(|| {
+                    RequestError::StringParsingError(format!("{} is not in the archive", path))
+                })??;
+                (path.rsplit('/').next().unwrap_or(path).to_string(), bytes)
+            }
+            None => {
+                let url = Url::parse(location).map_err(URLError::InvalidURL)?;
+                let file = url.path_segments().and_then(|mut s| s.next_back());
+                let file = file.unwrap_or_default().to_string();
+                let response = self.client.transport().stream(Request::get(url)).await?;
+                let status = response.status;
+                let bytes = response.bytes().await?;
+                if status >= 400 {
+                    return Err(RequestError::ApiError {
+                        status,
+                        body: format!("cannot download {}", location),
+                    });
+                }
+                (file, bytes)
+            }
+        };
+
+        let original = metadata
+            .get(&format!("{}-{}", kind, file.replace('.', "-")))
+            .and_then(|asset| asset.get("originalFilename"))
+            .and_then(Value::as_str);
+        let document = self
+            .client
+            .upload_asset_to(&self.dataset, asset_kind, bytes, original.unwrap_or(&file))
+            .await?;
+        document["_id"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| RequestError::StringParsingError(format!("no asset id in {}", document)))
+    }
+}
+
+pub(crate) fn parse_ndjson(ndjson: &[u8]) -> Result<Vec<Value>, RequestError> {
+    Ok(ndjson
+        .split(|byte| *byte == b'\n')
+        .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+        .map(serde_json::from_slice)
+        .collect::<Result<_, _>>()?)
+}
+
+/// The distinct `_sanityAsset` pointers of a document, in order
+fn collect_pointers(value: &Value, pointers: &mut Vec<String>) {
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::String(pointer)) = object.get("_sanityAsset") {
+                if !pointers.contains(pointer) {
+                    pointers.push(pointer.clone());
+                }
+            }
+            object
+                .values()
+                .for_each(|value| collect_pointers(value, pointers));
+        }
+        Value::Array(values) => values
+            .iter()
+            .for_each(|value| collect_pointers(value, pointers)),
+        _ => {}
+    }
+}
+
+/// Mark the strong references of a document weak, collecting the paths of their `_weak`
+///
+/// References to the uploaded assets stay strong, those documents exist already.
+fn weaken_references(value: &mut Value, path: &str, assets: &[&String], paths: &mut Vec<String>) {
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::String(id)) = object.get("_ref") {
+                let weak = object.get("_weak") == Some(&Value::Bool(true));
+                if !weak && !assets.contains(&id) {
+                    object.insert("_weak".to_string(), Value::Bool(true));
+                    paths.push(format!("{}._weak", path));
+                }
+                return;
+            }
+            for (key, value) in object.iter_mut() {
+                let path = match path {
+                    "" => key.clone(),
+                    path => format!("{}.{}", path, key),
+                };
+                weaken_references(value, &path, assets, paths);
+            }
+        }
+        Value::Array(values) => {
+            for (index, value) in values.iter_mut().enumerate() {
+                weaken_references(value, &format!("{}[{}]", path, index), assets, paths);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Replace `_sanityAsset` pointers with references to the uploaded assets
+fn rewrite_pointers(value: &mut Value, uploaded: &HashMap<String, String>) {
+    match value {
+        Value::Object(object) => {
+            let id = match object.get("_sanityAsset") {
+                Some(Value::String(pointer)) => uploaded.get(pointer),
+                _ => None,
+            };
+            if let Some(id) = id {
+                let reference = json!({"_type": "reference", "_ref": id});
+                object.remove("_sanityAsset");
+                object.insert("asset".to_string(), reference);
+            }
+            object
+                .values_mut()
+                .for_each(|value| rewrite_pointers(value, uploaded));
+        }
+        Value::Array(values) => values
+            .iter_mut()
+            .for_each(|value| rewrite_pointers(value, uploaded)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::transport::{test_client, MockTransport};
+
+    const UPLOADED: &str =
+        r#"{"document":{"_id":"image-new-10x20-png","_type":"sanity.imageAsset"}}"#;
+    const MUTATED: &str = r#"{"transactionId":"tx","results":[]}"#;
+
+    fn mutations(mock: &MockTransport) -> Vec<Value> {
+        mock.requests()
+            .iter()
+            .filter(|request| request.url.path().ends_with("/data/mutate/production"))
+            .map(|request| serde_json::from_slice(request.body.as_ref().unwrap()).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn imports_export_archives() -> Result<(), RequestError> {
+        let ndjson = r#"{"_id":"post-1","_type":"post","image":{"_type":"image","asset":{"_ref":"image-abc-10x20-png","_type":"reference"}}}
+{"_id":"post-2","_type":"post","cover":{"_type":"image","asset":{"_ref":"image-abc-10x20-png","_type":"reference"}}}
+{"_id":"post-3","_type":"post"}
+"#;
+        let export = Arc::new(
+            MockTransport::new()
+                .route("GET", "/v2022-03-07/data/export/production", ndjson)
+                .route(
+                    "GET",
+                    "/images/project/production/abc-10x20.png",
+                    b"png".to_vec(),
+                ),
+        );
+        let path = std::env::temp_dir().join(format!(
+            "sanity-rs-import-test-{}.tar.gz",
+            std::process::id()
+        ));
+        test_client(&export)
+            .export("production")
+            .archive(File::create(&path)?)
+            .await?;
+
+        let mock = Arc::new(
+            MockTransport::new()
+                .route("POST", "/v2022-03-07/assets/images/production", UPLOADED)
+                .route("POST", "/v2022-03-07/data/mutate/production", MUTATED),
+        );
+        let progress = Arc::new(Mutex::new(Vec::new()));
+        let reported = progress.clone();
+        let summary = test_client(&mock)
+            .import("production")
+            .mode(ConflictMode::Replace)
+            .batch_size(2)
+            .on_progress(move |p| reported.lock().unwrap().push((p.stage, p.done, p.total)))
+            .file(&path)
+            .await?;
+        std::fs::remove_file(&path)?;
+        assert_eq!(
+            summary,
+            ImportSummary {
+                documents: 3,
+                assets: 1
+            }
+        );
+        assert_eq!(
+            *progress.lock().unwrap(),
+            [
+                (ImportStage::UploadingAssets, 1, 1),
+                (ImportStage::WritingDocuments, 2, 3),
+                (ImportStage::WritingDocuments, 3, 3)
+            ]
+        );
+
+        let upload = &mock.requests()[0];
+        assert_eq!(upload.body.as_deref(), Some(&b"png"[..]));
+        assert_eq!(upload.url.query(), Some("filename=abc-10x20.png"));
+        assert_eq!(upload.headers["Content-Type"], "image/png");
+
+        let mutations = mutations(&mock);
+        assert_eq!(mutations.len(), 2);
+        assert_eq!(
+            mutations[0]["mutations"][1],
+            json!({"createOrReplace": {
+                "_id": "post-2",
+                "_type": "post",
+                "cover": {"_type": "image", "asset": {"_type": "reference", "_ref": "image-new-10x20-png"}}
+            }})
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn imports_ndjson() -> Result<(), RequestError> {
+        let ndjson = r#"{"_id":"a","_type":"author","cv":{"_sanityAsset":"file@https://example.com/files/cv.pdf"}}"#;
+        let mock = Arc::new(
+            MockTransport::new()
+                .route("GET", "/files/cv.pdf", b"%PDF".to_vec())
+                .route(
+                    "POST",
+                    "/v2022-03-07/assets/files/production",
+                    r#"{"document":{"_id":"file-cv-pdf"}}"#,
+                )
+                .route("POST", "/v2022-03-07/data/mutate/production", MUTATED),
+        );
+        let summary = test_client(&mock)
+            .import("production")
+            .mode(ConflictMode::Missing)
+            .ndjson(ndjson.as_bytes())
+            .await?;
+        assert_eq!(
+            summary,
+            ImportSummary {
+                documents: 1,
+                assets: 1
+            }
+        );
+        assert_eq!(
+            mutations(&mock)[0]["mutations"][0]["createIfNotExists"]["cv"],
+            json!({"asset": {"_type": "reference", "_ref": "file-cv-pdf"}})
+        );
+        let requests = mock.requests();
+        assert_eq!(requests[0].url.as_str(), "https://example.com/files/cv.pdf");
+        assert!(!requests[0].headers.contains_key("Authorization"));
+        assert!(requests[1].headers.contains_key("Authorization"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn strengthens_references_after_writing() -> Result<(), RequestError> {
+        let ndjson = r#"{"_id":"post-1","_type":"post","author":{"_type":"reference","_ref":"author-1"},"related":[{"_key":"a","_ref":"post-2","_weak":true}]}
+{"_id":"author-1","_type":"author","friends":[{"_key":"a","_ref":"author-2"}]}
+"#;
+        let mock = Arc::new(MockTransport::new().route(
+            "POST",
+            "/v2022-03-07/data/mutate/production",
+            MUTATED,
+        ));
+        let progress = Arc::new(Mutex::new(Vec::new()));
+        let reported = progress.clone();
+        test_client(&mock)
+            .import("production")
+            .batch_size(1)
+            .on_progress(move |p| reported.lock().unwrap().push((p.stage, p.done, p.total)))
+            .ndjson(ndjson.as_bytes())
+            .await?;
+        let mutations = mutations(&mock);
+        assert_eq!(mutations.len(), 4);
+        assert_eq!(
+            mutations[0]["mutations"][0]["create"]["author"],
+            json!({"_type": "reference", "_ref": "author-1", "_weak": true})
+        );
+        assert_eq!(
+            mutations[2]["mutations"],
+            json!([{"patch": {"id": "post-1", "unset": ["author._weak"]}}])
+        );
+        assert_eq!(
+            mutations[3]["mutations"],
+            json!([{"patch": {"id": "author-1", "unset": ["friends[0]._weak"]}}])
+        );
+        assert_eq!(
+            progress.lock().unwrap().last(),
+            Some(&(ImportStage::StrengtheningReferences, 2, 2))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn conflicts_fail() {
+        let mock = Arc::new(MockTransport::new().route_with_status(
+            "POST",
+            "/v2022-03-07/data/mutate/production",
+            409,
+            r#"{"error":{"description":"Document by ID \"a\" already exists"}}"#,
+        ));
+        let result = test_client(&mock)
+            .import("production")
+            .ndjson(br#"{"_id":"a","_type":"post"}"#)
+            .await;
+        assert!(matches!(
+            result,
+            Err(RequestError::ApiError { status: 409, .. })
+        ));
+        assert!(mutations(&mock)[0]["mutations"][0]["create"].is_object());
+    }
+
+    #[tokio::test]
+    async fn asset_ids_must_be_strings() {
+        let ndjson = r#"{"_id":"a","_type":"author","cv":{"_sanityAsset":"file@https://example.com/files/cv.pdf"}}"#;
+        let mock = Arc::new(
+            MockTransport::new()
+                .route("GET", "/files/cv.pdf", b"%PDF".to_vec())
+                .route(
+                    "POST",
+                    "/v2022-03-07/assets/files/production",
+                    r#"{"document":{"_id":42}}"#,
+                ),
+        );
+        let result = test_client(&mock)
+            .import("production")
+            .ndjson(ndjson.as_bytes())
+            .await;
+        assert!(matches!(result, Err(RequestError::StringParsingError(_))));
+        assert!(mutations(&mock).is_empty());
+    }
+}
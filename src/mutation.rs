@@ -0,0 +1,105 @@
+//! Write documents with mutations, sent together as one transaction
+//!
+//! ```no_run
+//! # use sanity_rs::client::SanityClient;
+//! # use sanity_rs::error::RequestError;
+//! # use sanity_rs::mutation::Mutation;
+//! # use serde_json::json;
+//! # async fn publish(client: &SanityClient) -> Result<(), RequestError> {
+//! let result = client
+//!     .mutate(&[
+//!         Mutation::CreateOrReplace(json!({"_id": "post-1", "_type": "post", "title": "Hello"})),
+//!         Mutation::Delete { id: "drafts.post-1".to_string() },
+//!     ])
+//!     .await?;
+//! println!("{}", result.transaction_id);
+//! # Ok(())
+//! # }
+//! ```
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::client::SanityClient;
+use crate::error::RequestError;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Mutation {
+    /// Create a document, failing if its `_id` exists
+    Create(Value),
+    CreateOrReplace(Value),
+    CreateIfNotExists(Value),
+    Delete {
+        id: String,
+    },
+    /// A patch as the API takes it, e.g. `{"id": "a", "set": {"title": "b"}}`
+    Patch(Value),
+}
+
+/// Response of the mutate endpoint
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MutationResult {
+    pub transaction_id: String,
+    #[serde(default)]
+    pub results: Vec<Value>,
+}
+
+impl SanityClient {
+    /// Apply mutations to the dataset of the client in one transaction
+    pub async fn mutate(&self, mutations: &[Mutation]) -> Result<MutationResult, RequestError> {
+        self.mutate_dataset(&self.config().dataset, mutations).await
+    }
+
+    pub(crate) async fn mutate_dataset(
+        &self,
+        dataset: &str,
+        mutations: &[Mutation],
+    ) -> Result<MutationResult, RequestError> {
+        let url = self.endpoint(&format!("data/mutate/{}", dataset))?;
+        let request = self
+            .request("POST", url)
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_vec(&json!({ "mutations": mutations }))?);
+        Ok(serde_json::from_value(self.send_json(request).await?)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::transport::{test_client, MockTransport};
+
+    #[tokio::test]
+    async fn sends_mutations() -> Result<(), RequestError> {
+        let mock = Arc::new(MockTransport::new().route(
+            "POST",
+            "/v2022-03-07/data/mutate/production",
+            r#"{"transactionId":"tx-1","results":[{"id":"a","operation":"create"}]}"#,
+        ));
+        let client = test_client(&mock);
+        let result = client
+            .mutate(&[
+                Mutation::Create(json!({"_id": "a", "_type": "post"})),
+                Mutation::Delete {
+                    id: "drafts.a".to_string(),
+                },
+                Mutation::Patch(json!({"id": "b", "set": {"title": "B"}})),
+            ])
+            .await?;
+        assert_eq!(result.transaction_id, "tx-1");
+        let body: Value =
+            serde_json::from_slice(mock.requests()[0].body.as_ref().unwrap()).unwrap();
+        assert_eq!(
+            body,
+            json!({"mutations": [
+                {"create": {"_id": "a", "_type": "post"}},
+                {"delete": {"id": "drafts.a"}},
+                {"patch": {"id": "b", "set": {"title": "B"}}}
+            ]})
+        );
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
 use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
 
 use crate::client::SanityClient;
 use crate::error::RequestError;
@@ -14,16 +15,18 @@ pub trait ORM {
 
 impl ORM for SanityClient {
     fn get_by_id(&mut self, id: &str) -> &mut SanityClient {
-        let string = format!("*[_id == '{}'][0]", id);
-        let query = &mut self.payload.query;
-        SanityURL::query(query, string.as_str());
+        let mut params = Map::new();
+        params.insert("id".to_string(), Value::from(id));
+        self.payload.groq = Some("*[_id == $id][0]".to_string());
+        self.payload.params = params;
         self
     }
 
     fn get_by_ids(&mut self, ids: &[&str]) -> &mut SanityClient {
-        let string = format!(r#"*[_id in ["{}"]]"#, ids.join("\",\""));
-        let query = &mut self.payload.query;
-        SanityURL::query(query, string.as_str());
+        let mut params = Map::new();
+        params.insert("ids".to_string(), Value::from(ids));
+        self.payload.groq = Some("*[_id in $ids]".to_string());
+        self.payload.params = params;
         self
     }
 
@@ -34,28 +37,30 @@ impl ORM for SanityClient {
         Ok(value)
     }
 
+    /// Send the pending query with `body` appended, e.g. a projection
     async fn send(&mut self) -> Result<&mut Self, RequestError> {
-        let query = &mut self.payload.query;
-        let body = &self.payload.body;
-        let url = format!("{}{}", query.as_str(), body.as_ref().unwrap());
-        let v = self.client.get(url.as_str()).send();
-        let v = v.await?.text().await?;
-        self.payload.query_result = Some(v);
-        Ok(self)
+        let groq = self.payload.groq.take().unwrap_or_default();
+        let query = format!(
+            "{}{}",
+            groq,
+            self.payload.body.as_deref().unwrap_or_default()
+        );
+        SanityURL::query_with_params(&mut self.payload.query, &query, &self.payload.params);
+        self.execute().await
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::config::SanityConfig;
-    use crate::create_client;
-    use crate::error::ConfigurationError;
+    use std::sync::Arc;
+
     use crate::error::RequestError;
     use crate::orm::ORM;
-    use dotenv::dotenv;
+    use crate::transport::{test_client, MockTransport};
     use serde::{Deserialize, Serialize};
+    use serde_json::{json, Value};
 
-    #[allow(non_snake_case)]
+    #[allow(dead_code, non_snake_case)]
     #[derive(Debug, Serialize, Deserialize)]
     struct Record {
         _id: String,
@@ -78,50 +83,69 @@ mod tests {
         ms: usize,
     }
 
+    fn mock() -> Arc<MockTransport> {
+        let documents = vec![
+            json!({"_id": "09139a58-311b-4779-8fa4-723f19242a8e", "_type": "post", "_createdAt": "2024-01-05T10:00:00Z"}),
+            json!({"_id": "ad79d8a3-35a9-4ac6-ab5b-cc0c62288b37", "_type": "post", "_createdAt": "2024-02-01T10:00:00Z"}),
+            json!({"_id": "unrelated", "_type": "author", "_createdAt": "2024-03-01T10:00:00Z"}),
+        ];
+        Arc::new(MockTransport::new().dataset(documents))
+    }
+
     #[tokio::test]
     async fn get_by_id() -> Result<(), RequestError> {
-        dotenv().ok();
-        let sanity_project_id = std::env::var("SANITY_PROJECT_ID")
-            .map_err(|_| ConfigurationError::MissingProjectID)
-            .expect("Missing project ID");
-        let sanity_dataset = std::env::var("SANITY_DATASET")
-            .map_err(|_| ConfigurationError::MissingDataset)
-            .expect("Missing dataset");
-        let config = SanityConfig::new(sanity_project_id, sanity_dataset);
-
-        let mut client = create_client(config);
+        let mock = mock();
+        let mut client = test_client(&mock);
         let v = client
             .get_by_id("09139a58-311b-4779-8fa4-723f19242a8e")
             .body("{_id,_createdAt}")
             .send()
-            .await;
-        assert!(v.is_ok());
+            .await?
+            .json::<Value>()?;
+        assert_eq!(
+            v["result"],
+            json!({"_id": "09139a58-311b-4779-8fa4-723f19242a8e", "_createdAt": "2024-01-05T10:00:00Z"})
+        );
+        let (query, params) = mock.requests()[0].groq().unwrap();
+        assert_eq!(query, "*[_id == $id][0]{_id,_createdAt}");
+        assert_eq!(params["id"], json!("09139a58-311b-4779-8fa4-723f19242a8e"));
         Ok(())
     }
 
     #[tokio::test]
     async fn get_by_ids() -> Result<(), RequestError> {
-        dotenv().ok();
-        let sanity_project_id = std::env::var("SANITY_PROJECT_ID")
-            .map_err(|_| ConfigurationError::MissingProjectID)
-            .expect("Missing project ID");
-        let sanity_dataset = std::env::var("SANITY_DATASET")
-            .map_err(|_| ConfigurationError::MissingDataset)
-            .expect("Missing dataset");
-        let config = SanityConfig::new(sanity_project_id, sanity_dataset);
-
-        let mut client = create_client(config);
+        let mock = mock();
+        let mut client = test_client(&mock);
         let ids = vec![
             "09139a58-311b-4779-8fa4-723f19242a8e",
             "ad79d8a3-35a9-4ac6-ab5b-cc0c62288b37",
         ];
+        let v = client.get_by_ids(&ids).body("").send().await?;
+        let v = v.json::<QueryResult>()?;
+        assert_eq!(v.result.len(), 2);
+        assert_eq!(v.result[1]._id, ids[1]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn canned_responses_and_auth() -> Result<(), RequestError> {
+        let mock = Arc::new(
+            MockTransport::new().respond(r#"*[_type == "post"]{_id}"#, json!([{"_id": "a"}])),
+        );
+        let mut client = test_client(&mock);
         let v = client
-            .get_by_ids(&ids)
-            .body("")
-            .send()
-            .await?;
-        let v = v.json::<QueryResult>();
-        assert!(v.is_ok());
+            .query("*[ _type == 'post' ] {\n  _id\n}")
+            .await?
+            .json::<Value>()?;
+        assert_eq!(v["result"], json!([{"_id": "a"}]));
+        assert_eq!(
+            mock.requests()[0].headers.get("Authorization"),
+            Some(&"Bearer token".to_string())
+        );
+        assert!(matches!(
+            client.query("*[_type == 'page']").await,
+            Err(RequestError::TransportError(_))
+        ));
         Ok(())
     }
 }
@@ -1,36 +1,167 @@
 #![allow(dead_code)]
-use std::collections::HashMap;
 use std::fmt::Display;
 use std::hash::Hash;
 
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+
+use super::handlers::Handlers;
+use super::html::block_html;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Block {
     Span,
+    #[default]
     Block,
 }
 
-#[derive(Debug, Eq, Hash, PartialEq)]
+/// The `style` of a block, styles defined in the schema are kept as `Custom`
+#[derive(Debug, Clone, Default, Eq, Hash, PartialEq, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
 pub enum Style {
     H1,
     H2,
     H3,
     H4,
     H5,
+    H6,
+    #[default]
     Normal,
     Blockquote,
+    Custom(String),
+}
+
+impl From<String> for Style {
+    fn from(style: String) -> Self {
+        match style.as_str() {
+            "h1" => Style::H1,
+            "h2" => Style::H2,
+            "h3" => Style::H3,
+            "h4" => Style::H4,
+            "h5" => Style::H5,
+            "h6" => Style::H6,
+            "normal" => Style::Normal,
+            "blockquote" => Style::Blockquote,
+            _ => Style::Custom(style),
+        }
+    }
+}
+
+impl From<Style> for String {
+    fn from(style: Style) -> Self {
+        match style {
+            Style::H1 => "h1".to_string(),
+            Style::H2 => "h2".to_string(),
+            Style::H3 => "h3".to_string(),
+            Style::H4 => "h4".to_string(),
+            Style::H5 => "h5".to_string(),
+            Style::H6 => "h6".to_string(),
+            Style::Normal => "normal".to_string(),
+            Style::Blockquote => "blockquote".to_string(),
+            Style::Custom(style) => style,
+        }
+    }
+}
+
+impl Style {
+    /// The HTML tag of the style, custom styles render as paragraphs
+    pub fn tag(&self) -> &'static str {
+        match self {
+            Style::H1 => "h1",
+            Style::H2 => "h2",
+            Style::H3 => "h3",
+            Style::H4 => "h4",
+            Style::H5 => "h5",
+            Style::H6 => "h6",
+            Style::Normal | Style::Custom(_) => "p",
+            Style::Blockquote => "blockquote",
+        }
+    }
 }
 
+/// A child of a block, a span or an inline object which is kept as it is
+///
+/// Blocks do not nest, a `block` among the children is kept as an inline object.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
 pub enum Children {
     Text(TextNode),
-    Node(Node),
+    Inline(Value),
+}
+
+impl<'de> Deserialize<'de> for Children {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        match value.get("_type").and_then(Value::as_str) {
+            Some("span") => serde_json::from_value(value).map(Children::Text),
+            None if value.get("text").is_some() => {
+                serde_json::from_value(value).map(Children::Text)
+            }
+            _ => Ok(Children::Inline(value)),
+        }
+        .map_err(D::Error::custom)
+    }
 }
 
 #[allow(non_snake_case)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct Node {
+    #[serde(default)]
     pub _key: String,
+    #[serde(default)]
     pub _type: Block,
+    #[serde(default)]
     pub children: Vec<Children>,
+    #[serde(default)]
     pub markDefs: Vec<MarkDef>,
+    #[serde(default)]
     pub style: Style,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub listItem: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub level: Option<u32>,
+}
+
+/// An item of a Portable Text array, a text block or a custom block such as an image
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum Content {
+    Block(Node),
+    /// Any other `_type`, kept as it is
+    Custom(Value),
+}
+
+impl<'de> Deserialize<'de> for Content {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        match value.get("_type").and_then(Value::as_str) {
+            Some("block") => serde_json::from_value(value)
+                .map(Content::Block)
+                .map_err(D::Error::custom),
+            _ => Ok(Content::Custom(value)),
+        }
+    }
+}
+
+impl From<Node> for Content {
+    fn from(node: Node) -> Self {
+        Content::Block(node)
+    }
+}
+
+impl Content {
+    /// The `_type` of the item, `block` for text blocks
+    pub fn type_name(&self) -> &str {
+        match self {
+            Content::Block(_) => "block",
+            Content::Custom(value) => value
+                .get("_type")
+                .and_then(Value::as_str)
+                .unwrap_or_default(),
+        }
+    }
 }
 
 pub trait Render {
@@ -39,27 +170,7 @@ pub trait Render {
 
 impl Render for Node {
     fn html(&self) -> String {
-        let mut result = String::from("");
-        let tag = match &self.style {
-            Style::H1 => "h1",
-            Style::H2 => "h2",
-            Style::H3 => "h3",
-            Style::H4 => "h4",
-            Style::H5 => "h5",
-            Style::Normal => "p",
-            Style::Blockquote => "blockquote",
-        };
-        for child in &self.children {
-            match child {
-                Children::Text(text) => {
-                    result.push_str(&format!("<{}>{}</{}>", tag, text.text, tag));
-                }
-                Children::Node(node) => {
-                    result.push_str(&node.html());
-                }
-            }
-        }
-        result
+        block_html(self, &Handlers::default())
     }
 }
 
@@ -69,11 +180,180 @@ impl Display for Node {
     }
 }
 
-pub struct MarkDef(HashMap<String, String>);
+/// An annotation of a block, spans refer to it by `_key` in their `marks`
+#[allow(non_snake_case)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MarkDef {
+    #[serde(default)]
+    pub _key: String,
+    #[serde(default)]
+    pub _type: String,
+    /// The other fields, e.g. `href` for links
+    #[serde(flatten)]
+    pub data: Map<String, Value>,
+}
 
+#[allow(non_snake_case)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct TextNode {
+    #[serde(default)]
     pub _key: String,
+    #[serde(default = "TextNode::span")]
     pub _type: String,
     pub text: String,
+    #[serde(default)]
     pub marks: Vec<String>,
 }
+
+impl TextNode {
+    fn span() -> String {
+        "span".to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::portabletext::html::is_safe_url;
+    use serde_json::json;
+
+    #[test]
+    fn deserialize_body() {
+        let body = json!([
+            {
+                "_type": "block",
+                "_key": "b1",
+                "style": "h2",
+                "markDefs": [
+                    {"_type": "link", "_key": "l1", "href": "https://sanity.io"},
+                    {"_type": "internalLink", "_key": "l2", "reference": {"_type": "reference", "_ref": "post-1"}}
+                ],
+                "children": [
+                    {"_type": "span", "_key": "s1", "text": "Hello ", "marks": ["strong"]},
+                    {"_type": "span", "_key": "s2", "text": "world", "marks": ["l1", "l2"]},
+                    {"_type": "footnote", "_key": "f1", "note": "Inline object"}
+                ]
+            },
+            {"_type": "block", "style": "lead", "listItem": "bullet", "level": 2, "children": [{"text": "Item"}]},
+            {"_type": "image", "_key": "i1", "asset": {"_type": "reference", "_ref": "image-abc-10x20-png"}}
+        ]);
+        let content: Vec<Content> = serde_json::from_value(body.clone()).unwrap();
+        assert_eq!(content.len(), 3);
+
+        let Content::Block(heading) = &content[0] else {
+            panic!("expected a block");
+        };
+        assert_eq!(heading.style, Style::H2);
+        assert_eq!(heading.markDefs[1]._type, "internalLink");
+        assert_eq!(heading.markDefs[1].data["reference"]["_ref"], "post-1");
+        assert_eq!(heading.markDefs[0].data["href"], "https://sanity.io");
+        assert!(matches!(&heading.children[1], Children::Text(span) if span.marks == ["l1", "l2"]));
+        assert!(
+            matches!(&heading.children[2], Children::Inline(inline) if inline["note"] == "Inline object")
+        );
+
+        let Content::Block(item) = &content[1] else {
+            panic!("expected a block");
+        };
+        assert_eq!(item.style, Style::Custom("lead".to_string()));
+        assert_eq!(item.listItem.as_deref(), Some("bullet"));
+        assert_eq!(item.level, Some(2));
+        assert_eq!(item._key, "");
+
+        assert_eq!(content[2].type_name(), "image");
+        assert_eq!(serde_json::to_value(&content[2]).unwrap(), body[2]);
+        assert_eq!(
+            serde_json::to_value(&content[0]).unwrap()["markDefs"],
+            body[0]["markDefs"]
+        );
+    }
+
+    #[test]
+    fn render_decorators() {
+        let node: Node = serde_json::from_value(json!({
+            "_type": "block",
+            "children": [
+                {"_type": "span", "text": "plain "},
+                {"_type": "span", "text": "bold ", "marks": ["strong"]},
+                {"_type": "span", "text": "both", "marks": ["em", "strong"]},
+                {"_type": "span", "text": " italic", "marks": ["em"]},
+                {"_type": "span", "text": " code", "marks": ["code"]},
+                {"_type": "span", "text": " u\ns", "marks": ["underline", "strike-through", "unknown"]}
+            ]
+        }))
+        .unwrap();
+        assert_eq!(
+            node.html(),
+            "<p>plain <strong>bold <em>both</em></strong><em> italic</em><code> code</code>\
+             <u><s> u<br/>s</s></u></p>"
+        );
+    }
+
+    #[test]
+    fn one_element_per_block() {
+        let node: Node = serde_json::from_value(json!({
+            "_type": "block",
+            "style": "h3",
+            "children": [
+                {"_type": "span", "text": "One, "},
+                {"_type": "span", "text": "two", "marks": ["em"]},
+                {"_type": "block", "children": [{"_type": "span", "text": "nested"}]},
+                {"_type": "span", "text": " and three"}
+            ]
+        }))
+        .unwrap();
+        assert!(matches!(&node.children[2], Children::Inline(_)));
+        assert_eq!(node.html(), "<h3>One, <em>two</em> and three</h3>");
+        assert_eq!(Node::default().html(), "<p></p>");
+    }
+
+    #[test]
+    fn escape_text_and_links() {
+        let node: Node = serde_json::from_value(json!({
+            "_type": "block",
+            "markDefs": [
+                {"_type": "link", "_key": "l1", "href": " JavaScript:alert(1)"},
+                {"_type": "link", "_key": "l2", "href": "java\tscript:alert(1)"},
+                {"_type": "link", "_key": "l3", "href": "mailto:a@b.c"}
+            ],
+            "children": [
+                {"_type": "span", "text": "<script>alert('x')</script> & ", "marks": ["strong"]},
+                {"_type": "span", "text": "one", "marks": ["l1"]},
+                {"_type": "span", "text": "two", "marks": ["l2"]},
+                {"_type": "span", "text": "mail", "marks": ["l3"]}
+            ]
+        }))
+        .unwrap();
+        assert_eq!(
+            node.html(),
+            "<p><strong>&lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt; &amp; </strong>onetwo\
+             <a href=\"mailto:a@b.c\">mail</a></p>"
+        );
+        assert!(is_safe_url("/posts/1?a=b:c"));
+        assert!(is_safe_url("#top"));
+        assert!(is_safe_url("HTTPS://sanity.io"));
+        assert!(!is_safe_url("data:text/html,hi"));
+        assert!(!is_safe_url("vbscript:msgbox"));
+    }
+
+    #[test]
+    fn render_links() {
+        let node: Node = serde_json::from_value(json!({
+            "_type": "block",
+            "markDefs": [
+                {"_type": "link", "_key": "l1", "href": "https://example.com/?a=1&b=\"2\""},
+                {"_type": "link", "_key": "l2"}
+            ],
+            "children": [
+                {"_type": "span", "text": "read ", "marks": ["l1"]},
+                {"_type": "span", "text": "this", "marks": ["l1", "strong"]},
+                {"_type": "span", "text": " or not", "marks": ["l2", "missing"]}
+            ]
+        }))
+        .unwrap();
+        assert_eq!(
+            node.html(),
+            "<p><a href=\"https://example.com/?a=1&amp;b=&quot;2&quot;\">read <strong>this</strong></a> or not</p>"
+        );
+    }
+}
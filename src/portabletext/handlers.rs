@@ -0,0 +1,84 @@
+//! Handlers which replace how parts of Portable Text are rendered
+//!
+//! A handler is called with the value it renders and a `Context` holding the children rendered
+//! with the other handlers, so it can wrap them or fall back to the default output.
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde_json::Value;
+
+use super::blocks::{MarkDef, Node, Style};
+use super::lists::{List, ListItem};
+
+/// What a handler needs besides its value
+pub struct Context<'a> {
+    children: &'a str,
+    default: &'a dyn Fn(&str) -> String,
+}
+
+impl<'a> Context<'a> {
+    pub(crate) fn new(children: &'a str, default: &'a dyn Fn(&str) -> String) -> Self {
+        Context { children, default }
+    }
+
+    /// The rendered children, empty for hard breaks and custom types
+    pub fn children(&self) -> &str {
+        self.children
+    }
+
+    /// The output without this handler
+    pub fn render_default(&self) -> String {
+        (self.default)(self.children)
+    }
+
+    /// The output without this handler around other children, e.g. decorated ones
+    pub fn render_default_with(&self, children: &str) -> String {
+        (self.default)(children)
+    }
+}
+
+pub type Handler<T> = Box<dyn Fn(&T, &Context) -> String + Send + Sync>;
+pub type ListHandler = Box<dyn Fn(&List, &Context) -> String + Send + Sync>;
+pub type ListItemHandler = Box<dyn Fn(&ListItem, &Context) -> String + Send + Sync>;
+pub type HardBreakHandler = Box<dyn Fn(&Context) -> String + Send + Sync>;
+
+/// What to do with custom types which have no handler
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnknownType {
+    /// Leave them out
+    #[default]
+    Skip,
+    /// Leave them out and record a warning
+    Warn,
+}
+
+/// The registered handlers of a renderer, by style, mark, `_type` or `listItem`
+#[derive(Default)]
+pub(crate) struct Handlers {
+    pub blocks: HashMap<Style, Handler<Node>>,
+    pub marks: HashMap<String, Handler<str>>,
+    pub annotations: HashMap<String, Handler<MarkDef>>,
+    pub lists: HashMap<String, ListHandler>,
+    pub list_items: HashMap<String, ListItemHandler>,
+    pub types: HashMap<String, Handler<Value>>,
+    pub hard_break: Option<HardBreakHandler>,
+    /// Project and dataset of the built-in `image` handler
+    pub images: Option<(String, String)>,
+    pub unknown_types: UnknownType,
+    pub warnings: Mutex<Vec<String>>,
+}
+
+impl Handlers {
+    /// Render with the handler if there is one, the default otherwise
+    pub fn call<T: ?Sized>(
+        handler: Option<&Handler<T>>,
+        value: &T,
+        children: &str,
+        default: &dyn Fn(&str) -> String,
+    ) -> String {
+        match handler {
+            Some(handler) => handler(value, &Context::new(children, default)),
+            None => default(children),
+        }
+    }
+}
@@ -0,0 +1,206 @@
+//! HTML output of Portable Text, text and attribute values are escaped
+use serde_json::Value;
+
+use crate::assets::ImageUrl;
+
+use super::blocks::{MarkDef, Node, Style};
+use super::handlers::{Context, Handlers, UnknownType};
+use super::lists::List;
+use super::marks::{build_marks_tree, MarkTree};
+
+/// Escape text for use in an HTML attribute or element
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Schemes a link may use, relative links have none
+pub const SAFE_SCHEMES: [&str; 4] = ["http", "https", "mailto", "tel"];
+
+/// Whether a link is relative or uses one of `SAFE_SCHEMES`, which rules out `javascript:`
+pub fn is_safe_url(url: &str) -> bool {
+    // Browsers ignore whitespace and control characters inside the scheme
+    let url: String = url
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect();
+    let scheme = match url.find([':', '/', '?', '#']) {
+        Some(end) if url[end..].starts_with(':') => &url[..end],
+        _ => return true,
+    };
+    SAFE_SCHEMES
+        .iter()
+        .any(|safe| safe.eq_ignore_ascii_case(scheme))
+}
+
+fn wrap(tag: &str, children: &str) -> String {
+    format!("<{}>{}</{}>", tag, children, tag)
+}
+
+pub(crate) fn block_html(node: &Node, handlers: &Handlers) -> String {
+    let default = |children: &str| wrap(node.style.tag(), children);
+    Handlers::call(
+        handlers.blocks.get(&node.style),
+        node,
+        &inline_html(node, handlers),
+        &default,
+    )
+}
+
+/// The spans and inline objects of a block, without its element
+pub(crate) fn inline_html(node: &Node, handlers: &Handlers) -> String {
+    marks_html(&build_marks_tree(&node.children, &node.markDefs), handlers)
+}
+
+/// The HTML tag of a decorator
+fn decorator_tag(mark: &str) -> Option<&'static str> {
+    match mark {
+        "strong" => Some("strong"),
+        "em" => Some("em"),
+        "code" => Some("code"),
+        "underline" => Some("u"),
+        "strike-through" => Some("s"),
+        _ => None,
+    }
+}
+
+/// Render an annotation without a registered handler, safe links become `<a href>`
+fn default_annotation(def: &MarkDef, children: &str) -> String {
+    match (
+        def._type.as_str(),
+        def.data.get("href").and_then(Value::as_str),
+    ) {
+        ("link", Some(href)) if is_safe_url(href) => {
+            format!("<a href=\"{}\">{}</a>", escape_html(href), children)
+        }
+        _ => children.to_string(),
+    }
+}
+
+/// `<img>` of an image field on the Sanity CDN, with its `alt` text
+fn image_html(project_id: &str, dataset: &str, value: &Value) -> String {
+    let Some(url) = ImageUrl::from_source(project_id, dataset, value) else {
+        return String::new();
+    };
+    let alt = value.get("alt").and_then(Value::as_str).unwrap_or_default();
+    format!(
+        "<img src=\"{}\" alt=\"{}\"/>",
+        escape_html(&url.url()),
+        escape_html(alt)
+    )
+}
+
+/// Render a custom block or inline object with its handler or a built-in one
+pub(crate) fn custom_html(value: &Value, handlers: &Handlers) -> String {
+    let type_name = value
+        .get("_type")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let builtin = match (&handlers.images, type_name) {
+        (Some((project_id, dataset)), "image") => Some(image_html(project_id, dataset, value)),
+        _ => None,
+    };
+    if let Some(handler) = handlers.types.get(type_name) {
+        let default = |_: &str| builtin.clone().unwrap_or_default();
+        return handler(value, &Context::new("", &default));
+    }
+    if let Some(html) = builtin {
+        return html;
+    }
+    if handlers.unknown_types == UnknownType::Warn {
+        handlers
+            .warnings
+            .lock()
+            .unwrap()
+            .push(format!("no handler for the type `{}`", type_name));
+    }
+    String::new()
+}
+
+/// Render a marks tree, unknown marks render their children only
+pub(crate) fn marks_html(tree: &[MarkTree], handlers: &Handlers) -> String {
+    let mut result = String::from("");
+    for node in tree {
+        match node {
+            MarkTree::Text(text) => result.push_str(&escape_html(text)),
+            MarkTree::HardBreak => {
+                let default = |_: &str| "<br/>".to_string();
+                let rendered = match &handlers.hard_break {
+                    Some(handler) => handler(&Context::new("", &default)),
+                    None => default(""),
+                };
+                result.push_str(&rendered);
+            }
+            MarkTree::Inline(value) => result.push_str(&custom_html(value, handlers)),
+            MarkTree::Mark {
+                def: Some(def),
+                children,
+                ..
+            } => result.push_str(&Handlers::call(
+                handlers.annotations.get(&def._type),
+                def,
+                &marks_html(children, handlers),
+                &|children| default_annotation(def, children),
+            )),
+            MarkTree::Mark {
+                key,
+                def: None,
+                children,
+            } => result.push_str(&Handlers::call(
+                handlers.marks.get(*key),
+                *key,
+                &marks_html(children, handlers),
+                &|children| match decorator_tag(key) {
+                    Some(tag) => wrap(tag, children),
+                    None => children.to_string(),
+                },
+            )),
+        }
+    }
+    result
+}
+
+/// Render a list and the lists nested in its items
+pub(crate) fn list_html(list: &List, handlers: &Handlers) -> String {
+    let mut items = String::from("");
+    for item in &list.items {
+        // Items keep their style, e.g. a heading inside a list, but not a paragraph around
+        // their text
+        let styled = |children: &str| match &item.node.style {
+            Style::Normal | Style::Custom(_) => children.to_string(),
+            style => wrap(style.tag(), children),
+        };
+        let mut children = match item.node.style {
+            Style::Normal => inline_html(item.node, handlers),
+            _ => Handlers::call(
+                handlers.blocks.get(&item.node.style),
+                item.node,
+                &inline_html(item.node, handlers),
+                &styled,
+            ),
+        };
+        for nested in &item.lists {
+            children.push_str(&list_html(nested, handlers));
+        }
+        let default = |children: &str| wrap("li", children);
+        items.push_str(&match handlers.list_items.get(list.kind) {
+            Some(handler) => handler(item, &Context::new(&children, &default)),
+            None => default(&children),
+        });
+    }
+    let default = |items: &str| wrap(if list.kind == "number" { "ol" } else { "ul" }, items);
+    match handlers.lists.get(list.kind) {
+        Some(handler) => handler(list, &Context::new(&items, &default)),
+        None => default(&items),
+    }
+}
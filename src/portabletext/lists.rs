@@ -0,0 +1,146 @@
+//! Nest the flat list blocks of Portable Text into lists
+//!
+//! List items are blocks with a `listItem` type and a `level`. Consecutive items make up a
+//! list, an item with a higher level starts a list inside the previous item and an item with
+//! another type at the same level starts a new list next to the current one.
+use super::blocks::{Content, Node};
+
+/// A block, or a list made of consecutive list items
+#[derive(Debug, Clone, PartialEq)]
+pub enum Nested<'a> {
+    Block(&'a Content),
+    List(List<'a>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct List<'a> {
+    /// The `listItem` of the items, e.g. `bullet` or `number`
+    pub kind: &'a str,
+    pub level: u32,
+    pub items: Vec<ListItem<'a>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListItem<'a> {
+    pub node: &'a Node,
+    /// Lists of the items with a higher level which follow this one
+    pub lists: Vec<List<'a>>,
+}
+
+impl<'a> List<'a> {
+    fn new(kind: &'a str, level: u32, node: &'a Node) -> Self {
+        List {
+            kind,
+            level,
+            items: vec![ListItem {
+                node,
+                lists: Vec::new(),
+            }],
+        }
+    }
+
+    /// Add an item to this list or to the last of its nested lists, `false` if it does not
+    /// belong in this list
+    fn insert(&mut self, kind: &'a str, level: u32, node: &'a Node) -> bool {
+        if level < self.level || (level == self.level && kind != self.kind) {
+            return false;
+        }
+        if level == self.level {
+            self.items.push(ListItem {
+                node,
+                lists: Vec::new(),
+            });
+            return true;
+        }
+        let item = self.items.last_mut().expect("lists have at least one item");
+        let inserted = match item.lists.last_mut() {
+            Some(list) => list.insert(kind, level, node),
+            None => false,
+        };
+        if !inserted {
+            item.lists.push(List::new(kind, level, node));
+        }
+        true
+    }
+}
+
+/// The `listItem` and level of a block, `None` when it is not a list item
+pub fn list_item(content: &Content) -> Option<(&Node, &str, u32)> {
+    match content {
+        Content::Block(node) => match node.listItem.as_deref() {
+            Some(kind) if !kind.is_empty() => Some((node, kind, node.level.unwrap_or(1))),
+            _ => None,
+        },
+        Content::Custom(_) => None,
+    }
+}
+
+/// Group the list items of a Portable Text array into nested lists
+pub fn nest_lists(input: &[Content]) -> Vec<Nested<'_>> {
+    let mut nested = Vec::new();
+    for content in input {
+        let Some((node, kind, level)) = list_item(content) else {
+            nested.push(Nested::Block(content));
+            continue;
+        };
+        if let Some(Nested::List(list)) = nested.last_mut() {
+            if list.insert(kind, level, node) {
+                continue;
+            }
+        }
+        nested.push(Nested::List(List::new(kind, level, node)));
+    }
+    nested
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn texts(items: &[ListItem]) -> Vec<String> {
+        items
+            .iter()
+            .map(|item| match &item.node.children[0] {
+                crate::portabletext::blocks::Children::Text(span) => span.text.clone(),
+                _ => String::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn nests_levels_and_kinds() {
+        let input: Vec<Content> = serde_json::from_value(json!([
+            {"_type": "block", "children": [{"text": "Intro"}]},
+            {"_type": "block", "listItem": "bullet", "level": 1, "children": [{"text": "a"}]},
+            {"_type": "block", "listItem": "bullet", "level": 3, "children": [{"text": "a.i"}]},
+            {"_type": "block", "listItem": "number", "level": 2, "children": [{"text": "a.1"}]},
+            {"_type": "block", "listItem": "bullet", "level": 1, "children": [{"text": "b"}]},
+            {"_type": "block", "listItem": "number", "children": [{"text": "1"}]},
+            {"_type": "block", "children": [{"text": "Outro"}]}
+        ]))
+        .unwrap();
+        let nested = nest_lists(&input);
+        assert_eq!(nested.len(), 4);
+        assert_eq!(nested[0], Nested::Block(&input[0]));
+        assert_eq!(nested[3], Nested::Block(&input[6]));
+
+        let Nested::List(bullets) = &nested[1] else {
+            panic!("expected a list");
+        };
+        assert_eq!((bullets.kind, bullets.level), ("bullet", 1));
+        assert_eq!(texts(&bullets.items), ["a", "b"]);
+        let lists = &bullets.items[0].lists;
+        assert_eq!(lists.len(), 2);
+        assert_eq!((lists[0].kind, lists[0].level), ("bullet", 3));
+        assert_eq!(texts(&lists[0].items), ["a.i"]);
+        assert_eq!((lists[1].kind, lists[1].level), ("number", 2));
+        assert_eq!(texts(&lists[1].items), ["a.1"]);
+
+        let Nested::List(numbers) = &nested[2] else {
+            panic!("expected a list");
+        };
+        assert_eq!((numbers.kind, numbers.level), ("number", 1));
+        assert_eq!(texts(&numbers.items), ["1"]);
+    }
+}
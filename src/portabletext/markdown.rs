@@ -0,0 +1,454 @@
+//! CommonMark output of Portable Text
+//!
+//! Blocks are separated by a blank line, lists are tight and nested by indentation. Text is
+//! escaped so it never turns into Markdown syntax, `code` blocks become fenced code blocks.
+//!
+//! ```
+//! use sanity_rs::portabletext::blocks::Content;
+//! use sanity_rs::portabletext::markdown::Markdown;
+//! use serde_json::json;
+//!
+//! let body: Vec<Content> = serde_json::from_value(json!([
+//!     {"_type": "block", "style": "h1", "children": [{"_type": "span", "text": "Title"}]},
+//!     {"_type": "block", "children": [{"_type": "span", "text": "Hello", "marks": ["strong"]}]}
+//! ]))
+//! .unwrap();
+//! assert_eq!(Markdown::new(body).render(), "# Title\n\n**Hello**");
+//! ```
+use serde_json::Value;
+
+use super::blocks::{Content, MarkDef, Node, Style};
+use super::handlers::{Context, Handlers};
+use super::html::is_safe_url;
+use super::lists::{nest_lists, List, Nested};
+use super::marks::{build_marks_tree, MarkTree};
+use crate::assets::ImageUrl;
+
+/// Serialize Portable Text to Markdown, with handlers like `Renderer`
+pub struct Markdown {
+    input: Vec<Content>,
+    handlers: Handlers,
+}
+
+impl Markdown {
+    pub fn new<T: Into<Content>>(input: impl IntoIterator<Item = T>) -> Self {
+        Markdown {
+            input: input.into_iter().map(Into::into).collect(),
+            handlers: Handlers::default(),
+        }
+    }
+
+    /// Serialize blocks of this style with `handler`, values it adds have to be escaped with
+    /// `escape_markdown`
+    pub fn block(
+        &mut self,
+        style: Style,
+        handler: impl Fn(&Node, &Context) -> String + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.handlers.blocks.insert(style, Box::new(handler));
+        self
+    }
+
+    /// Serialize a decorator, only the text of `underline`, `strike-through` and unknown ones
+    /// is kept by default
+    pub fn mark(
+        &mut self,
+        mark: &str,
+        handler: impl Fn(&str, &Context) -> String + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.handlers
+            .marks
+            .insert(mark.to_string(), Box::new(handler));
+        self
+    }
+
+    /// Serialize annotations of this `_type`, safe links without a handler become `[text](href)`
+    pub fn annotation(
+        &mut self,
+        type_name: &str,
+        handler: impl Fn(&MarkDef, &Context) -> String + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.handlers
+            .annotations
+            .insert(type_name.to_string(), Box::new(handler));
+        self
+    }
+
+    /// Serialize custom blocks and inline objects of this `_type`, which are left out otherwise
+    pub fn custom(
+        &mut self,
+        type_name: &str,
+        handler: impl Fn(&Value, &Context) -> String + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.handlers
+            .types
+            .insert(type_name.to_string(), Box::new(handler));
+        self
+    }
+
+    /// Serialize `image` blocks without a handler as `![alt](url)` on the CDN
+    pub fn images(&mut self, project_id: &str, dataset: &str) -> &mut Self {
+        self.handlers.images = Some((project_id.to_string(), dataset.to_string()));
+        self
+    }
+
+    pub fn render(&mut self) -> String {
+        let mut blocks = Vec::new();
+        for nested in nest_lists(&self.input) {
+            let markdown = match nested {
+                Nested::List(list) => list_markdown(&list, &self.handlers),
+                Nested::Block(Content::Block(node)) => block_markdown(node, &self.handlers),
+                Nested::Block(Content::Custom(value)) => custom_markdown(value, &self.handlers),
+            };
+            if !markdown.is_empty() {
+                blocks.push(markdown);
+            }
+        }
+        blocks.join("\n\n")
+    }
+}
+
+/// Escape the characters which could start Markdown syntax inside a line
+pub fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(
+            c,
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '&' | '~'
+        ) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Escape what would make a line a heading, a list item or a thematic break
+///
+/// The indentation of the first line is dropped, which would otherwise start a code block.
+fn escape_line_starts(text: &str) -> String {
+    text.split('\n')
+        .enumerate()
+        .map(|(index, line)| {
+            let content = line.trim_start_matches([' ', '\t']);
+            let indent = match index {
+                0 => "",
+                _ => &line[..line.len() - content.len()],
+            };
+            let digits = content.chars().take_while(char::is_ascii_digit).count();
+            if content.starts_with(['#', '+', '-', '=']) {
+                format!("{}\\{}", indent, content)
+            } else if digits > 0 && content[digits..].starts_with(['.', ')']) {
+                format!("{}{}\\{}", indent, &content[..digits], &content[digits..])
+            } else {
+                format!("{}{}", indent, content)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Put `delimiter` around the text, leaving surrounding spaces outside so it still applies
+fn delimit(delimiter: &str, children: &str) -> String {
+    let trimmed = children.trim_matches(' ');
+    if trimmed.is_empty() {
+        return children.to_string();
+    }
+    let start = children.len() - children.trim_start_matches(' ').len();
+    let end = children.trim_end_matches(' ').len();
+    format!(
+        "{}{}{}{}{}",
+        &children[..start],
+        delimiter,
+        trimmed,
+        delimiter,
+        &children[end..]
+    )
+}
+
+/// The longest run of `c` in the text
+fn longest_run(text: &str, c: char) -> usize {
+    text.split(|other| other != c)
+        .map(str::len)
+        .max()
+        .unwrap_or_default()
+}
+
+fn code_span(code: &str) -> String {
+    if code.is_empty() {
+        return String::new();
+    }
+    let fence = "`".repeat(longest_run(code, '`') + 1);
+    let padding = if code.starts_with('`') || code.ends_with('`') {
+        " "
+    } else {
+        ""
+    };
+    format!("{}{}{}{}{}", fence, padding, code, padding, fence)
+}
+
+/// A fenced code block of a `code` type with `code` and an optional `language`
+fn code_block(value: &Value) -> Option<String> {
+    let code = value.get("code")?.as_str()?;
+    // The info string ends at whitespace and may not hold backticks, such a language is dropped
+    let language = value
+        .get("language")
+        .and_then(Value::as_str)
+        .filter(|language| !language.contains(|c: char| c.is_whitespace() || c == '`'))
+        .unwrap_or_default();
+    let fence = "`".repeat(longest_run(code, '`').max(2) + 1);
+    Some(format!("{}{}\n{}\n{}", fence, language, code, fence))
+}
+
+/// A link destination which needs no angle brackets, whitespace and control characters are
+/// percent-encoded as well
+fn destination(href: &str) -> String {
+    let mut encoded = String::with_capacity(href.len());
+    for c in href.chars() {
+        if c.is_whitespace() || c.is_control() || matches!(c, '(' | ')' | '<' | '>') {
+            for byte in c.to_string().bytes() {
+                encoded.push_str(&format!("%{:02X}", byte));
+            }
+        } else {
+            encoded.push(c);
+        }
+    }
+    encoded
+}
+
+fn default_annotation(def: &MarkDef, children: &str) -> String {
+    match (
+        def._type.as_str(),
+        def.data.get("href").and_then(Value::as_str),
+    ) {
+        ("link", Some(href)) if is_safe_url(href) => {
+            format!("[{}]({})", children, destination(href))
+        }
+        _ => children.to_string(),
+    }
+}
+
+fn default_decorator(mark: &str, children: &str) -> String {
+    match mark {
+        "strong" => delimit("**", children),
+        "em" => delimit("*", children),
+        "code" => code_span(children),
+        _ => children.to_string(),
+    }
+}
+
+/// Serialize a marks tree, inside `code` the text is kept as it is
+fn marks_markdown(tree: &[MarkTree], handlers: &Handlers, code: bool) -> String {
+    let mut result = String::from("");
+    for node in tree {
+        match node {
+            MarkTree::Text(text) if code => result.push_str(text),
+            MarkTree::Text(text) => result.push_str(&escape_markdown(text)),
+            MarkTree::HardBreak if code => result.push(' '),
+            MarkTree::HardBreak => result.push_str("\\\n"),
+            MarkTree::Inline(value) => result.push_str(&custom_markdown(value, handlers)),
+            MarkTree::Mark {
+                def: Some(def),
+                children,
+                ..
+            } => result.push_str(&Handlers::call(
+                handlers.annotations.get(&def._type),
+                def,
+                &marks_markdown(children, handlers, code),
+                &|children| default_annotation(def, children),
+            )),
+            MarkTree::Mark {
+                key,
+                def: None,
+                children,
+            } => result.push_str(&Handlers::call(
+                handlers.marks.get(*key),
+                *key,
+                &marks_markdown(children, handlers, code || *key == "code"),
+                &|children| default_decorator(key, children),
+            )),
+        }
+    }
+    result
+}
+
+fn inline_markdown(node: &Node, handlers: &Handlers) -> String {
+    let tree = build_marks_tree(&node.children, &node.markDefs);
+    escape_line_starts(&marks_markdown(&tree, handlers, false))
+}
+
+fn prefix_lines(prefix: &str, text: &str) -> String {
+    text.split('\n')
+        .map(|line| format!("{}{}", prefix, line).trim_end().to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Markdown of a single block without handlers, `Render::html` is the HTML counterpart
+pub fn block_to_markdown(node: &Node) -> String {
+    block_markdown(node, &Handlers::default())
+}
+
+pub(crate) fn block_markdown(node: &Node, handlers: &Handlers) -> String {
+    let default = |children: &str| {
+        let level = match node.style {
+            Style::H1 => 1,
+            Style::H2 => 2,
+            Style::H3 => 3,
+            Style::H4 => 4,
+            Style::H5 => 5,
+            Style::H6 => 6,
+            Style::Blockquote => return prefix_lines("> ", children),
+            Style::Normal | Style::Custom(_) => return children.to_string(),
+        };
+        // Headings are a single line
+        format!("{} {}", "#".repeat(level), children.replace("\\\n", " "))
+    };
+    Handlers::call(
+        handlers.blocks.get(&node.style),
+        node,
+        &inline_markdown(node, handlers),
+        &default,
+    )
+}
+
+fn custom_markdown(value: &Value, handlers: &Handlers) -> String {
+    let type_name = value
+        .get("_type")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let builtin = match (&handlers.images, type_name) {
+        (Some((project_id, dataset)), "image") => ImageUrl::from_source(project_id, dataset, value)
+            .map(|url| {
+                let alt = value.get("alt").and_then(Value::as_str).unwrap_or_default();
+                format!("![{}]({})", escape_markdown(alt), destination(&url.url()))
+            }),
+        (_, "code") => code_block(value),
+        _ => None,
+    };
+    Handlers::call(handlers.types.get(type_name), value, "", &|_| {
+        builtin.clone().unwrap_or_default()
+    })
+}
+
+fn list_markdown(list: &List, handlers: &Handlers) -> String {
+    let mut lines = Vec::new();
+    for (index, item) in list.items.iter().enumerate() {
+        let marker = match list.kind {
+            "number" => format!("{}. ", index + 1),
+            _ => "- ".to_string(),
+        };
+        let indent = " ".repeat(marker.len());
+        let mut content = inline_markdown(item.node, handlers);
+        for nested in &item.lists {
+            content.push('\n');
+            content.push_str(&list_markdown(nested, handlers));
+        }
+        for (number, line) in content.split('\n').enumerate() {
+            let prefix = if number == 0 { &marker } else { &indent };
+            lines.push(format!("{}{}", prefix, line).trim_end().to_string());
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn serialize_document() {
+        let body: Vec<Content> = serde_json::from_value(json!([
+            {"_type": "block", "style": "h2", "children": [{"_type": "span", "text": "A *title*"}]},
+            {
+                "_type": "block",
+                "markDefs": [
+                    {"_type": "link", "_key": "l1", "href": "https://en.wikipedia.org/wiki/Rust_(language)"},
+                    {"_type": "link", "_key": "l2", "href": "javascript:alert(1)"}
+                ],
+                "children": [
+                    {"_type": "span", "text": "Some ", "marks": []},
+                    {"_type": "span", "text": "bold ", "marks": ["strong"]},
+                    {"_type": "span", "text": "and italic", "marks": ["strong", "em"]},
+                    {"_type": "span", "text": " text, "},
+                    {"_type": "span", "text": "a `tick`", "marks": ["code"]},
+                    {"_type": "span", "text": " and ", "marks": []},
+                    {"_type": "span", "text": "a link", "marks": ["l1"]},
+                    {"_type": "span", "text": " but no ", "marks": []},
+                    {"_type": "span", "text": "script", "marks": ["l2"]}
+                ]
+            },
+            {"_type": "block", "children": [{"_type": "span", "text": "# not a heading\n1. not a list"}]},
+            {"_type": "block", "style": "blockquote", "children": [{"_type": "span", "text": "Quoted\nlines"}]},
+            {"_type": "block", "listItem": "bullet", "children": [{"_type": "span", "text": "One"}]},
+            {"_type": "block", "listItem": "number", "level": 2, "children": [{"_type": "span", "text": "First"}]},
+            {"_type": "block", "listItem": "number", "level": 2, "children": [{"_type": "span", "text": "Second\nline"}]},
+            {"_type": "block", "listItem": "bullet", "children": [{"_type": "span", "text": "Two"}]},
+            {"_type": "code", "language": "md", "code": "```\nfenced\n```"},
+            {"_type": "code", "language": "js ```", "code": "alert(1)"},
+            {"_type": "callout", "text": "Careful"},
+            {"_type": "unknown"}
+        ]))
+        .unwrap();
+        let markdown = Markdown::new(body)
+            .custom("callout", |value, _| {
+                format!(
+                    "> **Note:** {}",
+                    escape_markdown(value["text"].as_str().unwrap_or_default())
+                )
+            })
+            .render();
+        assert_eq!(
+            markdown,
+            r#"## A \*title\*
+
+Some **bold *and italic*** text, `` a `tick` `` and [a link](https://en.wikipedia.org/wiki/Rust_%28language%29) but no script
+
+\# not a heading\
+1\. not a list
+
+> Quoted\
+> lines
+
+- One
+  1. First
+  2. Second\
+     line
+- Two
+
+````md
+```
+fenced
+```
+````
+
+```
+alert(1)
+```
+
+> **Note:** Careful"#
+        );
+    }
+
+    #[test]
+    fn escape_and_delimit() {
+        assert_eq!(
+            escape_markdown("a_b*c [d](e) <f> & `g`"),
+            r"a\_b\*c \[d\](e) \<f\> \& \`g\`"
+        );
+        assert_eq!(delimit("**", " bold "), " **bold** ");
+        assert_eq!(delimit("*", "  "), "  ");
+        assert_eq!(code_span("`"), "`` ` ``");
+        assert_eq!(
+            escape_line_starts("  - a\n12) b\n+ c\n=\n  - d\nplain"),
+            "\\- a\n12\\) b\n\\+ c\n\\=\n  \\- d\nplain"
+        );
+        assert_eq!(escape_line_starts("    code\n    more"), "code\n    more");
+        assert_eq!(escape_line_starts("\tcode"), "code");
+        assert_eq!(
+            destination("/a b\n(c)<d>\u{a0}"),
+            "/a%20b%0A%28c%29%3Cd%3E%C2%A0"
+        );
+    }
+}
@@ -0,0 +1,200 @@
+//! Nest the marks of consecutive spans like `buildMarksTree` of `@portabletext/toolkit`
+//!
+//! A mark shared by adjacent spans is opened once around all of them. Marks lasting longer
+//! are opened first, ties put annotations outside of decorators.
+use serde_json::Value;
+
+use super::blocks::{Children, MarkDef};
+
+/// Decorators in the order they are nested, outermost first
+pub const DECORATORS: [&str; 5] = ["strong", "em", "code", "underline", "strike-through"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MarkTree<'a> {
+    Text(&'a str),
+    /// A `\n` inside the text of a span
+    HardBreak,
+    /// An inline object
+    Inline(&'a Value),
+    /// A decorator or an annotation around its children, `def` is set for annotations
+    Mark {
+        key: &'a str,
+        def: Option<&'a MarkDef>,
+        children: Vec<MarkTree<'a>>,
+    },
+}
+
+/// Marks of the span at `index`, the ones shared with the most following spans first
+fn sorted_marks(children: &[Children], index: usize) -> Vec<&str> {
+    let Children::Text(span) = &children[index] else {
+        return Vec::new();
+    };
+    let occurrences = |mark: &str| {
+        1 + children[index + 1..]
+            .iter()
+            .take_while(|sibling| {
+                matches!(sibling, Children::Text(sibling) if sibling.marks.iter().any(|m| m == mark))
+            })
+            .count()
+    };
+    let position = |mark: &str| DECORATORS.iter().position(|known| *known == mark);
+    let mut marks: Vec<&str> = span.marks.iter().map(String::as_str).collect();
+    marks.sort_by(|a, b| {
+        occurrences(b)
+            .cmp(&occurrences(a))
+            .then_with(|| position(a).cmp(&position(b)))
+            .then_with(|| a.cmp(b))
+    });
+    marks
+}
+
+/// The children at `path`, each index picking a mark among the children of the previous one
+fn children_at<'t, 'a>(
+    tree: &'t mut Vec<MarkTree<'a>>,
+    path: &[usize],
+) -> &'t mut Vec<MarkTree<'a>> {
+    match path.split_first() {
+        Some((index, rest)) => match &mut tree[*index] {
+            MarkTree::Mark { children, .. } => children_at(children, rest),
+            _ => unreachable!("paths only lead through marks"),
+        },
+        None => tree,
+    }
+}
+
+/// Build the marks tree of the spans and inline objects of a block
+pub fn build_marks_tree<'a>(
+    children: &'a [Children],
+    mark_defs: &'a [MarkDef],
+) -> Vec<MarkTree<'a>> {
+    let mut tree = Vec::new();
+    // Keys of the open marks and where they are in the tree
+    let mut open: Vec<&str> = Vec::new();
+    let mut path: Vec<usize> = Vec::new();
+    for (index, child) in children.iter().enumerate() {
+        let mut needed = sorted_marks(children, index);
+        let kept = open
+            .iter()
+            .take_while(
+                |mark| match needed.iter().position(|needed| needed == *mark) {
+                    Some(position) => {
+                        needed.remove(position);
+                        true
+                    }
+                    None => false,
+                },
+            )
+            .count();
+        open.truncate(kept);
+        path.truncate(kept);
+
+        for key in needed {
+            let def = mark_defs.iter().find(|def| def._key == key);
+            let siblings = children_at(&mut tree, &path);
+            siblings.push(MarkTree::Mark {
+                key,
+                def,
+                children: Vec::new(),
+            });
+            path.push(siblings.len() - 1);
+            open.push(key);
+        }
+
+        let current = children_at(&mut tree, &path);
+        match child {
+            Children::Text(span) => {
+                for (line, text) in span.text.split('\n').enumerate() {
+                    if line > 0 {
+                        current.push(MarkTree::HardBreak);
+                    }
+                    if !text.is_empty() {
+                        current.push(MarkTree::Text(text));
+                    }
+                }
+            }
+            Children::Inline(value) => current.push(MarkTree::Inline(value)),
+        }
+    }
+    tree
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::portabletext::blocks::TextNode;
+
+    fn span(text: &str, marks: &[&str]) -> Children {
+        Children::Text(TextNode {
+            text: text.to_string(),
+            marks: marks.iter().map(|mark| mark.to_string()).collect(),
+            ..Default::default()
+        })
+    }
+
+    fn mark<'a>(key: &'a str, children: Vec<MarkTree<'a>>) -> MarkTree<'a> {
+        MarkTree::Mark {
+            key,
+            def: None,
+            children,
+        }
+    }
+
+    #[test]
+    fn shared_marks_open_once() {
+        let children = [
+            span("a", &["em"]),
+            span("b", &["strong", "em"]),
+            span("c", &["strong"]),
+            span("d", &[]),
+        ];
+        assert_eq!(
+            build_marks_tree(&children, &[]),
+            [
+                mark(
+                    "em",
+                    vec![
+                        MarkTree::Text("a"),
+                        mark("strong", vec![MarkTree::Text("b")])
+                    ]
+                ),
+                mark("strong", vec![MarkTree::Text("c")]),
+                MarkTree::Text("d")
+            ]
+        );
+
+        // The longest running mark is opened first, whatever order it is listed in
+        let children = [span("a", &["em", "strong"]), span("b", &["strong"])];
+        assert_eq!(
+            build_marks_tree(&children, &[]),
+            [mark(
+                "strong",
+                vec![mark("em", vec![MarkTree::Text("a")]), MarkTree::Text("b")]
+            )]
+        );
+    }
+
+    #[test]
+    fn annotations_and_breaks() {
+        let defs = [MarkDef {
+            _key: "l1".to_string(),
+            _type: "link".to_string(),
+            ..Default::default()
+        }];
+        let children = [span("one\ntwo", &["strong", "l1"])];
+        assert_eq!(
+            build_marks_tree(&children, &defs),
+            [MarkTree::Mark {
+                key: "l1",
+                def: Some(&defs[0]),
+                children: vec![mark(
+                    "strong",
+                    vec![
+                        MarkTree::Text("one"),
+                        MarkTree::HardBreak,
+                        MarkTree::Text("two")
+                    ]
+                )]
+            }]
+        );
+    }
+}
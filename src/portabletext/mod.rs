@@ -1,2 +1,8 @@
 pub mod blocks;
+pub mod handlers;
+pub mod html;
+pub mod lists;
+pub mod markdown;
+pub mod marks;
 pub mod renderer;
+pub mod text;
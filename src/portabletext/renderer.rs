@@ -1,64 +1,159 @@
-use std::collections::HashMap;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
 
-use super::blocks::{Block, Children, Node, Render, Style, TextNode};
+use super::blocks::{Content, MarkDef, Node, Style};
+use super::handlers::{Context, Handlers, UnknownType};
+use super::html::{block_html, custom_html, list_html};
+use super::lists::{nest_lists, List, ListItem, Nested};
 
 type Callback = fn(&Node) -> String;
 
 pub struct Renderer {
-    input: Vec<Node>,
-    config: HashMap<Style, Callback>,
-}
-
-fn default_callback(node: &Node) -> String {
-    let mut result = String::from("");
-    let tag = match &node.style {
-        Style::H1 => "h1",
-        Style::H2 => "h2",
-        Style::H3 => "h3",
-        Style::H4 => "h4",
-        Style::H5 => "h5",
-        Style::Normal => "p",
-        Style::Blockquote => "blockquote",
-    };
-    for child in &node.children {
-        match child {
-            Children::Text(text) => {
-                result.push_str(&format!("<{}>{}</{}>", tag, text.text, tag));
-            }
-            Children::Node(node) => {
-                result.push_str(&node.html());
-            }
-        }
-    }
-    result
+    input: Vec<Content>,
+    handlers: Handlers,
 }
 
 impl Renderer {
-    pub fn new(input: Vec<Node>) -> Self {
+    /// Render blocks or a whole Portable Text array, e.g. deserialized as `Vec<Content>`
+    pub fn new<T: Into<Content>>(input: impl IntoIterator<Item = T>) -> Self {
         Renderer {
-            input,
-            config: HashMap::new(),
+            input: input.into_iter().map(Into::into).collect(),
+            handlers: Handlers::default(),
         }
     }
 
     pub fn add(&mut self, style: Style, callback: Callback) -> &mut Self {
-        self.config.insert(style, callback);
+        self.block(style, move |node, _| callback(node))
+    }
+
+    /// Render blocks of this style with `handler`, e.g. to add a class to the default element
+    ///
+    /// Handlers are called with a `Context` holding the children rendered with the other
+    /// handlers, which are escaped already. Values a handler adds have to be escaped with
+    /// `escape_html`.
+    pub fn block(
+        &mut self,
+        style: Style,
+        handler: impl Fn(&Node, &Context) -> String + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.handlers.blocks.insert(style, Box::new(handler));
+        self
+    }
+
+    /// Render a decorator, e.g. `strong` or one defined in the schema
+    pub fn mark(
+        &mut self,
+        mark: &str,
+        handler: impl Fn(&str, &Context) -> String + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.handlers
+            .marks
+            .insert(mark.to_string(), Box::new(handler));
+        self
+    }
+
+    /// Render annotations of this `_type`. Links without a handler render as `<a href>` when
+    /// `is_safe_url`.
+    pub fn annotation(
+        &mut self,
+        type_name: &str,
+        handler: impl Fn(&MarkDef, &Context) -> String + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.handlers
+            .annotations
+            .insert(type_name.to_string(), Box::new(handler));
+        self
+    }
+
+    /// Render lists of this `listItem` type, the children are the rendered items. Lists of
+    /// unknown types without a handler render as `<ul>`.
+    pub fn list(
+        &mut self,
+        kind: &str,
+        handler: impl Fn(&List, &Context) -> String + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.handlers
+            .lists
+            .insert(kind.to_string(), Box::new(handler));
+        self
+    }
+
+    /// Render the items of lists of this `listItem` type, the children include nested lists
+    pub fn list_item(
+        &mut self,
+        kind: &str,
+        handler: impl Fn(&ListItem, &Context) -> String + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.handlers
+            .list_items
+            .insert(kind.to_string(), Box::new(handler));
+        self
+    }
+
+    /// Render custom blocks and inline objects of this `_type`, which are left out otherwise
+    pub fn custom(
+        &mut self,
+        type_name: &str,
+        handler: impl Fn(&Value, &Context) -> String + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.handlers
+            .types
+            .insert(type_name.to_string(), Box::new(handler));
+        self
+    }
+
+    /// Like `custom` with the value deserialized, values which do not deserialize are left out
+    pub fn custom_as<T: DeserializeOwned>(
+        &mut self,
+        type_name: &str,
+        handler: impl Fn(&T, &Context) -> String + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.custom(
+            type_name,
+            move |value, context| match serde_json::from_value::<T>(value.clone()) {
+                Ok(value) => handler(&value, context),
+                Err(_) => context.render_default(),
+            },
+        )
+    }
+
+    /// Render `image` blocks and inline images without a handler as `<img>` on the CDN,
+    /// cropped to their `crop` and `hotspot`
+    pub fn images(&mut self, project_id: &str, dataset: &str) -> &mut Self {
+        self.handlers.images = Some((project_id.to_string(), dataset.to_string()));
+        self
+    }
+
+    /// What to do with custom types without a handler, they are skipped by default
+    pub fn unknown_types(&mut self, unknown_types: UnknownType) -> &mut Self {
+        self.handlers.unknown_types = unknown_types;
+        self
+    }
+
+    /// Warnings of the last `render`, see `UnknownType::Warn`
+    pub fn warnings(&self) -> Vec<String> {
+        self.handlers.warnings.lock().unwrap().clone()
+    }
+
+    /// Render the line breaks inside spans, `<br/>` by default
+    pub fn hard_break(
+        &mut self,
+        handler: impl Fn(&Context) -> String + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.handlers.hard_break = Some(Box::new(handler));
         self
     }
 
     pub fn render(&mut self) -> String {
         let mut result = String::from("");
+        self.handlers.warnings.lock().unwrap().clear();
 
-        for node in &self.input {
-            let callback = self.config.get(&node.style);
-            let callback = match callback {
-                Some(callback) => callback,
-                None => {
-                    result.push_str(&default_callback(node));
-                    continue;
-                }
+        for nested in nest_lists(&self.input) {
+            let rendered = match nested {
+                Nested::List(list) => list_html(&list, &self.handlers),
+                Nested::Block(Content::Block(node)) => block_html(node, &self.handlers),
+                Nested::Block(Content::Custom(value)) => custom_html(value, &self.handlers),
             };
-            let rendered = callback(node);
             result.push_str(&rendered);
         }
         result
@@ -68,6 +163,9 @@ impl Renderer {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::portabletext::blocks::{Block, Children, Render, TextNode};
+    use crate::portabletext::html::escape_html;
+    use serde_json::json;
 
     #[test]
     fn render_headings() {
@@ -90,16 +188,18 @@ mod test {
             _type: Block::Block,
             children: vec![Children::Text(text)],
             markDefs: vec![],
+            ..Default::default()
         };
         let h1 = Node {
             _key: "key".to_string(),
             style: Style::H1,
             _type: Block::Block,
-            children: vec![Children::Text(text2), Children::Node(h2)],
+            children: vec![Children::Text(text2)],
             markDefs: vec![],
+            ..Default::default()
         };
 
-        let body = vec![h1];
+        let body = vec![h1, h2];
         let result = Renderer::new(body)
             .add(Style::H1, |node| node.html())
             .add(Style::Normal, |node| node.html())
@@ -129,6 +229,7 @@ mod test {
             _type: Block::Block,
             children: vec![Children::Text(text2)],
             markDefs: vec![],
+            ..Default::default()
         };
 
         let paragraph = Node {
@@ -137,6 +238,7 @@ mod test {
             _type: Block::Block,
             children: vec![Children::Text(text)],
             markDefs: vec![],
+            ..Default::default()
         };
 
         let body = vec![paragraph, blockquote];
@@ -169,6 +271,7 @@ mod test {
             _type: Block::Block,
             children: vec![Children::Text(text2)],
             markDefs: vec![],
+            ..Default::default()
         };
 
         let paragraph = Node {
@@ -177,6 +280,7 @@ mod test {
             _type: Block::Block,
             children: vec![Children::Text(text)],
             markDefs: vec![],
+            ..Default::default()
         };
 
         let body = vec![paragraph, blockquote];
@@ -190,4 +294,223 @@ mod test {
             result
         );
     }
+
+    #[test]
+    fn render_deserialized_body() {
+        let body: Vec<Content> = serde_json::from_value(json!([
+            {"_type": "block", "style": "h1", "children": [{"_type": "span", "text": "Title"}]},
+            {"_type": "image", "asset": {"_ref": "image-abc-10x20-png"}},
+            {"_type": "block", "children": [{"_type": "span", "text": "Body", "marks": []}]}
+        ]))
+        .unwrap();
+        assert_eq!(Renderer::new(body).render(), "<h1>Title</h1><p>Body</p>");
+    }
+
+    #[test]
+    fn render_custom_annotations() {
+        let body: Vec<Content> = serde_json::from_value(json!([{
+            "_type": "block",
+            "markDefs": [
+                {"_type": "internalLink", "_key": "i1", "reference": {"_ref": "post-1"}},
+                {"_type": "link", "_key": "l1", "href": "/about"}
+            ],
+            "children": [
+                {"_type": "span", "text": "Post", "marks": ["i1"]},
+                {"_type": "span", "text": " and "},
+                {"_type": "span", "text": "about", "marks": ["l1"]}
+            ]
+        }]))
+        .unwrap();
+        let result = Renderer::new(body)
+            .annotation("internalLink", |def, context| {
+                let id = def.data["reference"]["_ref"].as_str().unwrap_or_default();
+                format!("<a href=\"/posts/{}\">{}</a>", id, context.children())
+            })
+            .render();
+        assert_eq!(
+            result,
+            "<p><a href=\"/posts/post-1\">Post</a> and <a href=\"/about\">about</a></p>"
+        );
+    }
+
+    #[test]
+    fn render_lists() {
+        let body: Vec<Content> = serde_json::from_value(json!([
+            {"_type": "block", "listItem": "bullet", "children": [{"text": "One"}]},
+            {"_type": "block", "listItem": "number", "level": 2, "children": [{"text": "One.1"}]},
+            {"_type": "block", "listItem": "number", "level": 2, "children": [{"text": "One.2", "marks": ["em"]}]},
+            {"_type": "block", "listItem": "bullet", "level": 1, "style": "h3", "children": [{"text": "Two"}]},
+            {"_type": "block", "listItem": "number", "children": [{"text": "First"}]},
+            {"_type": "block", "children": [{"text": "After"}]},
+            {"_type": "block", "listItem": "check", "children": [{"text": "Done"}]},
+            {"_type": "block", "listItem": "check", "children": [{"text": "Todo"}]}
+        ]))
+        .unwrap();
+        assert_eq!(
+            Renderer::new(body.clone()).render(),
+            "<ul><li>One<ol><li>One.1</li><li><em>One.2</em></li></ol></li><li><h3>Two</h3></li></ul>\
+             <ol><li>First</li></ol><p>After</p><ul><li>Done</li><li>Todo</li></ul>"
+        );
+        let result = Renderer::new(body)
+            .list("check", |list, context| {
+                format!(
+                    "<ul class=\"check level-{}\">{}</ul>",
+                    list.level,
+                    context.children()
+                )
+            })
+            .render();
+        assert!(result.ends_with("<ul class=\"check level-1\"><li>Done</li><li>Todo</li></ul>"));
+    }
+
+    #[test]
+    fn render_list_item_styles() {
+        let body: Vec<Content> = serde_json::from_value(json!([
+            {"_type": "block", "listItem": "bullet", "style": "h3", "children": [{"text": "Title"}]},
+            {"_type": "block", "listItem": "bullet", "style": "lead", "children": [{"text": "Lead"}]}
+        ]))
+        .unwrap();
+        assert_eq!(
+            Renderer::new(body.clone()).render(),
+            "<ul><li><h3>Title</h3></li><li>Lead</li></ul>"
+        );
+        let result = Renderer::new(body)
+            .block(Style::H3, |_, context| {
+                format!("<h3 class=\"title\">{}</h3>", context.children())
+            })
+            .block(Style::Custom("lead".to_string()), |_, context| {
+                format!("<span class=\"lead\">{}</span>", context.children())
+            })
+            .render();
+        assert_eq!(
+            result,
+            "<ul><li><h3 class=\"title\">Title</h3></li><li><span class=\"lead\">Lead</span></li></ul>"
+        );
+    }
+
+    #[test]
+    fn render_with_handlers() {
+        let body: Vec<Content> = serde_json::from_value(json!([
+            {
+                "_type": "block",
+                "style": "h2",
+                "markDefs": [{"_type": "link", "_key": "l1", "href": "/a"}],
+                "children": [
+                    {"_type": "span", "text": "Big", "marks": ["strong", "l1"]},
+                    {"_type": "span", "text": " <deal>\nnow", "marks": ["highlight"]},
+                    {"_type": "emoji", "name": "tada"}
+                ]
+            },
+            {"_type": "block", "listItem": "bullet", "children": [{"_type": "span", "text": "Item"}]},
+            {"_type": "divider"}
+        ]))
+        .unwrap();
+        let prefix = "pt".to_string();
+        let result = Renderer::new(body)
+            .block(Style::H2, move |node, context| {
+                let id = format!("{}-{}", prefix, node.style.tag());
+                format!("<h2 id=\"{}\">{}</h2>", id, context.children())
+            })
+            .mark("strong", |_, context| {
+                context.render_default_with(&format!("*{}*", context.children()))
+            })
+            .mark("highlight", |mark, context| {
+                format!("<mark class=\"{}\">{}</mark>", mark, context.children())
+            })
+            .annotation("link", |_, context| {
+                context.render_default().replace("<a ", "<a rel=\"next\" ")
+            })
+            .list_item("bullet", |_, context| {
+                context
+                    .render_default()
+                    .replace("<li>", "<li class=\"bullet\">")
+            })
+            .list("bullet", |_, context| {
+                format!("<ul class=\"plain\">{}</ul>", context.children())
+            })
+            .custom("emoji", |value, _| {
+                format!(":{}:", value["name"].as_str().unwrap_or_default())
+            })
+            .custom("divider", |_, _| "<hr/>".to_string())
+            .hard_break(|_| "<br>".to_string())
+            .render();
+        assert_eq!(
+            result,
+            "<h2 id=\"pt-h2\"><a rel=\"next\" href=\"/a\"><strong>*Big*</strong></a>\
+             <mark class=\"highlight\"> &lt;deal&gt;<br>now</mark>:tada:</h2>\
+             <ul class=\"plain\"><li class=\"bullet\">Item</li></ul><hr/>"
+        );
+    }
+
+    #[test]
+    fn render_custom_types() {
+        #[derive(serde::Deserialize)]
+        struct Code {
+            language: String,
+            code: String,
+        }
+
+        let body: Vec<Content> = serde_json::from_value(json!([
+            {
+                "_type": "image",
+                "alt": "A \"cat\"",
+                "asset": {"_ref": "image-abc-10x20-png"},
+                "crop": {"top": 0.0, "bottom": 0.5, "left": 0.0, "right": 0.0},
+                "hotspot": {"x": 0.5, "y": 0.25, "width": 0.5, "height": 0.5}
+            },
+            {"_type": "code", "language": "rust", "code": "a < b"},
+            {"_type": "code", "language": 1},
+            {"_type": "block", "children": [{"_type": "span", "text": "Hi"}, {"_type": "mention"}]},
+            {"_type": "embed", "url": "https://example.com"}
+        ]))
+        .unwrap();
+        let mut renderer = Renderer::new(body);
+        renderer
+            .images("project", "production")
+            .unknown_types(UnknownType::Warn)
+            .custom_as("code", |code: &Code, _| {
+                format!(
+                    "<pre data-language=\"{}\">{}</pre>",
+                    escape_html(&code.language),
+                    escape_html(&code.code)
+                )
+            });
+        assert_eq!(
+            renderer.render(),
+            "<img src=\"https://cdn.sanity.io/images/project/production/abc-10x20.png?rect=0%2C0%2C10%2C10&amp;crop=focalpoint&amp;fp-x=0.5&amp;fp-y=0.5\" alt=\"A &quot;cat&quot;\"/>\
+             <pre data-language=\"rust\">a &lt; b</pre><p>Hi</p>"
+        );
+        assert_eq!(
+            renderer.warnings(),
+            [
+                "no handler for the type `mention`",
+                "no handler for the type `embed`"
+            ]
+        );
+
+        // Registered handlers win over the built-in image handler
+        let rendered = renderer
+            .custom("image", |_, context| {
+                format!("<figure>{}</figure>", context.render_default())
+            })
+            .render();
+        assert!(rendered.starts_with("<figure><img src="));
+    }
+
+    #[test]
+    fn render_on_other_threads() {
+        fn shared<T: Send + Sync>(_: &T) {}
+
+        let body: Vec<Content> =
+            serde_json::from_value(json!([{"_type": "block", "children": [{"text": "Hi"}]}]))
+                .unwrap();
+        shared(&crate::portabletext::markdown::Markdown::new(body.clone()));
+        let mut renderer = Renderer::new(body);
+        renderer.block(Style::Normal, |_, context| context.render_default());
+        shared(&renderer);
+        let rendered = std::thread::spawn(move || renderer.render())
+            .join()
+            .unwrap();
+        assert_eq!(rendered, "<p>Hi</p>");
+    }
 }
@@ -0,0 +1,196 @@
+//! Plain text of Portable Text, for meta descriptions, search snippets and feeds
+//!
+//! ```
+//! use sanity_rs::portabletext::blocks::Content;
+//! use sanity_rs::portabletext::text::{excerpt, to_plain_text, word_count};
+//! use serde_json::json;
+//!
+//! let body: Vec<Content> = serde_json::from_value(json!([
+//!     {"_type": "block", "style": "h1", "children": [{"_type": "span", "text": "Title"}]},
+//!     {"_type": "block", "children": [
+//!         {"_type": "span", "text": "Portable Text is "},
+//!         {"_type": "span", "text": "structured", "marks": ["em"]},
+//!         {"_type": "span", "text": " rich text."}
+//!     ]}
+//! ]))
+//! .unwrap();
+//! assert_eq!(to_plain_text(&body), "Title\n\nPortable Text is structured rich text.");
+//! assert_eq!(excerpt(&body, 24), "Title Portable Text is…");
+//! assert_eq!(word_count(&body), 7);
+//! ```
+use std::time::Duration;
+
+use super::blocks::{Children, Content, Node};
+use super::lists::{nest_lists, List, Nested};
+
+/// Reading speed used by `reading_time`
+pub const WORDS_PER_MINUTE: usize = 200;
+
+/// The text of the spans of a block, inline objects are left out
+pub fn block_text(node: &Node) -> String {
+    node.children
+        .iter()
+        .filter_map(|child| match child {
+            Children::Text(span) => Some(span.text.as_str()),
+            Children::Inline(_) => None,
+        })
+        .collect()
+}
+
+fn list_text(list: &List, depth: usize, lines: &mut Vec<String>) {
+    for (index, item) in list.items.iter().enumerate() {
+        let bullet = match list.kind {
+            "number" => format!("{}. ", index + 1),
+            _ => "- ".to_string(),
+        };
+        let indent = "  ".repeat(depth);
+        let text = block_text(item.node);
+        // Lines after the first line up with the text of the item
+        let continuation = format!("\n{}{}", indent, " ".repeat(bullet.len()));
+        lines.push(format!(
+            "{}{}{}",
+            indent,
+            bullet,
+            text.replace('\n', &continuation)
+        ));
+        for nested in &item.lists {
+            list_text(nested, depth + 1, lines);
+        }
+    }
+}
+
+/// The text of the blocks separated by blank lines, with list items on their own lines
+///
+/// Custom blocks such as images are left out.
+pub fn to_plain_text(input: &[Content]) -> String {
+    let mut blocks = Vec::new();
+    for nested in nest_lists(input) {
+        match nested {
+            Nested::List(list) => {
+                let mut lines = Vec::new();
+                list_text(&list, 0, &mut lines);
+                blocks.push(lines.join("\n"));
+            }
+            Nested::Block(Content::Block(node)) => blocks.push(block_text(node)),
+            Nested::Block(Content::Custom(_)) => {}
+        }
+    }
+    blocks.retain(|block| !block.trim().is_empty());
+    blocks.join("\n\n")
+}
+
+fn words(input: &[Content]) -> impl Iterator<Item = String> + '_ {
+    input
+        .iter()
+        .filter_map(|content| match content {
+            Content::Block(node) => Some(block_text(node)),
+            Content::Custom(_) => None,
+        })
+        .flat_map(|text| {
+            text.split_whitespace()
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        })
+}
+
+/// The start of the text on a single line, cut between words to at most `max_chars`
+/// characters including the `…` added when it is cut
+pub fn excerpt(input: &[Content], max_chars: usize) -> String {
+    let mut result = String::new();
+    if max_chars == 0 {
+        return result;
+    }
+    let mut length = 0;
+    for word in words(input) {
+        let word_length = word.chars().count();
+        let separator = usize::from(length > 0);
+        if length + separator + word_length > max_chars {
+            // Make room for the ellipsis
+            while length + 1 > max_chars {
+                match result.rfind(' ') {
+                    Some(end) => result.truncate(end),
+                    None => result.clear(),
+                }
+                length = result.chars().count();
+            }
+            result.push('…');
+            return result;
+        }
+        if separator > 0 {
+            result.push(' ');
+        }
+        result.push_str(&word);
+        length += separator + word_length;
+    }
+    result
+}
+
+/// Words of the text blocks, separated by whitespace
+pub fn word_count(input: &[Content]) -> usize {
+    words(input).count()
+}
+
+/// Time it takes to read the text at `WORDS_PER_MINUTE`, rounded up to whole seconds
+pub fn reading_time(input: &[Content]) -> Duration {
+    let words = word_count(input) as u64;
+    Duration::from_secs((words * 60).div_ceil(WORDS_PER_MINUTE as u64))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn body() -> Vec<Content> {
+        serde_json::from_value(json!([
+            {"_type": "block", "style": "h2", "children": [{"_type": "span", "text": "Shopping"}]},
+            {"_type": "image", "asset": {"_ref": "image-abc-10x20-png"}},
+            {"_type": "block", "listItem": "bullet", "children": [
+                {"_type": "span", "text": "Fresh "},
+                {"_type": "span", "text": "apples", "marks": ["strong"]},
+                {"_type": "emoji", "name": "apple"}
+            ]},
+            {"_type": "block", "listItem": "number", "level": 2, "children": [{"_type": "span", "text": "Granny\nSmith"}]},
+            {"_type": "block", "listItem": "number", "level": 2, "children": [{"_type": "span", "text": "Gala"}]},
+            {"_type": "block", "listItem": "bullet", "children": [{"_type": "span", "text": "Pears"}]},
+            {"_type": "block", "children": []},
+            {"_type": "block", "children": [{"_type": "span", "text": "Süße Grüße  from   the market."}]}
+        ]))
+        .unwrap()
+    }
+
+    #[test]
+    fn plain_text() {
+        assert_eq!(
+            to_plain_text(&body()),
+            "Shopping\n\n- Fresh apples\n  1. Granny\n     Smith\n  2. Gala\n- Pears\n\n\
+             Süße Grüße  from   the market."
+        );
+    }
+
+    #[test]
+    fn excerpts() {
+        let body = body();
+        assert_eq!(
+            excerpt(&body, 200),
+            "Shopping Fresh apples Granny Smith Gala Pears Süße Grüße from the market."
+        );
+        assert_eq!(excerpt(&body, 22), "Shopping Fresh apples…");
+        assert_eq!(excerpt(&body, 21), "Shopping Fresh…");
+        assert_eq!(excerpt(&body, 20), "Shopping Fresh…");
+        assert_eq!(excerpt(&body, 15), "Shopping Fresh…");
+        assert_eq!(excerpt(&body, 14), "Shopping…");
+        assert_eq!(excerpt(&body, 4), "…");
+        assert_eq!(excerpt(&body, 0), "");
+        // Counted in characters, not bytes
+        assert_eq!(excerpt(&body[7..], 11), "Süße Grüße…");
+    }
+
+    #[test]
+    fn reading_estimates() {
+        let body = body();
+        assert_eq!(word_count(&body), 12);
+        assert_eq!(reading_time(&body), Duration::from_secs(4));
+        assert_eq!(reading_time(&[]), Duration::ZERO);
+    }
+}
@@ -0,0 +1,334 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::mock::normalize;
+use super::{BoxFuture, Request, Response, Transport};
+use crate::error::RequestError;
+
+/// Headers which are never written to a cassette
+const SCRUBBED_HEADERS: &[&str] = &["authorization", "cookie", "set-cookie"];
+
+/// How a request is matched against the recorded ones when replaying
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Matching {
+    /// Same method, path, query text and parameters
+    Strict,
+    /// Same method, path and query once parsed, whitespace and parameters are ignored
+    Lenient,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Send requests with the inner transport and save them to the cassette
+    Record,
+    /// Answer requests from the cassette without a network
+    Replay,
+}
+
+/// A recorded request and its response
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    /// URL path with the dataset replaced by `:dataset`, the host is left out so cassettes
+    /// work for any project and dataset
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub params: Map<String, Value>,
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub headers: Map<String, Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    /// JSON bodies are stored as JSON to keep cassettes readable, anything else as a string
+    pub body: Value,
+}
+
+/// The interactions saved in a cassette file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+impl RecordedRequest {
+    fn new(request: &Request) -> Self {
+        let (query, params) = match request.groq() {
+            Some((query, params)) => (Some(query), params),
+            None => (None, Map::new()),
+        };
+        let headers = request
+            .headers
+            .iter()
+            .filter(|(name, _)| !SCRUBBED_HEADERS.contains(&name.to_lowercase().as_str()))
+            .map(|(name, value)| (name.clone(), Value::String(value.clone())))
+            .collect();
+        Self {
+            method: request.method.clone(),
+            path: without_dataset(request.url.path()),
+            query,
+            params,
+            headers,
+            body: request
+                .body
+                .as_ref()
+                .map(|body| String::from_utf8_lossy(body).into_owned()),
+        }
+    }
+
+    fn matches(&self, other: &RecordedRequest, matching: Matching) -> bool {
+        if self.method != other.method
+            || without_dataset(&self.path) != without_dataset(&other.path)
+            || self.body != other.body
+        {
+            return false;
+        }
+        match matching {
+            Matching::Strict => self.query == other.query && self.params == other.params,
+            Matching::Lenient => {
+                self.query.as_deref().map(normalize) == other.query.as_deref().map(normalize)
+            }
+        }
+    }
+}
+
+/// Replace the dataset in paths such as `/v2022-03-07/data/query/production`
+fn without_dataset(path: &str) -> String {
+    let mut segments: Vec<&str> = path.split('/').collect();
+    if segments.len() > 4 && segments[2] == "data" {
+        segments[4] = ":dataset";
+    }
+    segments.join("/")
+}
+
+impl From<&Response> for RecordedResponse {
+    fn from(response: &Response) -> Self {
+        let body = match serde_json::from_str(&response.body) {
+            Ok(json @ (Value::Object(_) | Value::Array(_))) => json,
+            _ => Value::String(response.body.clone()),
+        };
+        Self {
+            status: response.status,
+            body,
+        }
+    }
+}
+
+impl From<&RecordedResponse> for Response {
+    fn from(response: &RecordedResponse) -> Self {
+        let body = match &response.body {
+            Value::String(body) => body.clone(),
+            json => json.to_string(),
+        };
+        Self {
+            status: response.status,
+            body,
+        }
+    }
+}
+
+/// Records real requests to a JSON cassette once and replays them offline afterwards
+///
+/// Authorization and cookie headers are scrubbed before anything is written.
+pub struct CassetteTransport {
+    path: PathBuf,
+    mode: Mode,
+    matching: Matching,
+    inner: Option<Arc<dyn Transport>>,
+    cassette: Mutex<Cassette>,
+}
+
+impl CassetteTransport {
+    /// Send requests with `inner` and save every interaction to `path`
+    pub fn record(path: impl AsRef<Path>, inner: Arc<dyn Transport>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            mode: Mode::Record,
+            matching: Matching::Strict,
+            inner: Some(inner),
+            cassette: Mutex::new(Cassette::default()),
+        }
+    }
+
+    /// Answer requests from the cassette at `path`
+    pub fn replay(path: impl AsRef<Path>) -> Result<Self, RequestError> {
+        let file = std::fs::read_to_string(path.as_ref()).map_err(|e| {
+            RequestError::TransportError(format!(
+                "cannot read cassette {}: {}",
+                path.as_ref().display(),
+                e
+            ))
+        })?;
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            mode: Mode::Replay,
+            matching: Matching::Strict,
+            inner: None,
+            cassette: Mutex::new(serde_json::from_str(&file)?),
+        })
+    }
+
+    pub fn matching(mut self, matching: Matching) -> Self {
+        self.matching = matching;
+        self
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// The interactions recorded or loaded so far
+    pub fn cassette(&self) -> Cassette {
+        self.cassette.lock().unwrap().clone()
+    }
+
+    fn save(&self, cassette: &Cassette) -> Result<(), RequestError> {
+        let write = || -> std::io::Result<()> {
+            if let Some(parent) = self.path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let json = serde_json::to_string_pretty(cassette).map_err(std::io::Error::other)?;
+            std::fs::write(&self.path, json + "\n")
+        };
+        write().map_err(|e| {
+            RequestError::TransportError(format!(
+                "cannot write cassette {}: {}",
+                self.path.display(),
+                e
+            ))
+        })
+    }
+
+    fn replayed(&self, request: &RecordedRequest) -> Result<Response, RequestError> {
+        let cassette = self.cassette.lock().unwrap();
+        cassette
+            .interactions
+            .iter()
+            .find(|interaction| interaction.request.matches(request, self.matching))
+            .map(|interaction| Response::from(&interaction.response))
+            .ok_or_else(|| {
+                RequestError::TransportError(format!(
+                    "no interaction in {} matches {} {} {}",
+                    self.path.display(),
+                    request.method,
+                    request.path,
+                    request.query.as_deref().unwrap_or_default()
+                ))
+            })
+    }
+}
+
+impl Transport for CassetteTransport {
+    fn send(&self, request: Request) -> BoxFuture<'_, Result<Response, RequestError>> {
+        Box::pin(async move {
+            let recorded = RecordedRequest::new(&request);
+            match (&self.mode, &self.inner) {
+                (Mode::Record, Some(inner)) => {
+                    let response = inner.send(request).await?;
+                    let cassette = {
+                        let mut cassette = self.cassette.lock().unwrap();
+                        cassette.interactions.push(Interaction {
+                            request: recorded,
+                            response: RecordedResponse::from(&response),
+                        });
+                        cassette.clone()
+                    };
+                    self.save(&cassette)?;
+                    Ok(response)
+                }
+                _ => self.replayed(&recorded),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::transport::MockTransport;
+    use serde_json::json;
+    use url::Url;
+
+    fn request(query: &str, params: &[(&str, &str)]) -> Request {
+        let mut url =
+            Url::parse("https://abc123.api.sanity.io/v2022-03-07/data/query/production").unwrap();
+        {
+            let mut pairs = url.query_pairs_mut();
+            pairs.append_pair("query", query);
+            for (name, value) in params {
+                pairs.append_pair(&format!("${}", name), value);
+            }
+        }
+        Request::get(url).header("Authorization", "Bearer secret")
+    }
+
+    #[tokio::test]
+    async fn record_then_replay() -> Result<(), RequestError> {
+        let path =
+            std::env::temp_dir().join(format!("sanity-rs-cassette-{}.json", std::process::id()));
+        let mock = Arc::new(MockTransport::new().respond_with_params(
+            "*[_id == $id]",
+            [("id".to_string(), json!("a"))].into_iter().collect(),
+            json!([{"_id": "a"}]),
+        ));
+        let recorder = CassetteTransport::record(&path, mock);
+        let recorded = recorder
+            .send(request("*[_id == $id]", &[("id", "\"a\"")]))
+            .await?;
+
+        let file = std::fs::read_to_string(&path).unwrap();
+        assert!(!file.contains("secret"));
+
+        let replayer = CassetteTransport::replay(&path)?;
+        let replayed = replayer
+            .send(request("*[_id == $id]", &[("id", "\"a\"")]))
+            .await?;
+        assert_eq!(replayed, recorded);
+        assert_eq!(
+            serde_json::from_str::<Value>(&replayed.body).unwrap()["result"],
+            json!([{"_id": "a"}])
+        );
+
+        // Strict matching needs the same query text and parameters
+        assert!(replayer
+            .send(request("*[ _id == $id ]", &[("id", "\"a\"")]))
+            .await
+            .is_err());
+        assert!(replayer
+            .send(request("*[_id == $id]", &[("id", "\"b\"")]))
+            .await
+            .is_err());
+
+        let lenient = CassetteTransport::replay(&path)?.matching(Matching::Lenient);
+        assert!(lenient
+            .send(request("*[ _id == $id ]", &[("id", "\"b\"")]))
+            .await
+            .is_ok());
+        assert!(lenient
+            .send(request("*[_id == $other]", &[]))
+            .await
+            .is_err());
+
+        // Recorded against one dataset, replayed against another
+        assert!(file.contains("/v2022-03-07/data/query/:dataset"));
+        let mut other = request("*[_id == $id]", &[("id", "\"a\"")]);
+        other.url.set_path("/v2022-03-07/data/query/staging");
+        assert_eq!(replayer.send(other).await?, recorded);
+
+        std::fs::remove_file(&path).unwrap();
+        Ok(())
+    }
+}
@@ -0,0 +1,202 @@
+use std::sync::Mutex;
+
+use serde_json::{json, Map, Value};
+
+use super::{BoxFuture, BufferedBody, Request, Response, StreamingResponse, Transport};
+use crate::error::RequestError;
+use crate::groq::eval::Evaluator;
+use crate::groq::parse;
+
+/// A transport for tests that answers queries without a network
+///
+/// Routes answer requests by method and path, whatever the host. Canned responses are
+/// matched on the query and its parameters, queries are compared after parsing so
+/// whitespace does not matter. Anything else is evaluated against the fixture dataset, if
+/// one is set. Every request is recorded.
+///
+/// ```
+/// use std::sync::Arc;
+/// use sanity_rs::client::SanityClient;
+/// use sanity_rs::config::SanityConfig;
+/// use sanity_rs::orm::ORM;
+/// use sanity_rs::transport::MockTransport;
+/// use serde_json::{json, Value};
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let mock = Arc::new(MockTransport::new().respond(r#"count(*[_type == "post"])"#, json!(3)));
+/// let config = SanityConfig::new("project".to_string(), "production".to_string());
+/// let mut client = SanityClient::with_transport(config, mock.clone()).unwrap();
+/// let response: Value = client.query(r#"count(*[_type == "post"])"#).await.unwrap().json().unwrap();
+/// assert_eq!(response["result"], json!(3));
+/// assert_eq!(mock.requests().len(), 1);
+/// # }
+/// ```
+#[derive(Default)]
+pub struct MockTransport {
+    routes: Vec<(String, String, u16, Vec<u8>)>,
+    responses: Vec<(String, Map<String, Value>, Value)>,
+    documents: Option<Vec<Value>>,
+    requests: Mutex<Vec<Request>>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer requests to `path` on any host, e.g. `/v2022-03-07/data/export/production`
+    pub fn route(self, method: &str, path: &str, body: impl Into<Vec<u8>>) -> Self {
+        self.route_with_status(method, path, 200, body)
+    }
+
+    pub fn route_with_status(
+        mut self,
+        method: &str,
+        path: &str,
+        status: u16,
+        body: impl Into<Vec<u8>>,
+    ) -> Self {
+        self.routes
+            .push((method.to_string(), path.to_string(), status, body.into()));
+        self
+    }
+
+    /// Answer `query` without parameters with `result`
+    pub fn respond(self, query: &str, result: Value) -> Self {
+        self.respond_with_params(query, Map::new(), result)
+    }
+
+    /// Answer `query` with exactly these parameters with `result`
+    pub fn respond_with_params(
+        mut self,
+        query: &str,
+        params: Map<String, Value>,
+        result: Value,
+    ) -> Self {
+        self.responses.push((normalize(query), params, result));
+        self
+    }
+
+    /// Evaluate unmatched queries against these documents
+    pub fn dataset(mut self, documents: Vec<Value>) -> Self {
+        self.documents = Some(documents);
+        self
+    }
+
+    /// Evaluate unmatched queries against an NDJSON export, one document per line
+    pub fn ndjson(self, ndjson: &str) -> Result<Self, RequestError> {
+        let documents = ndjson
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<Value>, _>>()?;
+        Ok(self.dataset(documents))
+    }
+
+    /// The requests received so far
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    fn routed(&self, request: &Request) -> Option<(u16, Vec<u8>)> {
+        self.routes
+            .iter()
+            .find(|(method, path, _, _)| *method == request.method && path == request.url.path())
+            .map(|(_, _, status, body)| (*status, body.clone()))
+    }
+
+    fn answer(&self, request: &Request) -> Result<Response, RequestError> {
+        if let Some((status, body)) = self.routed(request) {
+            return Ok(Response {
+                status,
+                body: String::from_utf8_lossy(&body).into_owned(),
+            });
+        }
+        let (query, params) = request.groq().ok_or_else(|| {
+            RequestError::TransportError(format!("no mock response for {}", request.url))
+        })?;
+        let normalized = normalize(&query);
+        let canned = self
+            .responses
+            .iter()
+            .find(|(q, p, _)| *q == normalized && *p == params)
+            .map(|(_, _, result)| result.clone());
+        let result = match (canned, &self.documents) {
+            (Some(result), _) => result,
+            (None, Some(documents)) => {
+                match Evaluator::new(documents).params(&params).query(&query) {
+                    Ok(result) => result,
+                    Err(e) => {
+                        return Ok(Response {
+                            status: 400,
+                            body: json!({
+                                "error": {"description": e.to_string(), "query": query}
+                            })
+                            .to_string(),
+                        })
+                    }
+                }
+            }
+            (None, None) => {
+                return Err(RequestError::TransportError(format!(
+                    "no mock response for query `{}` with params {}",
+                    query,
+                    Value::Object(params)
+                )))
+            }
+        };
+        Ok(Response {
+            status: 200,
+            body: json!({"query": query, "result": result, "syncTags": [], "ms": 0}).to_string(),
+        })
+    }
+}
+
+impl Transport for MockTransport {
+    fn send(&self, request: Request) -> BoxFuture<'_, Result<Response, RequestError>> {
+        let response = self.answer(&request);
+        self.requests.lock().unwrap().push(request);
+        Box::pin(async move { response })
+    }
+
+    /// Routed bodies are served as is in small chunks, like a network would
+    fn stream(&self, request: Request) -> BoxFuture<'_, Result<StreamingResponse, RequestError>> {
+        let response = match self.routed(&request) {
+            Some((status, body)) => Ok(StreamingResponse {
+                status,
+                body: Box::new(BufferedBody::new(body, 64)),
+            }),
+            None => self.answer(&request).map(|response| StreamingResponse {
+                status: response.status,
+                body: Box::new(BufferedBody::new(response.body.into_bytes(), 64)),
+            }),
+        };
+        self.requests.lock().unwrap().push(request);
+        Box::pin(async move { response })
+    }
+}
+
+/// Queries that parse compare by their compact rendering
+pub(crate) fn normalize(query: &str) -> String {
+    match parse(query) {
+        Ok(expr) => expr.to_string(),
+        Err(_) => query.trim().to_string(),
+    }
+}
+
+/// The configuration of the clients used in tests: dataset `production` of `project`, with
+/// the token `token`
+#[cfg(test)]
+pub(crate) fn test_config() -> crate::config::SanityConfig {
+    let mut config =
+        crate::config::SanityConfig::new("project".to_string(), "production".to_string());
+    config.token = Some("token".to_string());
+    config
+}
+
+/// A client for tests which sends its requests to `mock`
+#[cfg(test)]
+pub(crate) fn test_client(mock: &std::sync::Arc<MockTransport>) -> crate::client::SanityClient {
+    crate::client::SanityClient::with_transport(test_config(), mock.clone()).unwrap()
+}
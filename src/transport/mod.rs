@@ -0,0 +1,200 @@
+//! How `SanityClient` talks to the API
+//!
+//! `ReqwestTransport` sends requests over HTTP. Tests can swap in a `MockTransport` or a
+//! `CassetteTransport` replaying recorded responses so they run offline.
+pub mod cassette;
+pub mod mock;
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+
+use reqwest::Client as ReqwestClient;
+use serde_json::{Map, Value};
+use url::Url;
+
+use crate::error::RequestError;
+
+pub use cassette::CassetteTransport;
+pub use mock::MockTransport;
+#[cfg(test)]
+pub(crate) use mock::{test_client, test_config};
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// An HTTP request to the Sanity API
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub method: String,
+    pub url: Url,
+    pub headers: BTreeMap<String, String>,
+    pub body: Option<Vec<u8>>,
+}
+
+/// The status and body of a response
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+impl Request {
+    pub fn get(url: Url) -> Self {
+        Self {
+            method: "GET".to_string(),
+            url,
+            headers: BTreeMap::new(),
+            body: None,
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = Some(body.into());
+        self
+    }
+
+    /// The GROQ query and `$name` parameters carried in the URL, if any
+    pub fn groq(&self) -> Option<(String, Map<String, Value>)> {
+        let mut query = None;
+        let mut params = Map::new();
+        for (name, value) in self.url.query_pairs() {
+            if name == "query" {
+                query = Some(value.into_owned());
+            } else if let Some(name) = name.strip_prefix('$') {
+                let value =
+                    serde_json::from_str(&value).unwrap_or(Value::String(value.into_owned()));
+                params.insert(name.to_string(), value);
+            }
+        }
+        query.map(|query| (query, params))
+    }
+}
+
+/// A response body read chunk by chunk
+pub trait ChunkStream: Send {
+    /// The next chunk of the body, `None` once it has been read
+    fn next_chunk(&mut self) -> BoxFuture<'_, Result<Option<Vec<u8>>, RequestError>>;
+}
+
+/// A response whose body has not been read yet
+pub struct StreamingResponse {
+    pub status: u16,
+    pub body: Box<dyn ChunkStream>,
+}
+
+impl StreamingResponse {
+    /// Read the whole body
+    pub async fn bytes(mut self) -> Result<Vec<u8>, RequestError> {
+        let mut bytes = Vec::new();
+        while let Some(chunk) = self.body.next_chunk().await? {
+            bytes.extend(chunk);
+        }
+        Ok(bytes)
+    }
+}
+
+/// A body which is already in memory, served in chunks of at most `chunk_size` bytes
+pub struct BufferedBody {
+    bytes: Vec<u8>,
+    chunk_size: usize,
+}
+
+impl BufferedBody {
+    pub fn new(bytes: Vec<u8>, chunk_size: usize) -> Self {
+        Self {
+            bytes,
+            chunk_size: chunk_size.max(1),
+        }
+    }
+}
+
+impl ChunkStream for BufferedBody {
+    fn next_chunk(&mut self) -> BoxFuture<'_, Result<Option<Vec<u8>>, RequestError>> {
+        let chunk = if self.bytes.is_empty() {
+            None
+        } else {
+            let rest = self.bytes.split_off(self.chunk_size.min(self.bytes.len()));
+            Some(std::mem::replace(&mut self.bytes, rest))
+        };
+        Box::pin(async move { Ok(chunk) })
+    }
+}
+
+pub trait Transport: Send + Sync {
+    fn send(&self, request: Request) -> BoxFuture<'_, Result<Response, RequestError>>;
+
+    /// Send a request and read the response as it arrives, e.g. for exports and assets
+    ///
+    /// By default the response of `send` is served as a single chunk.
+    fn stream(&self, request: Request) -> BoxFuture<'_, Result<StreamingResponse, RequestError>> {
+        Box::pin(async move {
+            let response = self.send(request).await?;
+            let size = response.body.len();
+            Ok(StreamingResponse {
+                status: response.status,
+                body: Box::new(BufferedBody::new(response.body.into_bytes(), size)),
+            })
+        })
+    }
+}
+
+/// Sends requests over HTTP with `reqwest`
+#[derive(Debug, Clone, Default)]
+pub struct ReqwestTransport {
+    client: ReqwestClient,
+}
+
+impl ReqwestTransport {
+    pub fn new(client: ReqwestClient) -> Self {
+        Self { client }
+    }
+}
+
+impl ReqwestTransport {
+    async fn response(&self, request: Request) -> Result<reqwest::Response, RequestError> {
+        let method = reqwest::Method::from_bytes(request.method.as_bytes())
+            .map_err(|e| RequestError::TransportError(e.to_string()))?;
+        let mut builder = self.client.request(method, request.url);
+        for (name, value) in &request.headers {
+            builder = builder.header(name, value);
+        }
+        if let Some(body) = request.body {
+            builder = builder.body(body);
+        }
+        Ok(builder.send().await?)
+    }
+}
+
+struct ReqwestBody(reqwest::Response);
+
+impl ChunkStream for ReqwestBody {
+    fn next_chunk(&mut self) -> BoxFuture<'_, Result<Option<Vec<u8>>, RequestError>> {
+        Box::pin(async move { Ok(self.0.chunk().await?.map(|chunk| chunk.to_vec())) })
+    }
+}
+
+impl Transport for ReqwestTransport {
+    fn send(&self, request: Request) -> BoxFuture<'_, Result<Response, RequestError>> {
+        Box::pin(async move {
+            let response = self.response(request).await?;
+            let status = response.status().as_u16();
+            let body = response.text().await?;
+            Ok(Response { status, body })
+        })
+    }
+
+    fn stream(&self, request: Request) -> BoxFuture<'_, Result<StreamingResponse, RequestError>> {
+        Box::pin(async move {
+            let response = self.response(request).await?;
+            Ok(StreamingResponse {
+                status: response.status().as_u16(),
+                body: Box::new(ReqwestBody(response)),
+            })
+        })
+    }
+}
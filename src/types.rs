@@ -0,0 +1,364 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A calendar date as stored by the `date` field type, e.g. `2024-05-01`
+pub type Date = chrono::NaiveDate;
+
+/// A UTC timestamp as stored by the `datetime` field type and the system timestamps
+pub type DateTime = chrono::DateTime<chrono::Utc>;
+
+/// A document with its system fields
+///
+/// The document specific fields are flattened into `fields`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Document<T> {
+    #[serde(rename = "_id")]
+    pub id: String,
+    #[serde(rename = "_type")]
+    pub type_name: String,
+    #[serde(rename = "_rev")]
+    pub rev: String,
+    #[serde(rename = "_createdAt")]
+    pub created_at: DateTime,
+    #[serde(rename = "_updatedAt")]
+    pub updated_at: DateTime,
+    #[serde(flatten)]
+    pub fields: T,
+}
+
+/// A reference to another document
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Reference {
+    #[serde(rename = "_ref")]
+    pub reference: String,
+    #[serde(rename = "_type", default = "Reference::type_name")]
+    pub type_name: String,
+    #[serde(rename = "_key", default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    #[serde(rename = "_weak", default, skip_serializing_if = "Option::is_none")]
+    pub weak: Option<bool>,
+    #[serde(
+        rename = "_strengthenOnPublish",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub strengthen_on_publish: Option<StrengthenOnPublish>,
+}
+
+impl Reference {
+    /// Create a strong reference to the document with the given id
+    pub fn new(id: &str) -> Self {
+        Self {
+            reference: id.to_string(),
+            type_name: Self::type_name(),
+            key: None,
+            weak: None,
+            strengthen_on_publish: None,
+        }
+    }
+
+    /// Create a weak reference to the document with the given id
+    pub fn weak(id: &str) -> Self {
+        Self {
+            weak: Some(true),
+            ..Self::new(id)
+        }
+    }
+
+    /// Whether the reference is allowed to point to a missing document
+    pub fn is_weak(&self) -> bool {
+        self.weak.unwrap_or(false)
+    }
+
+    fn type_name() -> String {
+        "reference".to_string()
+    }
+}
+
+/// Marks a weak reference that becomes strong once the referenced document is published
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StrengthenOnPublish {
+    #[serde(rename = "type")]
+    pub type_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weak: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<Template>,
+}
+
+/// Initial value template used when strengthening a reference
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Template {
+    pub id: String,
+    #[serde(default)]
+    pub params: serde_json::Map<String, Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Slug {
+    #[serde(rename = "_type", default = "Slug::type_name")]
+    pub type_name: String,
+    pub current: String,
+}
+
+impl Slug {
+    pub fn new(current: &str) -> Self {
+        Self {
+            type_name: Self::type_name(),
+            current: current.to_string(),
+        }
+    }
+
+    fn type_name() -> String {
+        "slug".to_string()
+    }
+}
+
+/// An image field with its asset reference and optional crop and hotspot
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Image {
+    #[serde(rename = "_type", default = "Image::type_name")]
+    pub type_name: String,
+    #[serde(rename = "_key", default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asset: Option<Reference>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crop: Option<ImageCrop>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hotspot: Option<ImageHotspot>,
+}
+
+impl Image {
+    fn type_name() -> String {
+        "image".to_string()
+    }
+}
+
+/// Crop rectangle, each side given as a fraction of the image size
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ImageCrop {
+    pub top: f64,
+    pub bottom: f64,
+    pub left: f64,
+    pub right: f64,
+}
+
+/// Focal area of an image, relative to the image size
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ImageHotspot {
+    pub x: f64,
+    pub y: f64,
+    pub height: f64,
+    pub width: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct File {
+    #[serde(rename = "_type", default = "File::type_name")]
+    pub type_name: String,
+    #[serde(rename = "_key", default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asset: Option<Reference>,
+}
+
+impl File {
+    fn type_name() -> String {
+        "file".to_string()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Geopoint {
+    #[serde(rename = "_type", default = "Geopoint::type_name")]
+    pub type_name: String,
+    pub lat: f64,
+    pub lng: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alt: Option<f64>,
+}
+
+impl Geopoint {
+    fn type_name() -> String {
+        "geopoint".to_string()
+    }
+}
+
+/// A run of text inside a Portable Text block
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Span {
+    #[serde(rename = "_type", default = "Span::type_name")]
+    pub type_name: String,
+    #[serde(rename = "_key", default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    pub text: String,
+    #[serde(default)]
+    pub marks: Vec<String>,
+}
+
+impl Span {
+    fn type_name() -> String {
+        "span".to_string()
+    }
+}
+
+/// A Portable Text block
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Block {
+    #[serde(rename = "_type", default = "Block::type_name")]
+    pub type_name: String,
+    #[serde(rename = "_key", default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    #[serde(default = "Block::style")]
+    pub style: String,
+    #[serde(default)]
+    pub children: Vec<Span>,
+    #[serde(rename = "markDefs", default)]
+    pub mark_defs: Vec<Value>,
+    #[serde(rename = "listItem", default, skip_serializing_if = "Option::is_none")]
+    pub list_item: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub level: Option<u32>,
+}
+
+impl Block {
+    fn type_name() -> String {
+        "block".to_string()
+    }
+
+    fn style() -> String {
+        "normal".to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Debug, Deserialize)]
+    struct Post {
+        title: String,
+        slug: Slug,
+        author: Reference,
+        #[serde(rename = "mainImage")]
+        main_image: Image,
+        #[serde(rename = "publishedAt")]
+        published_at: Date,
+        location: Geopoint,
+    }
+
+    #[test]
+    fn deserialize_document() {
+        let value = json!({
+            "_id": "09139a58-311b-4779-8fa4-723f19242a8e",
+            "_type": "post",
+            "_rev": "r4nd0m",
+            "_createdAt": "2024-11-24T10:03:41Z",
+            "_updatedAt": "2024-11-25T08:00:00Z",
+            "title": "Hello",
+            "slug": { "_type": "slug", "current": "hello" },
+            "author": { "_type": "reference", "_ref": "author-1" },
+            "mainImage": {
+                "_type": "image",
+                "asset": { "_type": "reference", "_ref": "image-abc-200x100-png" },
+                "crop": { "top": 0.1, "bottom": 0.0, "left": 0.0, "right": 0.2 },
+                "hotspot": { "x": 0.5, "y": 0.5, "height": 0.3, "width": 0.3 }
+            },
+            "publishedAt": "2024-11-24",
+            "location": { "_type": "geopoint", "lat": 59.91, "lng": 10.75 }
+        });
+        let document: Document<Post> = serde_json::from_value(value).unwrap();
+        assert_eq!(document.id, "09139a58-311b-4779-8fa4-723f19242a8e");
+        assert_eq!(document.type_name, "post");
+        assert_eq!(
+            document.created_at.to_rfc3339(),
+            "2024-11-24T10:03:41+00:00"
+        );
+        assert_eq!(document.fields.title, "Hello");
+        assert_eq!(document.fields.slug.current, "hello");
+        assert_eq!(document.fields.author, Reference::new("author-1"));
+        assert_eq!(
+            document.fields.main_image.asset.unwrap().reference,
+            "image-abc-200x100-png"
+        );
+        assert_eq!(document.fields.main_image.crop.unwrap().right, 0.2);
+        assert_eq!(document.fields.main_image.hotspot.unwrap().x, 0.5);
+        assert_eq!(document.fields.published_at.to_string(), "2024-11-24");
+        assert_eq!(document.fields.location.alt, None);
+    }
+
+    #[test]
+    fn serialize_keeps_type() {
+        let image: Image = serde_json::from_value(json!({
+            "asset": { "_ref": "image-abc-200x100-png" }
+        }))
+        .unwrap();
+        assert_eq!(
+            serde_json::to_value(&image).unwrap(),
+            json!({
+                "_type": "image",
+                "asset": { "_type": "reference", "_ref": "image-abc-200x100-png" }
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(Slug::new("hello")).unwrap(),
+            json!({ "_type": "slug", "current": "hello" })
+        );
+        let file =
+            json!({ "_type": "pdf", "asset": { "_type": "reference", "_ref": "file-abc-pdf" } });
+        let parsed: File = serde_json::from_value(file.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), file);
+        let point: Geopoint =
+            serde_json::from_value(json!({ "lat": 59.91, "lng": 10.75 })).unwrap();
+        assert_eq!(
+            serde_json::to_value(&point).unwrap(),
+            json!({ "_type": "geopoint", "lat": 59.91, "lng": 10.75 })
+        );
+    }
+
+    #[test]
+    fn weak_reference() {
+        let value = json!({
+            "_type": "reference",
+            "_ref": "author-1",
+            "_key": "k1",
+            "_weak": true,
+            "_strengthenOnPublish": { "type": "author", "template": { "id": "author", "params": {} } }
+        });
+        let reference: Reference = serde_json::from_value(value.clone()).unwrap();
+        assert!(reference.is_weak());
+        assert_eq!(reference.key.as_deref(), Some("k1"));
+        let strengthen = reference.strengthen_on_publish.as_ref().unwrap();
+        assert_eq!(strengthen.type_name, "author");
+        assert_eq!(strengthen.template.as_ref().unwrap().id, "author");
+        assert_eq!(serde_json::to_value(&reference).unwrap(), value);
+        assert!(!Reference::new("author-1").is_weak());
+        assert!(Reference::weak("author-1").is_weak());
+    }
+
+    #[test]
+    fn deserialize_block() {
+        let value = json!({
+            "_type": "block",
+            "_key": "b1",
+            "children": [
+                { "_type": "span", "_key": "s1", "text": "Hello ", "marks": [] },
+                { "_type": "span", "_key": "s2", "text": "world", "marks": ["strong", "l1"] }
+            ],
+            "markDefs": [{ "_type": "link", "_key": "l1", "href": "https://sanity.io" }],
+            "listItem": "bullet",
+            "level": 1
+        });
+        let block: Block = serde_json::from_value(value).unwrap();
+        assert_eq!(block.type_name, "block");
+        assert_eq!(block.style, "normal");
+        assert_eq!(block.children.len(), 2);
+        assert_eq!(block.children[1].type_name, "span");
+        assert_eq!(block.children[1].marks, vec!["strong", "l1"]);
+        assert_eq!(block.mark_defs[0]["href"], "https://sanity.io");
+        assert_eq!(block.list_item.as_deref(), Some("bullet"));
+        assert_eq!(block.level, Some(1));
+    }
+}
@@ -1,14 +1,11 @@
 #![allow(dead_code)]
 use std::fmt::Display;
 
-use crate::error::URLError;
+use crate::error::{ParseError, URLError};
+use crate::groq::parse;
+use serde_json::{Map, Value};
 use url::Url;
 
-pub struct Query {
-    condition: String,
-    body: String,
-}
-
 #[derive(Debug)]
 pub struct SanityURL {
     project_id: String,
@@ -65,36 +62,101 @@ impl SanityURL {
     }
 
     pub fn build(&mut self) -> Result<Url, URLError> {
+        let path = format!("data/query/{}", self.dataset);
+        self.endpoint(&path)
+    }
+
+    /// Url of any API endpoint, e.g. `data/export/production`
+    pub fn endpoint(&mut self, path: &str) -> Result<Url, URLError> {
         let url = Url::parse(&format!(
-            "https://{}.{}/{}/data/query/{}",
-            self.project_id, self.host, self.api_version, self.dataset,
+            "https://{}.{}/{}/{}",
+            self.project_id, self.host, self.api_version, path,
         ))
         .map_err(URLError::InvalidURL)?;
         Ok(url)
     }
 
-    pub fn query(url: &mut Url, query: &str) {
-        if query.is_empty() || query.len() < 3 {
+    /// Validate a GROQ query and set it as the `query` parameter, as written apart from
+    /// whitespace
+    pub fn query(url: &mut Url, query: &str) -> Result<(), ParseError> {
+        if query.trim().is_empty() {
             url.set_query(None);
-            return;
+            return Ok(());
         }
-        let cond_start = query.find("*[").unwrap_or(0) + 2;
-        let cond_end = query.find("]").unwrap_or(0);
-        let condition = query[cond_start..cond_end].to_string();
-        let mut body = query[cond_end + 1..]
-            .to_string()
-            .split_whitespace()
-            .collect::<String>();
-        if condition.is_empty() {
-            url.set_query(None);
-            return;
+        parse(query)?;
+        url.set_query(Some(&format!("query={}", compact(query))));
+        Ok(())
+    }
+
+    /// Set the query verbatim along with its parameters, sent as `$name=<json>` pairs
+    pub fn query_with_params(url: &mut Url, query: &str, params: &Map<String, Value>) {
+        let mut pairs = vec![format!("query={}", escape(query.trim()))];
+        for (name, value) in params {
+            pairs.push(format!("${}={}", escape(name), escape(&value.to_string())));
+        }
+        url.set_query(Some(&pairs.join("&")));
+    }
+}
+
+/// Drop comments and whitespace around punctuation and collapse the rest, string literals
+/// are kept
+fn compact(query: &str) -> String {
+    const PUNCTUATION: &[char] = &['{', '}', '[', ']', '(', ')', ','];
+    let mut result = String::with_capacity(query.len());
+    let mut quote = None;
+    let mut escaped = false;
+    let mut space = false;
+    let mut chars = query.chars().peekable();
+    while let Some(c) = chars.next() {
+        if let Some(open) = quote {
+            result.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == open {
+                quote = None;
+            }
+            continue;
+        }
+        if c == '/' && chars.peek() == Some(&'/') {
+            // A line comment runs to the end of the line, which is whitespace
+            for c in chars.by_ref() {
+                if c == '\n' {
+                    break;
+                }
+            }
+            space = true;
+            continue;
+        }
+        if c.is_whitespace() {
+            space = true;
+            continue;
+        }
+        if space
+            && !PUNCTUATION.contains(&c)
+            && result
+                .chars()
+                .last()
+                .is_some_and(|last| !PUNCTUATION.contains(&last))
+        {
+            result.push(' ');
         }
-        if body.is_empty() {
-            body = "".to_string();
+        space = false;
+        if c == '"' || c == '\'' {
+            quote = Some(c);
         }
-        let query = format!("*[{}]{}", condition, body);
-        url.set_query(Some(&format!("query={}", query)));
+        result.push(c);
     }
+    result
+}
+
+/// Escape the characters `Url::set_query` leaves alone but which break up a query pair
+fn escape(component: &str) -> String {
+    component
+        .replace('%', "%25")
+        .replace('&', "%26")
+        .replace('+', "%2B")
 }
 
 #[cfg(test)]
@@ -132,7 +194,7 @@ mod test {
             .api_version(&"v2022-03-07".to_string())
             .host("api.sanity.io".to_string())
             .build()?;
-        SanityURL::query(&mut sanity_url, query);
+        SanityURL::query(&mut sanity_url, query).unwrap();
         assert_eq!(
             sanity_url.as_str(),
             "https://abc123.api.sanity.io/v2022-03-07/data/query/production?query=*[_id%20==%20%2209139a58-311b-4779-8fa4-723f19242a8e%22]{_id,_type,_createdAt,_updatedAt}"
@@ -154,7 +216,7 @@ mod test {
             .api_version(&"v2023-01-01".to_string())
             .host("api.sanity.io".to_string())
             .build()?;
-        SanityURL::query(&mut sanity_url, query);
+        SanityURL::query(&mut sanity_url, query).unwrap();
         assert_eq!(
             sanity_url.as_str(),
                 "https://abc123.api.sanity.io/v2023-01-01/data/query/blog?query=*[type%20==%20%22post%22%20&&%20published%20==%20true]{title,author,categories[]-%3Etitle}"
@@ -170,7 +232,7 @@ mod test {
             .api_version(&"v2023-05-01".to_string())
             .host("api.sanity.io".to_string())
             .build()?;
-        SanityURL::query(&mut sanity_url, "");
+        SanityURL::query(&mut sanity_url, "").unwrap();
         assert_eq!(
             sanity_url.as_str(),
             "https://xyz456.api.sanity.io/v2023-05-01/data/query/test"
@@ -191,7 +253,7 @@ mod test {
             .api_version(&"v2023-05-01".to_string())
             .host("api.sanity.io".to_string())
             .build()?;
-        SanityURL::query(&mut sanity_url, query);
+        SanityURL::query(&mut sanity_url, query).unwrap();
         assert_eq!(
             sanity_url.as_str(),
              "https://abc123.api.sanity.io/v2023-05-01/data/query/store?query=*[name%20==%20%22O%27Reilly%22%20&&%20price%20%3C%20100.0]{name,price}",
@@ -199,6 +261,29 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn query_with_params() -> Result<(), URLError> {
+        let mut sanity_url = SanityURL::new()
+            .project_id(&"abc123".to_string())
+            .dataset(&"blog".to_string())
+            .api_version(&"v2023-05-01".to_string())
+            .host("api.sanity.io".to_string())
+            .build()?;
+        let mut params = Map::new();
+        params.insert("p0".to_string(), Value::from("post"));
+        params.insert("p1".to_string(), Value::from("a&b=c+d"));
+        SanityURL::query_with_params(
+            &mut sanity_url,
+            "*[_type == $p0 && title == $p1] | order(publishedAt desc)",
+            &params,
+        );
+        assert_eq!(
+            sanity_url.as_str(),
+            "https://abc123.api.sanity.io/v2023-05-01/data/query/blog?query=*[_type%20==%20$p0%20%26%26%20title%20==%20$p1]%20|%20order(publishedAt%20desc)&$p0=%22post%22&$p1=%22a%26b=c%2Bd%22"
+        );
+        Ok(())
+    }
+
     #[test]
     fn one_line_query() -> Result<(), URLError> {
         let query = r#"*[_type == "post"]{title, author}"#;
@@ -208,11 +293,46 @@ mod test {
             .api_version(&"v2023-05-01".to_string())
             .host("api.sanity.io".to_string())
             .build()?;
-        SanityURL::query(&mut sanity_url, query);
+        SanityURL::query(&mut sanity_url, query).unwrap();
         assert_eq!(
             sanity_url.as_str(),
             "https://abc123.api.sanity.io/v2023-05-01/data/query/blog?query=*[_type%20==%20%22post%22]{title,author}"
         );
         Ok(())
     }
+
+    #[test]
+    fn query_keeps_literals() -> Result<(), URLError> {
+        let query = "*[title == \"a  b\" && rating > 4.50]\n  | order(publishedAt desc) [0...2]";
+        let mut sanity_url = SanityURL::new()
+            .project_id(&"abc123".to_string())
+            .dataset(&"blog".to_string())
+            .api_version(&"v2023-05-01".to_string())
+            .host("api.sanity.io".to_string())
+            .build()?;
+        SanityURL::query(&mut sanity_url, query).unwrap();
+        assert_eq!(
+            sanity_url.as_str(),
+            "https://abc123.api.sanity.io/v2023-05-01/data/query/blog?query=*[title%20==%20%22a%20%20b%22%20&&%20rating%20%3E%204.50]|%20order(publishedAt%20desc)[0...2]"
+        );
+        assert!(SanityURL::query(&mut sanity_url, "*[title ==]").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn query_drops_comments() -> Result<(), URLError> {
+        let query = "*[_type == \"post\" && url == \"https://a.b\"] // posts\n{title} // done";
+        let mut sanity_url = SanityURL::new()
+            .project_id(&"abc123".to_string())
+            .dataset(&"blog".to_string())
+            .api_version(&"v2023-05-01".to_string())
+            .host("api.sanity.io".to_string())
+            .build()?;
+        SanityURL::query(&mut sanity_url, query).unwrap();
+        assert_eq!(
+            sanity_url.as_str(),
+            "https://abc123.api.sanity.io/v2023-05-01/data/query/blog?query=*[_type%20==%20%22post%22%20&&%20url%20==%20%22https://a.b%22]{title}"
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,54 @@
+use sanity_rs::codegen::{generate, schema::Schema};
+use serde_json::json;
+
+#[allow(dead_code)]
+mod blog {
+    include!("../fixtures/codegen/blog.rs");
+}
+
+#[test]
+fn generated_code_matches_fixture() {
+    let schema = Schema::from_json(include_str!("../fixtures/codegen/blog.schema.json")).unwrap();
+    assert_eq!(
+        generate(&schema),
+        include_str!("../fixtures/codegen/blog.rs")
+    );
+}
+
+#[test]
+fn deserialize_through_generated_code() {
+    let document = json!({
+        "_id": "post-1",
+        "_type": "post",
+        "_createdAt": "2024-01-01T10:00:00Z",
+        "title": "Hello",
+        "slug": {"_type": "slug", "current": "hello"},
+        "status": "in-review",
+        "body": [
+            {"_key": "a", "_type": "block", "children": [{"_type": "span", "text": "Hi"}]},
+            {"_key": "b", "_type": "image", "alt": "A cat", "asset": {"_ref": "image-abc-10x20-png", "_type": "reference"}},
+            {"_type": "code", "language": "rust", "code": "fn main() {}"}
+        ],
+        "extras": [{"_type": "code", "code": "ls"}, ["news", "rust"]]
+    });
+    let post: blog::Post = serde_json::from_value(document.clone()).unwrap();
+    assert_eq!(post.type_name, "post");
+    assert_eq!(post.status, blog::PostStatus::InReview);
+    let body = post.body.as_ref().unwrap();
+    assert!(matches!(&body[0], blog::BlockContentItem::Block(block) if block.type_name == "block"));
+    assert!(
+        matches!(&body[1], blog::BlockContentItem::Image(image) if image.alt.as_deref() == Some("A cat"))
+    );
+    assert!(matches!(&body[2], blog::BlockContentItem::Code(code) if code.code == "fn main() {}"));
+    assert_eq!(post.extras.as_ref().unwrap().len(), 2);
+
+    let value = serde_json::to_value(&post).unwrap();
+    assert_eq!(value["body"][0]["_type"], "block");
+    assert_eq!(value["body"][1]["_type"], "image");
+    assert_eq!(value["body"][2], document["body"][2]);
+    assert_eq!(value["extras"], document["extras"]);
+
+    let unknown = json!([{"_type": "video", "url": "https://example.com"}]);
+    let error = serde_json::from_value::<blog::BlockContent>(unknown).unwrap_err();
+    assert!(error.to_string().contains("unknown variant `video`"));
+}